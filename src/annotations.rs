@@ -0,0 +1,147 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+};
+
+use regex::Regex;
+
+/// Ordered from most to least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn parse(text: &str) -> Option<Severity> {
+        match text {
+            "error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "note" => Some(Severity::Note),
+            _ => None,
+        }
+    }
+
+    pub fn label_text(&self) -> String {
+        match self {
+            Severity::Error => "error".to_string(),
+            Severity::Warning => "warning".to_string(),
+            Severity::Note => "note".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// External feedback, e.g. lint warnings, attached to lines of the new version of files.
+#[derive(Debug, Default, Clone)]
+pub struct Annotations {
+    /// Annotations by path relative to the repository root, then by line number.
+    files: HashMap<String, BTreeMap<u32, Vec<Annotation>>>,
+    count: usize,
+}
+
+impl Annotations {
+    /// Parses one annotation per line, in the form compilers report diagnostics:
+    ///
+    /// ```text
+    /// path:line[:column]: severity[code]: message
+    /// ```
+    ///
+    /// `severity` is `error`, `warning` or `note`; the column and code are optional.
+    /// Paths under `root` are made relative to it. Lines in any other form are ignored,
+    /// so tool output can be used as is.
+    pub fn parse(text: &str, root: &str) -> Annotations {
+        let pattern = Regex::new(
+            r"^(?P<file>.+?):(?P<line>\d+):(?:\d+:)?\s*(?P<severity>[a-z]+)(?:\[[^\]]*\])?:\s*(?P<message>.*)$",
+        )
+        .expect("Annotation pattern should be valid");
+
+        let mut annotations = Annotations::default();
+        for line in text.lines() {
+            let Some(captures) = pattern.captures(line.trim_end()) else {
+                continue;
+            };
+            let (Ok(line), Some(severity)) = (
+                captures["line"].parse::<u32>(),
+                Severity::parse(&captures["severity"]),
+            ) else {
+                continue;
+            };
+
+            let file = captures["file"].replace('\\', "/");
+            let file = file
+                .strip_prefix(root)
+                .map(|file| file.trim_start_matches('/'))
+                .unwrap_or(&file)
+                .trim_start_matches("./")
+                .to_string();
+            annotations
+                .files
+                .entry(file)
+                .or_default()
+                .entry(line)
+                .or_default()
+                .push(Annotation {
+                    severity,
+                    message: captures["message"].to_string(),
+                });
+            annotations.count += 1;
+        }
+
+        annotations
+    }
+
+    pub fn for_file(&self, file_name: &str) -> BTreeMap<u32, Vec<Annotation>> {
+        self.files.get(file_name).cloned().unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+pub fn load(path: &Path, root: &str) -> io::Result<Annotations> {
+    Ok(Annotations::parse(&fs::read_to_string(path)?, root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics_are_parsed() {
+        let annotations = Annotations::parse(
+            "src/main.rs:12:5: warning: unused import\n\
+             /repo/src/git.rs:3: error[E0425]: cannot find value\n\
+             ./src/git.rs:3: note: defined here\n\
+             warning: 2 warnings emitted\n\
+             src/main.rs:1: hint: not a severity",
+            "/repo",
+        );
+
+        assert_eq!(annotations.len(), 3);
+        assert_eq!(
+            annotations.for_file("src/main.rs")[&12],
+            vec![Annotation {
+                severity: Severity::Warning,
+                message: "unused import".to_string(),
+            }]
+        );
+        let severities = annotations.for_file("src/git.rs")[&3]
+            .iter()
+            .map(|annotation| annotation.severity)
+            .collect::<Vec<Severity>>();
+        assert_eq!(severities, vec![Severity::Error, Severity::Note]);
+    }
+}
@@ -1,32 +1,470 @@
-use std::path::PathBuf;
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
+};
 
-use crate::git::{self, Diff, Stats};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::Annotations,
+    file_tree,
+    git::{
+        self, BranchStatus, CommitInfo, Diff, DiffParsingError, DiffSettings, FileLog, Permalink,
+        RevisionRange, SkippedFile, StashInfo, Stats,
+    },
+    moves,
+    patch::{self, PatchError},
+    search::{FileMatches, SearchQuery},
+    session::RecentProjects,
+    settings::{IgnoredPaths, Settings},
+};
+
+/// How many commits the log panel and the history of a file list.
+pub const LOG_LENGTH: usize = 200;
 
 #[derive(Default)]
 pub struct ControlData {
     pub show_err_dialog: bool,
     pub error_information: String,
+    /// Kept after the error dialog is closed, for the diagnostics in the about window.
+    pub last_error: Option<String>,
+    pub show_about: bool,
+    pub show_paste_patch: bool,
+    pub show_settings: bool,
+    pub show_debug_overlay: bool,
+    pub settings: Settings,
+    pub recent_projects: RecentProjects,
+    pub show_log: bool,
+    pub show_search: bool,
+    pub search_query: SearchQuery,
+    /// Why the search query couldn't be used, e.g. an invalid regex.
+    pub search_error: Option<String>,
+    pub search_results: Vec<FileMatches>,
+    /// Number of searches started, used to tell their results apart.
+    pub search_count: usize,
+    /// Stops the running search, once set when it is replaced.
+    pub search_cancel: Arc<AtomicBool>,
+    pub show_find: bool,
+    /// What's looked for in the shown file, apart from the search of all files.
+    pub find_query: SearchQuery,
+    /// Index of the match of `find_query` that was last jumped to.
+    pub find_match: usize,
     pub diff_type: DiffType,
     pub selected_diff_index: usize,
+    pub files_collapsed: bool,
+    /// Only lists the files matching it, see `search::file_filter`.
+    pub file_filter: String,
+    /// Only show per-file stats, without rendering any diff.
+    pub stats_only: bool,
+    /// Files marked as viewed, by diff type and name.
+    pub viewed: HashSet<(DiffType, String)>,
+    /// Show every file's diff one after the other instead of only the selected one.
+    pub gallery: bool,
+    /// Kept while switching files and reloading.
+    pub view_mode: ViewMode,
+    /// List the files matching the ignored paths after the others.
+    pub show_ignored: bool,
+    pub file_times: FileTimes,
+    /// The line being blamed, if its popup is open.
+    pub blame: Option<LineBlame>,
+    /// The file whose history is listed, if its window is open.
+    pub file_history: Option<FileHistory>,
+    /// Loaded lint or test results, shown next to the lines they refer to.
+    pub annotations: Annotations,
+    /// Diffs whose lines are being computed on a background thread.
+    pub loading_diffs: HashSet<(DiffType, usize)>,
+    pub secondary_diff_index: Option<usize>,
+    pub scroll_to_row: Option<usize>,
+    pub history: History,
+    /// The listing running on a background thread, if any.
+    pub load: Option<Load>,
+    /// Number of listings started, used to tell their results apart.
+    pub load_count: usize,
+    /// Files listed out of all changed files, while a slow listing is running.
+    pub load_progress: Option<(usize, usize)>,
+}
+
+impl ControlData {
+    /// Names of the files of `diff_type` marked as viewed.
+    pub fn viewed_files(&self, diff_type: &DiffType) -> HashSet<String> {
+        self.viewed
+            .iter()
+            .filter(|(viewed_type, _)| viewed_type == diff_type)
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// Indices of `diffs` in the order the file list shows them.
+    pub fn listed_order(&self, diffs: &[Diff]) -> Vec<usize> {
+        file_tree::listed_order(diffs, &self.settings, &self.file_filter)
+    }
+}
+
+/// A repository or directory listing, which can be cancelled.
+pub struct Load {
+    pub id: usize,
+    pub cancel: Arc<AtomicBool>,
+    /// What the listing is doing, shown until it reports its progress.
+    pub status: &'static str,
+}
+
+impl Load {
+    /// The status of opening a repository, which can be slow by itself on network drives.
+    pub const OPENING: &'static str = "Opening…";
+}
+
+/// A place in the diffs that navigation can return to.
+#[derive(Clone, PartialEq)]
+pub struct Location {
+    pub diff_type: DiffType,
+    pub diff_index: usize,
+    /// Row at the top of the file pane.
+    pub row: usize,
+}
+
+/// The files in the panes and the scroll position, taken before a refresh so they can be
+/// restored by file name rather than by index.
+pub struct ViewSnapshot {
+    selected: Option<Diff>,
+    secondary: Option<Diff>,
+    /// The files marked as viewed, which stay so unless their content changes.
+    viewed: Vec<(DiffType, Diff)>,
+    /// Row at the top of the first pane.
+    row: usize,
+}
+
+impl ViewSnapshot {
+    pub fn take(app_data: &AppData, control_data: &ControlData, row: usize) -> ViewSnapshot {
+        let diffs = &app_data.diff_data(&control_data.diff_type).diffs;
+        ViewSnapshot {
+            selected: diffs.get(control_data.selected_diff_index).cloned(),
+            secondary: control_data
+                .secondary_diff_index
+                .and_then(|i| diffs.get(i))
+                .cloned(),
+            viewed: [DiffType::Modified, DiffType::Staged, DiffType::All]
+                .into_iter()
+                .flat_map(|diff_type| {
+                    let viewed = control_data.viewed_files(&diff_type);
+                    app_data
+                        .diff_data(&diff_type)
+                        .diffs
+                        .iter()
+                        .filter(move |diff| viewed.contains(&diff.file_name()))
+                        .map(move |diff| (diff_type.clone(), diff.clone()))
+                        .collect::<Vec<(DiffType, Diff)>>()
+                })
+                .collect(),
+            row,
+        }
+    }
+
+    /// Selects the snapshot's files again in the refreshed `app_data`. The scroll position
+    /// is only kept if the selected file didn't change.
+    pub fn restore(self, app_data: &AppData, control_data: &mut ControlData) {
+        let diffs = &app_data.diff_data(&control_data.diff_type).diffs;
+        let find = |snapshot: &Option<Diff>| {
+            let snapshot = snapshot.as_ref()?;
+            diffs
+                .iter()
+                .position(|diff| diff.file_name() == snapshot.file_name())
+                .map(|i| (i, diffs[i].has_same_content(snapshot)))
+        };
+
+        match find(&self.selected) {
+            Some((i, unchanged)) => {
+                control_data.selected_diff_index = i;
+                control_data.scroll_to_row = Some(if unchanged { self.row } else { 0 });
+            }
+            None => {
+                control_data.selected_diff_index = control_data
+                    .selected_diff_index
+                    .min(diffs.len().saturating_sub(1));
+                control_data.scroll_to_row = Some(0);
+            }
+        }
+        control_data.secondary_diff_index = find(&self.secondary).map(|(i, _)| i);
+
+        control_data.viewed = self
+            .viewed
+            .into_iter()
+            .filter(|(diff_type, viewed)| {
+                app_data.diff_data(diff_type).diffs.iter().any(|diff| {
+                    diff.file_name() == viewed.file_name() && diff.has_same_content(viewed)
+                })
+            })
+            .map(|(diff_type, diff)| (diff_type, diff.file_name()))
+            .collect();
+    }
+
+    /// Whether `new` lists the same changes as `old`, only more recently.
+    pub fn is_refresh(old: &AppData, new: &AppData) -> bool {
+        old.project_path == new.project_path
+            && old.compared_directory == new.compared_directory
+            && old.commit.as_ref().map(|commit| &commit.id)
+                == new.commit.as_ref().map(|commit| &commit.id)
+            && old.stash.as_ref().map(|stash| stash.index)
+                == new.stash.as_ref().map(|stash| stash.index)
+            && old.revisions.as_ref().map(RevisionRange::notation)
+                == new.revisions.as_ref().map(RevisionRange::notation)
+            && !old.pasted
+            && !new.pasted
+    }
+}
+
+/// Browser-style back and forward history of the visited locations.
+#[derive(Default)]
+pub struct History {
+    back: Vec<Location>,
+    forward: Vec<Location>,
+}
+
+impl History {
+    /// Oldest locations are forgotten past this many.
+    const LIMIT: usize = 100;
+
+    /// Records `current` as being left for a new location, dropping the forward history.
+    pub fn push(&mut self, current: Location) {
+        self.forward.clear();
+        if self.back.last() != Some(&current) {
+            self.back.push(current);
+        }
+        if self.back.len() > History::LIMIT {
+            self.back.remove(0);
+        }
+    }
+
+    /// Steps back from `current`, which can then be returned to with [`History::go_forward`].
+    pub fn go_back(&mut self, current: Location) -> Option<Location> {
+        let location = self.back.pop()?;
+        self.forward.push(current);
+        Some(location)
+    }
+
+    pub fn go_forward(&mut self, current: Location) -> Option<Location> {
+        let location = self.forward.pop()?;
+        self.back.push(current);
+        Some(location)
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.back.clear();
+        self.forward.clear();
+    }
+}
+
+/// The commit that last changed a line, shown in a popup.
+pub struct LineBlame {
+    pub file_name: String,
+    pub line_no: u32,
+    pub state: BlameState,
+}
+
+pub enum BlameState {
+    Loading,
+    /// The line was changed after the last commit.
+    Uncommitted,
+    Commit(CommitInfo),
+    Failed(String),
+}
+
+/// The commits that changed a file, shown in a window to pick two of them to compare.
+pub struct FileHistory {
+    pub file_name: String,
+    /// Whether the commits from before the file was renamed are listed too.
+    pub follow_renames: bool,
+    pub state: FileHistoryState,
+}
+
+pub enum FileHistoryState {
+    Loading,
+    Commits(FileLog),
+    Failed(String),
+}
+
+/// When each changed file was last touched, in seconds since the epoch.
+#[derive(Default, Clone)]
+pub struct FileTimes {
+    pub committed: HashMap<String, i64>,
+    pub modified: HashMap<String, i64>,
+}
+
+impl FileTimes {
+    /// Working tree changes are dated by file modification, staged ones by the last commit.
+    pub fn for_diff_type(&self, diff_type: &DiffType) -> &HashMap<String, i64> {
+        match diff_type {
+            DiffType::Modified | DiffType::All => &self.modified,
+            DiffType::Staged => &self.committed,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct AppData {
     pub project_path: String,
+    /// Which worktree is open, if the repository has several.
+    pub worktree: Option<String>,
+    /// The checked out branch, for the window title.
+    pub branch: Option<String>,
+    /// Links to the commit shown, which working-tree and stash changes aren't in.
+    pub permalink: Option<Permalink>,
+    /// The old side when comparing two directories outside of git, `project_path` being the new one.
+    pub compared_directory: Option<String>,
+    /// The diffs were read from a pasted patch, so there are no files behind them.
+    pub pasted: bool,
+    /// The commit the working tree is compared with, `None` before the first commit or when
+    /// something else is shown.
+    pub head: Option<CommitInfo>,
+    /// How the checked out branch compares to its upstream, when the working tree is shown.
+    pub branch_status: Option<BranchStatus>,
+    /// The commit whose changes are shown instead of the working tree's, if any.
+    pub commit: Option<CommitInfo>,
+    /// The stash compared to the working tree, if any.
+    pub stash: Option<StashInfo>,
+    /// The two revisions compared with each other, if any.
+    pub revisions: Option<RevisionRange>,
+    /// The repository's stashes, which can be compared to the working tree.
+    pub stashes: Vec<StashInfo>,
+    /// The most recent commits on HEAD, which can be shown from the log panel.
+    pub log: Vec<CommitInfo>,
     pub modified_diff_data: DiffData,
     pub staged_diff_data: DiffData,
+    /// Staged and modified changes together, compared to HEAD.
+    pub combined_diff_data: DiffData,
+    /// Time spent loading and parsing the diffs.
+    pub load_duration: Duration,
 }
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct DiffData {
     pub diffs: Vec<Diff>,
     pub stats: Stats,
+    /// Files that couldn't be read while listing the changes.
+    pub skipped: Vec<SkippedFile>,
+    /// Files matching the ignored paths, left out of `diffs` unless they are shown.
+    pub ignored: Vec<Diff>,
 }
 
-#[derive(PartialEq, Clone, Default)]
+impl DiffData {
+    /// Moves the files `ignored_paths` matches out of `diffs` and the stats. When
+    /// `show_ignored`, they are listed after the other files instead, so that showing and
+    /// hiding them keeps the indices of the others.
+    pub fn apply_ignores(&mut self, ignored_paths: &IgnoredPaths, show_ignored: bool) {
+        let mut diffs = std::mem::take(&mut self.diffs);
+        for diff in self.ignored.drain(..) {
+            if !diffs
+                .iter()
+                .any(|shown| shown.file_name() == diff.file_name())
+            {
+                diffs.push(diff);
+            }
+        }
+
+        let (ignored, mut diffs): (Vec<Diff>, Vec<Diff>) = diffs
+            .into_iter()
+            .partition(|diff| ignored_paths.matches(&diff.file_name()));
+        if show_ignored {
+            diffs.extend(ignored.iter().cloned());
+        }
+        self.stats = Stats::of(&diffs);
+        self.diffs = diffs;
+        self.ignored = ignored;
+    }
+
+    /// Links the blocks moved between files once every file is loaded, or unlinks them
+    /// when `enabled` is off; see [`moves::detect`]. Files that failed to load are left out
+    /// rather than holding the others up.
+    pub fn detect_moves(&mut self, enabled: bool) {
+        let settled = |diff: &Diff| diff.is_loaded() || diff.load_error().is_some();
+        let moves = match enabled {
+            true if self.diffs.iter().all(settled) => moves::detect(&self.diffs),
+            true => return,
+            false => vec![Vec::new(); self.diffs.len()],
+        };
+        for (diff, moves) in self.diffs.iter_mut().zip(moves) {
+            diff.moves = moves;
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.diffs
+            .iter()
+            .map(|diff| {
+                diff.lines
+                    .iter()
+                    .filter(|line| !line.is_eof_marker())
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Replaces a listed diff with its loaded version, keeping the totals in line with it.
+    ///
+    /// Stale results, e.g. from before a refresh, are ignored.
+    pub fn update_diff(&mut self, i: usize, diff: Diff) {
+        let Some(listed) = self.diffs.get_mut(i) else {
+            return;
+        };
+        if listed.file_name() != diff.file_name() || listed.is_loaded() {
+            return;
+        }
+
+        self.stats.replace(listed, &diff);
+        *listed = diff;
+    }
+
+    /// Records why a listed diff couldn't be loaded, or with `None`, that it is tried again.
+    ///
+    /// Stale results, e.g. from before a refresh, are ignored like in [`DiffData::update_diff`].
+    pub fn set_load_error(&mut self, i: usize, file_name: &str, error: Option<String>) {
+        if let Some(listed) = self.diffs.get_mut(i) {
+            if listed.file_name() == file_name && !listed.is_loaded() {
+                listed.set_load_error(error);
+            }
+        }
+    }
+
+    pub fn file_names(&self) -> Vec<String> {
+        self.diffs.iter().map(Diff::file_name).collect()
+    }
+}
+
+/// How the lines of a file are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ViewMode {
+    /// Both sides interleaved in one column, like `git diff`.
+    #[default]
+    Unified,
+    /// The old side on the left and the new side on the right.
+    Split,
+}
+
+impl ViewMode {
+    pub fn label_text(&self) -> String {
+        match self {
+            ViewMode::Unified => "Unified".to_string(),
+            ViewMode::Split => "Split".to_string(),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Default, Serialize, Deserialize)]
 pub enum DiffType {
     #[default]
     Modified,
     Staged,
+    /// Modified and staged changes together.
+    All,
 }
 
 impl DiffType {
@@ -34,48 +472,437 @@ impl DiffType {
         match self {
             DiffType::Modified => "Modified".to_string(),
             DiffType::Staged => "Staged".to_string(),
+            DiffType::All => "All".to_string(),
+        }
+    }
+
+    /// Short name of the area the changes are in, as `git status` calls them.
+    pub fn area_text(&self) -> String {
+        match self {
+            DiffType::Modified => "worktree".to_string(),
+            DiffType::Staged => "index".to_string(),
+            DiffType::All => "HEAD".to_string(),
+        }
+    }
+
+    /// The diff type with the rest of the changes to a file, if the changes are split in two.
+    pub fn other(&self) -> Option<DiffType> {
+        match self {
+            DiffType::Modified => Some(DiffType::Staged),
+            DiffType::Staged => Some(DiffType::Modified),
+            DiffType::All => None,
         }
     }
 }
 
-pub enum AppDataCreationError {
-    Parsing,
+/// Paths are kept as strings, which only works for valid UTF-8.
+fn path_string(path: PathBuf) -> Result<String, DiffParsingError> {
+    match path.to_str() {
+        Some(path) => Ok(path.to_owned()),
+        None => Err(DiffParsingError::Io(
+            path,
+            "the path isn't valid UTF-8".to_string(),
+        )),
+    }
 }
 
 impl AppData {
-    pub fn from_pathbuf(path: PathBuf) -> Result<AppData, AppDataCreationError> {
-        let project_path = path
-            .to_str()
-            .ok_or(AppDataCreationError::Parsing)?
-            .to_owned();
-        let (modified_diffs, modified_stats) =
-            git::get_diffs(project_path.clone()).map_err(|_| AppDataCreationError::Parsing)?;
-        let (staged_diffs, staged_stats) = git::get_staged_diffs(project_path.clone())
-            .map_err(|_| AppDataCreationError::Parsing)?;
+    pub fn from_pathbuf(
+        path: PathBuf,
+        diff_settings: DiffSettings,
+        progress: git::Progress,
+    ) -> Result<AppData, DiffParsingError> {
+        let start = Instant::now();
+        let root = git::repository_root(&path)?;
+        let project_path = path_string(root)?;
+
+        // The three listings are reported as one, counting the files of those done already.
+        let listed = Cell::new(0);
+        let current_total = Cell::new(0);
+        let mut progress = |done, total| {
+            current_total.set(total);
+            progress(listed.get() + done, listed.get() + total)
+        };
+        let next_listing = || listed.set(listed.get() + current_total.replace(0));
+
+        let (modified_diffs, modified_stats, modified_skipped) =
+            git::list_diffs(project_path.clone(), diff_settings, &mut progress)?;
+        next_listing();
+        let (staged_diffs, staged_stats, staged_skipped) =
+            git::list_staged_diffs(project_path.clone(), diff_settings, &mut progress)?;
+        next_listing();
+        let (combined_diffs, combined_stats, combined_skipped) =
+            git::list_combined_diffs(project_path.clone(), diff_settings, &mut progress)?;
 
         let modified_diff_data = DiffData {
             diffs: modified_diffs,
             stats: modified_stats,
+            skipped: modified_skipped,
+            ignored: Vec::new(),
         };
 
         let staged_diff_data = DiffData {
             diffs: staged_diffs,
             stats: staged_stats,
+            skipped: staged_skipped,
+            ignored: Vec::new(),
+        };
+
+        let combined_diff_data = DiffData {
+            diffs: combined_diffs,
+            stats: combined_stats,
+            skipped: combined_skipped,
+            ignored: Vec::new(),
         };
 
         Ok(AppData {
+            worktree: git::worktree_name(project_path.clone()),
+            branch: git::branch_name(project_path.clone()),
+            permalink: None,
+            compared_directory: None,
+            pasted: false,
+            head: git::head_commit_info(project_path.clone()),
+            branch_status: git::branch_status(project_path.clone()),
+            commit: None,
+            stash: None,
+            revisions: None,
+            stashes: git::list_stashes(project_path.clone()).unwrap_or_default(),
+            log: git::commit_log(project_path.clone(), LOG_LENGTH).unwrap_or_default(),
             project_path,
             modified_diff_data,
             staged_diff_data,
+            combined_diff_data,
+            load_duration: start.elapsed(),
         })
     }
+
+    /// Compares two directory trees; the changes are shown as modified ones.
+    pub fn from_directories(
+        old_dir: PathBuf,
+        new_dir: PathBuf,
+        diff_settings: DiffSettings,
+        progress: git::Progress,
+    ) -> Result<AppData, DiffParsingError> {
+        let start = Instant::now();
+        let (diffs, stats, skipped) =
+            git::diff_directories(&old_dir, &new_dir, diff_settings, progress)?;
+
+        Ok(AppData {
+            project_path: path_string(new_dir)?,
+            worktree: None,
+            branch: None,
+            permalink: None,
+            compared_directory: Some(path_string(old_dir)?),
+            pasted: false,
+            head: None,
+            branch_status: None,
+            commit: None,
+            stash: None,
+            revisions: None,
+            stashes: Vec::new(),
+            log: Vec::new(),
+            modified_diff_data: DiffData {
+                diffs,
+                stats,
+                skipped,
+                ignored: Vec::new(),
+            },
+            staged_diff_data: DiffData::default(),
+            combined_diff_data: DiffData::default(),
+            load_duration: start.elapsed(),
+        })
+    }
+
+    /// Shows the changes of the commit `revspec` names in the repository at `project_path`,
+    /// as modified changes.
+    pub fn from_commit(
+        project_path: String,
+        revspec: &str,
+        diff_settings: DiffSettings,
+        progress: git::Progress,
+    ) -> Result<AppData, DiffParsingError> {
+        let start = Instant::now();
+        let (commit, diffs, stats, skipped) =
+            git::list_commit_diffs(project_path.clone(), revspec, diff_settings, progress)?;
+
+        Ok(AppData {
+            worktree: git::worktree_name(project_path.clone()),
+            branch: git::branch_name(project_path.clone()),
+            permalink: git::permalink(project_path.clone(), &commit.id),
+            stashes: git::list_stashes(project_path.clone()).unwrap_or_default(),
+            log: git::commit_log(project_path.clone(), LOG_LENGTH).unwrap_or_default(),
+            project_path,
+            compared_directory: None,
+            pasted: false,
+            head: None,
+            branch_status: None,
+            commit: Some(commit),
+            stash: None,
+            revisions: None,
+            modified_diff_data: DiffData {
+                diffs,
+                stats,
+                skipped,
+                ignored: Vec::new(),
+            },
+            staged_diff_data: DiffData::default(),
+            combined_diff_data: DiffData::default(),
+            load_duration: start.elapsed(),
+        })
+    }
+
+    /// Shows the changes between two revisions of the repository at `project_path`, as
+    /// modified changes; see [`git::list_diffs_between`].
+    pub fn from_revisions(
+        project_path: String,
+        (from, to): (&str, &str),
+        merge_base: bool,
+        paths: &[String],
+        diff_settings: DiffSettings,
+        progress: git::Progress,
+    ) -> Result<AppData, DiffParsingError> {
+        let start = Instant::now();
+        let (revisions, diffs, stats, skipped) = git::list_diffs_between(
+            project_path.clone(),
+            from,
+            to,
+            merge_base,
+            paths,
+            diff_settings,
+            progress,
+        )?;
+
+        Ok(AppData {
+            worktree: git::worktree_name(project_path.clone()),
+            branch: git::branch_name(project_path.clone()),
+            permalink: git::permalink(project_path.clone(), &revisions.to_commit.id),
+            stashes: git::list_stashes(project_path.clone()).unwrap_or_default(),
+            log: git::commit_log(project_path.clone(), LOG_LENGTH).unwrap_or_default(),
+            project_path,
+            compared_directory: None,
+            pasted: false,
+            head: None,
+            branch_status: None,
+            commit: None,
+            stash: None,
+            revisions: Some(revisions),
+            modified_diff_data: DiffData {
+                diffs,
+                stats,
+                skipped,
+                ignored: Vec::new(),
+            },
+            staged_diff_data: DiffData::default(),
+            combined_diff_data: DiffData::default(),
+            load_duration: start.elapsed(),
+        })
+    }
+
+    /// Shows what popping `stash@{index}` would change in the working tree at `project_path`,
+    /// as modified changes.
+    pub fn from_stash(
+        project_path: String,
+        index: usize,
+        diff_settings: DiffSettings,
+        progress: git::Progress,
+    ) -> Result<AppData, DiffParsingError> {
+        let start = Instant::now();
+        let (stash, diffs, stats, skipped) =
+            git::stash_vs_worktree(project_path.clone(), index, diff_settings, progress)?;
+
+        Ok(AppData {
+            worktree: git::worktree_name(project_path.clone()),
+            branch: git::branch_name(project_path.clone()),
+            permalink: None,
+            stashes: git::list_stashes(project_path.clone()).unwrap_or_default(),
+            log: git::commit_log(project_path.clone(), LOG_LENGTH).unwrap_or_default(),
+            project_path,
+            compared_directory: None,
+            pasted: false,
+            head: None,
+            branch_status: None,
+            commit: None,
+            stash: Some(stash),
+            revisions: None,
+            modified_diff_data: DiffData {
+                diffs,
+                stats,
+                skipped,
+                ignored: Vec::new(),
+            },
+            staged_diff_data: DiffData::default(),
+            combined_diff_data: DiffData::default(),
+            load_duration: start.elapsed(),
+        })
+    }
+
+    /// Shows the files of a patch, as modified changes.
+    pub fn from_patch(text: &str) -> Result<AppData, PatchError> {
+        let start = Instant::now();
+        let (diffs, stats) = patch::parse(text)?;
+
+        Ok(AppData {
+            project_path: "Pasted patch".to_string(),
+            worktree: None,
+            branch: None,
+            permalink: None,
+            compared_directory: None,
+            pasted: true,
+            head: None,
+            branch_status: None,
+            commit: None,
+            stash: None,
+            revisions: None,
+            stashes: Vec::new(),
+            log: Vec::new(),
+            modified_diff_data: DiffData {
+                diffs,
+                stats,
+                skipped: Vec::new(),
+                ignored: Vec::new(),
+            },
+            staged_diff_data: DiffData::default(),
+            combined_diff_data: DiffData::default(),
+            load_duration: start.elapsed(),
+        })
+    }
+
+    /// Whether a repository's working tree and index are shown, rather than e.g. a commit.
+    pub fn shows_working_tree(&self) -> bool {
+        self.compared_directory.is_none()
+            && !self.pasted
+            && self.commit.is_none()
+            && self.stash.is_none()
+            && self.revisions.is_none()
+    }
+
+    /// Applies the ignored paths of `settings` to both diff types; see [`DiffData::apply_ignores`].
+    pub fn apply_ignores(&mut self, settings: &Settings, show_ignored: bool) {
+        let ignored_paths = settings.for_repo(&self.project_path).ignored_paths();
+        self.modified_diff_data
+            .apply_ignores(&ignored_paths, show_ignored);
+        self.staged_diff_data
+            .apply_ignores(&ignored_paths, show_ignored);
+        self.combined_diff_data
+            .apply_ignores(&ignored_paths, show_ignored);
+    }
+
+    pub fn detect_moves(&mut self, enabled: bool) {
+        self.modified_diff_data.detect_moves(enabled);
+        self.staged_diff_data.detect_moves(enabled);
+        self.combined_diff_data.detect_moves(enabled);
+    }
+
+    pub fn diff_data(&self, diff_type: &DiffType) -> &DiffData {
+        match diff_type {
+            DiffType::Modified => &self.modified_diff_data,
+            DiffType::Staged => &self.staged_diff_data,
+            DiffType::All => &self.combined_diff_data,
+        }
+    }
+
+    pub fn diff_data_mut(&mut self, diff_type: &DiffType) -> &mut DiffData {
+        match diff_type {
+            DiffType::Modified => &mut self.modified_diff_data,
+            DiffType::Staged => &mut self.staged_diff_data,
+            DiffType::All => &mut self.combined_diff_data,
+        }
+    }
 }
 
 pub enum Message {
     LoadDiff(PathBuf),
-    UpdateAppData(AppData),
+    /// Compares an old and a new directory that don't need to be repositories.
+    CompareDirectories(PathBuf, PathBuf),
+    /// Shows the changes of a commit of the open repository.
+    ViewCommit(String),
+    /// Compares two revisions of the open repository, old one first, from their merge-base
+    /// if the flag is set. Only the given paths are compared, unless there are none.
+    CompareRevisions(String, String, bool, Vec<String>),
+    /// Forgets a recently opened repository, e.g. one that can't be opened anymore.
+    RemoveRecentProject(String),
+    ClearRecentProjects,
+    /// Lists the shown changes again, from wherever they came from.
+    Refresh,
+    /// Compares `stash@{index}` of the open repository to its working tree.
+    CompareStash(usize),
+    /// The result of the listing with the given id.
+    UpdateAppData(usize, Box<AppData>),
+    UpdateLoadProgress(usize, usize, usize),
+    LoadFailed(usize, String),
+    CancelLoad,
+    UpdateFileTimes(FileTimes),
+    UpdateDiff(DiffType, usize, Box<Diff>),
+    /// The lines of a diff, by its index and file name, couldn't be computed, and why.
+    DiffLoadFailed(DiffType, usize, String, String),
+    /// Loads the diff at the index of the current diff type again after it failed.
+    RetryDiff(usize),
     ShowError(String),
     ChangeDiffType(DiffType),
     ChangeSelectedDiffIndex(usize),
+    /// Lists only the files matching the query, moving the selection off hidden files.
+    ChangeFileFilter(String),
+    /// Selects the next file of the list, or the previous one if `false`.
+    StepSelectedDiff(bool),
+    /// Scrolls the selected file to the next hunk, or the previous one if `false`.
+    StepHunk(bool),
+    /// Scrolls the selected file to the next run of changed lines, or the previous one if
+    /// `false`, wrapping around at either end.
+    StepChange(bool),
+    /// Selects a file of the given diff type, switching to it.
+    ChangeSelectedDiff(DiffType, usize),
+    CopyUnifiedDiff,
+    /// Copies the file at the index as a patch.
+    CopyPatch(usize),
+    /// Saves every listed file as one patch.
+    SavePatch(PathBuf),
+    CopyText(String),
+    LoadAnnotations(PathBuf),
+    UpdateAnnotations(Annotations),
+    OpenExternalDiff(usize),
+    /// Looks up the commit that last changed an old line number of a diff.
+    BlameLine(Box<Diff>, u32),
+    UpdateBlame(BlameState),
+    CloseBlame,
+    RevealInFileManager(usize),
+    /// Lists the commits that changed the file at the index.
+    ShowFileHistory(usize),
+    /// Lists the commits from before the file whose history is shown was renamed too, or
+    /// stops doing so.
+    FollowRenames(bool),
+    /// The commits that changed a file, by its name and whether renames were followed.
+    UpdateFileHistory(String, bool, FileHistoryState),
+    CloseFileHistory,
+    /// Opens a diff's file in the editor, at a line number of its new side.
+    OpenInEditor(Box<Diff>, u32),
+    OpenSecondaryPane(usize),
+    CloseSecondaryPane,
+    ToggleSearch,
+    ToggleLog,
+    SearchAllFiles(SearchQuery),
+    /// The matches in one file, from the search with the id.
+    AddSearchResult(usize, FileMatches),
+    JumpToRow(usize, usize),
+    ToggleFind,
+    UpdateFindQuery(SearchQuery),
+    /// Scrolls to a match of the find query, by its index and row.
+    FindMatch(usize, usize),
+    NavigateBack,
+    NavigateForward,
+    ToggleSettings,
+    ToggleAbout,
+    TogglePastePatch,
+    ViewPatch(String),
+    ToggleDebugOverlay,
+    ToggleFileList,
+    ToggleStatsOnly,
+    ToggleGallery,
+    ChangeViewMode(ViewMode),
+    /// Marks or unmarks a file of the current diff type as viewed.
+    ToggleViewed(String),
+    /// Marks the selected file as viewed and selects the next one that isn't.
+    MarkViewedAndAdvance,
+    ToggleShowIgnored,
+    UpdateSettings(Box<Settings>),
     CloseError,
 }
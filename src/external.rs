@@ -0,0 +1,88 @@
+use std::{io, path::Path, process::Command};
+
+#[derive(Debug)]
+pub enum LaunchError {
+    EmptyCommand,
+    /// Neither an editor command nor `$EDITOR` is set.
+    NoEditor,
+    NotFound(String),
+    Io(String, io::Error),
+}
+
+impl LaunchError {
+    pub fn message(&self) -> String {
+        match self {
+            LaunchError::EmptyCommand => "No external diff tool configured!".to_string(),
+            LaunchError::NoEditor => "No editor configured and $EDITOR isn't set!".to_string(),
+            LaunchError::NotFound(program) => format!("External tool \"{}\" not found!", program),
+            LaunchError::Io(program, err) => {
+                format!("Could not launch external tool \"{}\": {}", program, err)
+            }
+        }
+    }
+}
+
+/// Spawns `template` with `{old}` and `{new}` substituted by the given paths.
+pub fn launch_diff_tool(template: &str, old: &Path, new: &Path) -> Result<(), LaunchError> {
+    let old = old.to_string_lossy();
+    let new = new.to_string_lossy();
+
+    let mut args = template
+        .split_whitespace()
+        .map(|arg| arg.replace("{old}", &old).replace("{new}", &new));
+    let program = args.next().ok_or(LaunchError::EmptyCommand)?;
+
+    spawn(program, args)
+}
+
+/// Spawns `template` with `{path}` and `{line}` substituted, or `$EDITOR +{line} {path}`
+/// when it is empty.
+pub fn open_in_editor(template: &str, path: &Path, line: u32) -> Result<(), LaunchError> {
+    let template = match template.trim() {
+        "" => match std::env::var("EDITOR") {
+            Ok(editor) if !editor.trim().is_empty() => format!("{} +{{line}} {{path}}", editor),
+            _ => return Err(LaunchError::NoEditor),
+        },
+        template => template.to_string(),
+    };
+    let path = path.to_string_lossy();
+    let line = line.to_string();
+
+    let mut args = template
+        .split_whitespace()
+        .map(|arg| arg.replace("{path}", &path).replace("{line}", &line));
+    let program = args.next().ok_or(LaunchError::NoEditor)?;
+
+    spawn(program, args)
+}
+
+/// Opens the system file browser at `path`, selecting it where the platform supports that.
+///
+/// Files that no longer exist (e.g. deleted ones) reveal their containing directory instead.
+pub fn reveal_in_file_manager(path: &Path) -> Result<(), LaunchError> {
+    let directory = path.parent().unwrap_or(path).to_string_lossy().to_string();
+    let exists = path.exists();
+    let path = path.to_string_lossy().to_string();
+
+    if cfg!(target_os = "windows") {
+        match exists {
+            true => spawn("explorer".to_string(), [format!("/select,{}", path)]),
+            false => spawn("explorer".to_string(), [directory]),
+        }
+    } else if cfg!(target_os = "macos") {
+        match exists {
+            true => spawn("open".to_string(), ["-R".to_string(), path]),
+            false => spawn("open".to_string(), [directory]),
+        }
+    } else {
+        spawn("xdg-open".to_string(), [directory])
+    }
+}
+
+fn spawn(program: String, args: impl IntoIterator<Item = String>) -> Result<(), LaunchError> {
+    match Command::new(&program).args(args).spawn() {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Err(LaunchError::NotFound(program)),
+        Err(err) => Err(LaunchError::Io(program, err)),
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    git::{self, Diff},
+    search,
+    settings::Settings,
+};
+
+/// A directory of changed files, with what changed anywhere below it.
+#[derive(Debug, Default, PartialEq)]
+pub struct Directory {
+    /// Path relative to the parent directory. Directories with nothing but a single
+    /// subdirectory are merged with it, so this can be several components, e.g. `src/ui`.
+    pub name: String,
+    /// Full path, relative to the prefix the tree was built with.
+    pub path: String,
+    pub directories: Vec<Directory>,
+    /// Indices of the files directly in the directory.
+    pub files: Vec<usize>,
+    /// Number of files anywhere below the directory.
+    pub file_count: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl Directory {
+    /// E.g. "src/ (4 files, +30 −10)".
+    pub fn label_text(&self) -> String {
+        let files = match self.file_count {
+            1 => "1 file".to_string(),
+            count => format!("{} files", count),
+        };
+        format!(
+            "{}/ ({}, +{} −{})",
+            self.name, files, self.insertions, self.deletions
+        )
+    }
+
+    /// Whether the file at `index` is anywhere below the directory.
+    pub fn contains(&self, index: usize) -> bool {
+        self.files.contains(&index) || self.directories.iter().any(|dir| dir.contains(index))
+    }
+
+    /// The files anywhere below the directory, in the order the tree lists them: those in
+    /// its subdirectories first, then its own.
+    pub fn file_indices(&self) -> Vec<usize> {
+        let mut indices = (self.directories.iter())
+            .flat_map(Directory::file_indices)
+            .collect::<Vec<_>>();
+        indices.extend(&self.files);
+        indices
+    }
+
+    fn subdirectory(&mut self, name: &str) -> &mut Directory {
+        let position = match self.directories.iter().position(|dir| dir.name == name) {
+            Some(position) => position,
+            None => {
+                let path = match self.path.is_empty() {
+                    true => name.to_string(),
+                    false => format!("{}/{}", self.path, name),
+                };
+                self.directories.push(Directory {
+                    name: name.to_string(),
+                    path,
+                    ..Directory::default()
+                });
+                self.directories.len() - 1
+            }
+        };
+        &mut self.directories[position]
+    }
+
+    fn merge_single_children(&mut self) {
+        for dir in &mut self.directories {
+            while dir.files.is_empty() && dir.directories.len() == 1 {
+                let child = dir.directories.remove(0);
+                *dir = Directory {
+                    name: format!("{}/{}", dir.name, child.name),
+                    ..child
+                };
+            }
+            dir.merge_single_children();
+        }
+    }
+}
+
+/// Groups the files of `diffs` by the directories they are in below `prefix`, keeping the
+/// order of `indices` otherwise. The returned root has an empty name.
+pub fn build(diffs: &[Diff], indices: &[usize], prefix: &Path) -> Directory {
+    let mut root = Directory::default();
+    for &i in indices {
+        let diff = &diffs[i];
+        let name = diff.file_name_without_prefix(prefix);
+        let mut components = name.split('/').collect::<Vec<_>>();
+        components.pop();
+
+        let mut dir = &mut root;
+        for component in components {
+            dir.file_count += 1;
+            dir.insertions += diff.insertions();
+            dir.deletions += diff.deletions();
+            dir = dir.subdirectory(component);
+        }
+        dir.file_count += 1;
+        dir.insertions += diff.insertions();
+        dir.deletions += diff.deletions();
+        dir.files.push(i);
+    }
+    root.merge_single_children();
+    root
+}
+
+/// The directory the file list shows paths relative to.
+pub fn prefix(diffs: &[Diff], settings: &Settings) -> PathBuf {
+    match settings.trim_common_prefix {
+        true => git::common_directory(diffs),
+        false => PathBuf::new(),
+    }
+}
+
+/// Indices of `diffs` in the order the file list shows them, leaving out the files `filter`
+/// hides. Stepping through files follows this too.
+pub fn listed_order(diffs: &[Diff], settings: &Settings, filter: &str) -> Vec<usize> {
+    let mut order = settings.file_order.indices(diffs);
+    if let Some(matcher) = search::file_filter(filter) {
+        order.retain(|&i| {
+            matcher.is_match(&diffs[i].file_name())
+                || matcher.is_match(&diffs[i].display_name(Path::new("")))
+        });
+    }
+    match settings.file_tree {
+        true => build(diffs, &order, &prefix(diffs, settings)).file_indices(),
+        false => order,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch;
+
+    #[test]
+    fn files_are_grouped_by_directory() {
+        let (diffs, _) = patch::parse(
+            "--- a/README.md\n+++ b/README.md\n@@ -1,1 +1,1 @@\n-a\n+b\n\
+             --- a/src/ui/mod.rs\n+++ b/src/ui/mod.rs\n@@ -1,1 +1,2 @@\n a\n+b\n\
+             --- a/src/ui/code.rs\n+++ b/src/ui/code.rs\n@@ -1,2 +1,1 @@\n a\n-b\n\
+             --- a/src/ui/widgets/list.rs\n+++ b/src/ui/widgets/list.rs\n@@ -1,1 +1,2 @@\n a\n+b\n",
+        )
+        .unwrap();
+
+        let root = build(&diffs, &[0, 1, 2, 3], Path::new(""));
+        assert_eq!(root.files, [0]);
+        assert_eq!(root.file_count, 4);
+        // `src` has nothing but `ui`, so they are shown as one.
+        let ui = &root.directories[0];
+        assert_eq!((ui.name.as_str(), ui.path.as_str()), ("src/ui", "src/ui"));
+        assert_eq!(ui.files, [1, 2]);
+        assert_eq!(ui.label_text(), "src/ui/ (3 files, +2 −1)");
+        assert_eq!(ui.directories[0].path, "src/ui/widgets");
+        assert!(ui.contains(3) && !ui.contains(0));
+
+        let root = build(&diffs, &[3, 2, 1, 0], Path::new(""));
+        assert_eq!(root.directories[0].files, [2, 1]);
+    }
+
+    #[test]
+    fn files_are_listed_as_shown() {
+        let (diffs, _) = patch::parse(
+            "--- a/README.md\n+++ b/README.md\n@@ -1,1 +1,1 @@\n-a\n+b\n\
+             --- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,1 +1,2 @@\n a\n+b\n\
+             --- a/src/ui/mod.rs\n+++ b/src/ui/mod.rs\n@@ -1,2 +1,1 @@\n a\n-b\n",
+        )
+        .unwrap();
+        let mut settings = Settings {
+            file_tree: false,
+            ..Settings::default()
+        };
+        assert_eq!(listed_order(&diffs, &settings, ""), [0, 1, 2]);
+        assert_eq!(listed_order(&diffs, &settings, "*.rs"), [1, 2]);
+
+        // Subdirectories come before the files next to them.
+        settings.file_tree = true;
+        assert_eq!(listed_order(&diffs, &settings, ""), [2, 1, 0]);
+        assert_eq!(listed_order(&diffs, &settings, "src"), [2, 1]);
+    }
+}
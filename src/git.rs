@@ -1,363 +1,4021 @@
+use crate::{moves::MovedBlock, word_diff};
 use core::fmt;
-use git2::{DiffStats, Repository};
-use std::{cell::RefCell, path::PathBuf, rc::Rc};
+use git2::{Delta, DiffFindOptions, DiffFlags, DiffOptions, DiffStats, FileMode, Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    env, fs,
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    process,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::UNIX_EPOCH,
+};
+
+#[derive(Debug, Clone)]
+struct DiffFile {
+    path: PathBuf,
+    id: Oid,
+    size: u64,
+    mode: FileMode,
+}
+
+impl DiffFile {
+    fn new(file: git2::DiffFile) -> Option<DiffFile> {
+        Some(DiffFile {
+            path: file.path()?.to_path_buf(),
+            id: file.id(),
+            size: file.size(),
+            mode: file.mode(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    fn detect(content: &[u8]) -> Encoding {
+        match content {
+            [0xEF, 0xBB, 0xBF, ..] => Encoding::Utf8Bom,
+            [0xFF, 0xFE, ..] => Encoding::Utf16Le,
+            [0xFE, 0xFF, ..] => Encoding::Utf16Be,
+            _ => Encoding::Utf8,
+        }
+    }
+
+    /// Decodes `content` as UTF-16, dropping the byte order mark.
+    fn decode(&self, content: &[u8]) -> String {
+        let units = content.get(2..).unwrap_or_default().chunks_exact(2);
+        let units = match self {
+            Encoding::Utf16Be => units.map(|c| u16::from_be_bytes([c[0], c[1]])).collect(),
+            _ => units
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<u16>>(),
+        };
+        String::from_utf16_lossy(&units)
+    }
+
+    pub fn label_text(&self) -> String {
+        match self {
+            Encoding::Utf8 => "UTF-8".to_string(),
+            Encoding::Utf8Bom => "UTF-8 with BOM".to_string(),
+            Encoding::Utf16Le => "UTF-16 LE".to_string(),
+            Encoding::Utf16Be => "UTF-16 BE".to_string(),
+        }
+    }
+}
+
+/// What a diff compares: HEAD to the index, the index to the working tree, HEAD to the
+/// working tree, a commit to its first parent, or two commits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Source {
+    Index,
+    Workdir,
+    /// Staged and unstaged changes together.
+    Head,
+    Commit(Oid),
+    /// From the first commit to the second.
+    Range(Oid, Oid),
+    /// From the working tree to what popping the stash commit would restore.
+    Stash(Oid),
+}
+
+impl Source {
+    /// Whether the new side, or otherwise the old one, may only exist in the working tree.
+    fn reads_workdir(&self, new_side: bool) -> bool {
+        match self {
+            Source::Stash(_) => !new_side,
+            _ => new_side,
+        }
+    }
+}
+
+/// How git matches up the lines of both sides of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum DiffAlgorithm {
+    /// git's default.
+    #[default]
+    Myers,
+    /// Matches unique lines first, which keeps moved functions from interleaving.
+    Patience,
+    /// Spends extra time to find the smallest diff.
+    Minimal,
+}
+
+impl DiffAlgorithm {
+    pub fn label_text(&self) -> String {
+        match self {
+            DiffAlgorithm::Myers => "Myers".to_string(),
+            DiffAlgorithm::Patience => "Patience".to_string(),
+            DiffAlgorithm::Minimal => "Minimal".to_string(),
+        }
+    }
+
+    fn options(&self) -> DiffOptions {
+        let mut options = DiffOptions::new();
+        match self {
+            DiffAlgorithm::Myers => {}
+            DiffAlgorithm::Patience => {
+                options.patience(true);
+            }
+            DiffAlgorithm::Minimal => {
+                options.minimal(true);
+            }
+        }
+        options
+    }
+}
+
+/// Which whitespace differences count as changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Whitespace {
+    #[default]
+    Compare,
+    /// Like `git diff -b`: changes in the amount of whitespace, and whitespace at the end of
+    /// lines, are ignored.
+    IgnoreAmount,
+    /// Like `git diff -w`: lines are compared as if they had no whitespace at all.
+    IgnoreAll,
+}
+
+impl Whitespace {
+    pub fn label_text(&self) -> String {
+        match self {
+            Whitespace::Compare => "Compare".to_string(),
+            Whitespace::IgnoreAmount => "Ignore amount".to_string(),
+            Whitespace::IgnoreAll => "Ignore all".to_string(),
+        }
+    }
+
+    pub fn hover_text(&self) -> String {
+        match self {
+            Whitespace::Compare => "Whitespace changes count like any other".to_string(),
+            Whitespace::IgnoreAmount => {
+                "Ignore trailing whitespace and changes in the amount of it, like git diff -b"
+                    .to_string()
+            }
+            Whitespace::IgnoreAll => "Ignore whitespace altogether, like git diff -w".to_string(),
+        }
+    }
+}
+
+/// How git computes diffs, as chosen in the settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffSettings {
+    pub algorithm: DiffAlgorithm,
+    /// Unchanged lines shown around each change.
+    pub context_lines: u32,
+    pub whitespace: Whitespace,
+    /// List files git doesn't track yet among the working tree changes, as added.
+    pub include_untracked: bool,
+}
+
+impl Default for DiffSettings {
+    fn default() -> DiffSettings {
+        DiffSettings {
+            algorithm: DiffAlgorithm::default(),
+            context_lines: 3,
+            whitespace: Whitespace::default(),
+            include_untracked: false,
+        }
+    }
+}
+
+impl DiffSettings {
+    fn options(&self) -> DiffOptions {
+        let mut options = self.algorithm.options();
+        options.context_lines(self.context_lines);
+        match self.whitespace {
+            Whitespace::Compare => {}
+            Whitespace::IgnoreAmount => {
+                options.ignore_whitespace_change(true);
+                options.ignore_whitespace_eol(true);
+            }
+            Whitespace::IgnoreAll => {
+                options.ignore_whitespace(true);
+            }
+        }
+        options
+    }
+
+    /// The options for listing the changes of `source`, which only has untracked files when
+    /// its new side is the working tree.
+    fn source_options(&self, source: Source) -> DiffOptions {
+        let mut options = self.options();
+        if self.include_untracked && matches!(source, Source::Workdir | Source::Head) {
+            options
+                .include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+        }
+        options
+    }
+}
+
+/// How the lines of a file are terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    fn detect(content: &[u8]) -> LineEnding {
+        match content.windows(2).any(|pair| pair == b"\r\n") {
+            true => LineEnding::Crlf,
+            false => LineEnding::Lf,
+        }
+    }
+
+    pub fn label_text(&self) -> String {
+        match self {
+            LineEnding::Lf => "LF".to_string(),
+            LineEnding::Crlf => "CRLF".to_string(),
+        }
+    }
+}
+
+/// Length of both versions of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSizes {
+    pub old_lines: usize,
+    pub new_lines: usize,
+    pub old_bytes: usize,
+    pub new_bytes: usize,
+}
+
+impl FileSizes {
+    fn measure(old_content: &[u8], new_content: &[u8]) -> FileSizes {
+        FileSizes {
+            old_lines: count_lines(old_content),
+            new_lines: count_lines(new_content),
+            old_bytes: old_content.len(),
+            new_bytes: new_content.len(),
+        }
+    }
+
+    /// E.g. "142 → 156 lines (+14), 4.1 KB → 4.5 KB"; binary files only have bytes.
+    pub fn label_text(&self, is_binary: bool) -> String {
+        let bytes = format!(
+            "{} → {}",
+            format_bytes(self.old_bytes),
+            format_bytes(self.new_bytes)
+        );
+        if is_binary {
+            return bytes;
+        }
+
+        format!(
+            "{} → {} lines ({:+}), {}",
+            self.old_lines,
+            self.new_lines,
+            self.new_lines as i64 - self.old_lines as i64,
+            bytes
+        )
+    }
+}
+
+fn count_lines(content: &[u8]) -> usize {
+    let decoded;
+    let text = match Encoding::detect(content) {
+        encoding @ (Encoding::Utf16Le | Encoding::Utf16Be) => {
+            decoded = encoding.decode(content);
+            decoded.as_bytes()
+        }
+        Encoding::Utf8 | Encoding::Utf8Bom => content,
+    };
+    let newlines = text.iter().filter(|&&byte| byte == b'\n').count();
+
+    match text.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1048576.0),
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Diff {
-    old_file: PathBuf,
-    new_file: PathBuf,
+    old_file: DiffFile,
+    new_file: DiffFile,
+    status: Delta,
+    source: Source,
+    /// How the diff was listed, and is loaded.
+    diff_settings: DiffSettings,
+    /// Whether `headers`, `lines` and the rendered content have been computed.
+    loaded: bool,
+    /// Why computing them failed, if it did, until it is tried again.
+    load_error: Option<String>,
+    insertions: usize,
+    deletions: usize,
+    pub is_binary: bool,
+    pub encoding: Encoding,
+    /// Set when the only difference between both sides is how lines are terminated.
+    pub line_ending_change: Option<(LineEnding, LineEnding)>,
+    /// Set when only one side starts with a byte order mark.
+    pub encoding_change: Option<(Encoding, Encoding)>,
+    /// Size of both versions, known once the diff is loaded.
+    pub sizes: Option<FileSizes>,
+    /// Blocks moved to or from other files, when move detection is on.
+    pub moves: Vec<MovedBlock>,
     pub headers: Vec<Header>,
     pub lines: Vec<Line>,
     pub content: String,
     pub origins_content: String,
     pub lines_content: String,
     pub header_indices: Vec<usize>,
+    pub line_rows: Vec<usize>,
     pub insertion_indices: Vec<usize>,
     pub deletion_indices: Vec<usize>,
     pub neutral_indices: Vec<usize>,
+    /// Byte ranges of the changed lines that differ from the line they replace or were
+    /// replaced by, by row. Sorted by row.
+    pub changed_spans: Vec<(usize, Vec<Range<usize>>)>,
 }
 
 impl Diff {
-    fn new(old_file: PathBuf, new_file: PathBuf, headers: Vec<Header>, lines: Vec<Line>) -> Diff {
-        let longest_line = get_longest_line(lines.clone());
+    fn new(
+        old_file: DiffFile,
+        new_file: DiffFile,
+        status: Delta,
+        is_binary: bool,
+        headers: Vec<Header>,
+        lines: Vec<Line>,
+    ) -> Diff {
+        let line_number_width = line_number_width(&lines);
 
         let mut content = "".to_owned();
         let mut origins_content = "".to_owned();
         let mut lines_content = "".to_owned();
         let mut header_indices = Vec::new();
+        let mut line_rows = Vec::new();
         let mut insertion_indices = Vec::new();
         let mut deletion_indices = Vec::new();
         let mut neutral_indices = Vec::new();
 
-        let mut i = 0;
-        for line in &lines {
-            for header in &headers {
-                if header.line == line.new_lineno.unwrap_or(0)
-                    && line.origin != '+'
-                    && line.origin != '-'
-                {
-                    content.push_str(format!("{}\n", header.content).as_str());
-                    origins_content.push_str(" \n");
-                    lines_content.push_str(" \n");
-                    header_indices.push(i);
-                    i += 1;
-                }
+        let mut i: usize = 0;
+        let mut pending_headers = headers.iter().peekable();
+        // Headers of hunks whose lines are all hidden come after the last line.
+        for (index, line) in lines.iter().map(Some).chain([None]).enumerate() {
+            // The end-of-file markers annotate the line before them rather than
+            // being part of the file, so they don't get a row of their own.
+            if line.is_some_and(Line::is_eof_marker) {
+                line_rows.push(i.saturating_sub(1));
+                continue;
             }
-            let mut line_no = match line.origin {
-                '+' => line.new_lineno.unwrap_or(0).to_string(),
-                '-' => line.old_lineno.unwrap_or(0).to_string(),
-                _ => line.new_lineno.unwrap_or(0).to_string(),
-            };
 
-            while line_no.len() != longest_line {
-                line_no = format!(" {}", line_no);
+            while let Some(header) = pending_headers.next_if(|header| header.first_line <= index) {
+                content.push_str(format!("{}\n", header.content).as_str());
+                origins_content.push_str(" \n");
+                lines_content.push_str(" \n");
+                header_indices.push(i);
+                i += 1;
             }
+            let Some(line) = line else {
+                break;
+            };
+            // Padded to the width of the largest line number, and never cut.
+            let line_no = format!("{:>1$}", line.display_lineno(), line_number_width);
 
             content.push_str(format!("{}\n", line.content.as_str()).as_str());
             origins_content.push_str(format!("{} \n", line.origin).as_str());
             lines_content.push_str(format!("{}\n", line_no).as_str());
 
-            match line.origin {
-                '+' => insertion_indices.push(i),
-                '-' => deletion_indices.push(i),
-                _ => neutral_indices.push(i),
+            line_rows.push(i);
+            match line.kind() {
+                LineKind::Insertion => insertion_indices.push(i),
+                LineKind::Deletion => deletion_indices.push(i),
+                LineKind::Context | LineKind::EofMarker | LineKind::Header => {
+                    neutral_indices.push(i)
+                }
             };
 
             i += 1;
         }
 
-        Diff {
+        let mut diff = Diff {
             old_file,
             new_file,
+            status,
+            source: Source::Workdir,
+            diff_settings: DiffSettings::default(),
+            loaded: true,
+            load_error: None,
+            insertions: insertion_indices.len(),
+            deletions: deletion_indices.len(),
+            is_binary,
+            encoding: Encoding::default(),
+            line_ending_change: None,
+            encoding_change: None,
+            sizes: None,
+            moves: Vec::new(),
             headers,
             lines,
             content,
             origins_content,
             lines_content,
             header_indices,
+            line_rows,
             insertion_indices,
             deletion_indices,
             neutral_indices,
+            changed_spans: Vec::new(),
+        };
+        diff.changed_spans = diff.find_changed_spans();
+        diff
+    }
+
+    /// Compares every removed line with the line added in its place, as paired in the
+    /// side-by-side view.
+    fn find_changed_spans(&self) -> Vec<(usize, Vec<Range<usize>>)> {
+        let mut spans = Vec::new();
+        for row in self.split_rows() {
+            let (Some(old), Some(new)) = (row.old, row.new) else {
+                continue;
+            };
+            if old == new {
+                continue;
+            }
+            let Some(changed) =
+                word_diff::changed_spans(&self.lines[old].content, &self.lines[new].content)
+            else {
+                continue;
+            };
+            spans.push((self.line_rows[old], changed.old));
+            spans.push((self.line_rows[new], changed.new));
         }
+        spans.sort_by_key(|(row, _)| *row);
+        spans
     }
 
-    pub fn file_name(&self) -> String {
-        self.old_file
-            .to_str()
-            .unwrap_or("Error fetching file name")
-            .to_owned()
+    /// A diff whose lines are left to be computed by [`load_diff`].
+    fn unloaded(
+        old_file: DiffFile,
+        new_file: DiffFile,
+        status: Delta,
+        source: Source,
+        diff_settings: DiffSettings,
+        is_binary: bool,
+        (insertions, deletions): (usize, usize),
+    ) -> Diff {
+        Diff {
+            source,
+            diff_settings,
+            loaded: false,
+            insertions,
+            deletions,
+            ..Diff::new(
+                old_file,
+                new_file,
+                status,
+                is_binary,
+                Vec::new(),
+                Vec::new(),
+            )
+        }
     }
-}
 
-fn get_longest_line(lines: Vec<Line>) -> usize {
-    let mut longest_line = 0;
-    for line in &lines {
-        let line_no = match line.origin {
-            '+' => line.new_lineno.unwrap_or(0),
-            '-' => line.old_lineno.unwrap_or(0),
-            _ => line.new_lineno.unwrap_or(0),
+    /// A diff read from patch text rather than computed by git; see [`crate::patch`].
+    pub fn from_parts(
+        old_path: PathBuf,
+        new_path: PathBuf,
+        status: Delta,
+        is_binary: bool,
+        headers: Vec<Header>,
+        lines: Vec<Line>,
+    ) -> Diff {
+        let side = |path: PathBuf, exists: bool| DiffFile {
+            path,
+            id: Oid::zero(),
+            size: 0,
+            mode: match exists {
+                true => FileMode::Blob,
+                false => FileMode::Unreadable,
+            },
         };
 
-        if line_no > longest_line {
-            longest_line = line_no;
-        }
+        Diff::new(
+            side(old_path, status != Delta::Added),
+            side(new_path, status != Delta::Deleted),
+            status,
+            is_binary,
+            headers,
+            lines,
+        )
     }
 
-    longest_line.to_string().len()
-}
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
 
-impl fmt::Display for Diff {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(
-            f,
-            "diff --git a/{} b/{}",
-            self.old_file.to_str().unwrap_or("Error fetching file name"),
-            self.new_file.to_str().unwrap_or("Error fetching file name"),
-        )?;
+    /// Why the lines couldn't be computed, if they couldn't; see [`load_diff`].
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
 
-        for line in &self.lines {
-            write!(f, "{}", line)?;
-        }
+    pub fn set_load_error(&mut self, error: Option<String>) {
+        self.load_error = error;
+    }
 
-        Ok(())
+    /// Whether the file isn't tracked by git yet; see [`DiffSettings::include_untracked`].
+    pub fn is_untracked(&self) -> bool {
+        self.status == Delta::Untracked
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Header {
-    pub content: String,
-    pub line: u32,
-}
+    /// Whether `other` is the same change to the same file, e.g. after a refresh.
+    ///
+    /// Worktree files may not have an id yet, so their size and line counts are compared too.
+    pub fn has_same_content(&self, other: &Diff) -> bool {
+        self.content_key() == other.content_key()
+    }
 
-#[derive(Debug)]
-struct HeaderParserError;
+    /// What [`Diff::has_same_content`] compares. View state that only holds for this version
+    /// of the change, like the hunks collapsed by their index, is kept under it.
+    pub fn content_key(&self) -> (Oid, Oid, u64, u64, i32, usize, usize) {
+        (
+            self.old_file.id,
+            self.new_file.id,
+            self.old_file.size,
+            self.new_file.size,
+            self.status as i32,
+            self.insertions,
+            self.deletions,
+        )
+    }
 
-impl Header {
-    fn new(raw: String) -> Result<Header, HeaderParserError> {
-        let line: u32 = raw
-            .split(' ')
-            .nth(2)
-            .ok_or(HeaderParserError)?
-            .split(',')
-            .next()
-            .ok_or(HeaderParserError)?
-            .get(1..)
-            .ok_or(HeaderParserError)?
-            .parse()
-            .map_err(|_| HeaderParserError)?;
+    pub fn insertions(&self) -> usize {
+        self.insertions
+    }
 
-        Ok(Header { content: raw, line })
+    pub fn deletions(&self) -> usize {
+        self.deletions
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Line {
-    pub old_lineno: Option<u32>,
-    pub new_lineno: Option<u32>,
-    pub content: String,
-    pub origin: char,
-}
+    /// The lines of the file shown in `rows`, leaving out headers and end-of-file markers.
+    pub fn lines_in(&self, rows: RangeInclusive<usize>) -> Vec<&Line> {
+        self.lines
+            .iter()
+            .zip(&self.line_rows)
+            .filter(|(line, row)| rows.contains(row) && !line.is_eof_marker())
+            .map(|(line, _)| line)
+            .collect()
+    }
 
-impl Line {
-    fn new(
-        old_lineno: Option<u32>,
-        new_lineno: Option<u32>,
-        content: String,
-        origin: char,
-    ) -> Line {
-        Line {
-            old_lineno,
-            new_lineno,
-            content,
-            origin,
-        }
+    /// The lines shown in `rows`, one per row and without hunk headers. With `origins`, each
+    /// starts with its `+`, `-` or space, to paste as part of a patch.
+    pub fn copied_text(&self, rows: RangeInclusive<usize>, origins: bool) -> String {
+        self.lines_in(rows)
+            .iter()
+            .map(|line| match origins {
+                true => format!("{}{}", line.origin, line.content),
+                false => line.content.clone(),
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
     }
-}
 
-impl fmt::Display for Line {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {}", self.origin, self.content)
+    /// The line of the new side at `row`, or for a removed line, the one after it that is
+    /// left. Falls back to the last line before it, for lines removed at the end.
+    pub fn new_line_near(&self, row: usize) -> Option<u32> {
+        let numbered = self
+            .lines
+            .iter()
+            .zip(&self.line_rows)
+            .filter_map(|(line, &r)| line.new_lineno.map(|line_no| (r, line_no)));
+        let mut before = None;
+        for (line_row, line_no) in numbered {
+            if line_row >= row {
+                return Some(line_no);
+            }
+            before = Some(line_no);
+        }
+        before
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Stats {
-    pub files_changed: usize,
-    pub insertions: usize,
-    pub deletions: usize,
-}
+    /// First and last line number shown in `rows`, preferring the new side's numbers.
+    pub fn line_numbers(&self, rows: RangeInclusive<usize>) -> Option<RangeInclusive<u32>> {
+        let numbers = self
+            .lines_in(rows)
+            .iter()
+            .filter_map(|line| line.new_lineno.or(line.old_lineno))
+            .collect::<Vec<u32>>();
 
-impl Stats {
-    fn new(diff_stats: DiffStats) -> Stats {
-        Stats {
-            files_changed: diff_stats.files_changed(),
-            insertions: diff_stats.insertions(),
-            deletions: diff_stats.deletions(),
+        Some(*numbers.iter().min()?..=*numbers.iter().max()?)
+    }
+
+    /// Spoken form of a rendered row for screen readers, e.g. "added line 3: let x = 1;".
+    pub fn row_description(&self, row: usize) -> Option<String> {
+        if let Some(i) = self.header_indices.iter().position(|&header| header == row) {
+            return Some(format!("hunk {}", self.headers.get(i)?.content));
         }
+
+        // End-of-file markers share the row of the line before them, which is the one read out.
+        let line = (self.lines.iter())
+            .zip(&self.line_rows)
+            .find(|(line, &line_row)| line_row == row && !line.is_eof_marker())?
+            .0;
+        let description = match line.kind() {
+            LineKind::Insertion => {
+                format!("added line {}: {}", line.display_lineno(), line.content)
+            }
+            LineKind::Deletion => {
+                format!("removed line {}: {}", line.display_lineno(), line.content)
+            }
+            LineKind::Context => format!("line {}: {}", line.display_lineno(), line.content),
+            LineKind::EofMarker | LineKind::Header => return None,
+        };
+        Some(description)
     }
-}
 
-#[derive(Debug)]
-pub struct DiffParsingError;
+    /// This diff with every run of more than `threshold` unchanged lines folded into a band,
+    /// except for the runs whose first hidden line is in `expanded`. A few lines are kept at
+    /// either end of a run, so the changes around it keep their context.
+    ///
+    /// The hunks in `collapsed`, by their index in `headers`, are hidden but for their header.
+    pub fn folded(
+        &self,
+        threshold: Option<usize>,
+        expanded: &HashSet<usize>,
+        collapsed: &HashSet<usize>,
+    ) -> FoldedDiff {
+        let hunk_lines = |hunk: usize| {
+            let end = self
+                .headers
+                .get(hunk + 1)
+                .map_or(self.lines.len(), |next| next.first_line);
+            self.headers[hunk].first_line..end
+        };
+        let mut hidden = vec![false; self.lines.len()];
+        for &hunk in collapsed.iter().filter(|&&hunk| hunk < self.headers.len()) {
+            hidden[hunk_lines(hunk)].fill(true);
+        }
 
-pub fn get_staged_diffs(path: String) -> Result<(Vec<Diff>, Stats), DiffParsingError> {
-    let repo = Repository::open(path).map_err(|_| DiffParsingError)?;
-    let head = repo
-        .head()
-        .map_err(|_| DiffParsingError)?
-        .peel_to_tree()
-        .map_err(|_| DiffParsingError)?;
-    let diffs = repo
-        .diff_tree_to_index(Some(&head), None, None)
-        .map_err(|_| DiffParsingError)?;
+        // Runs end at hunk headers, which stay in place.
+        let hunk_starts = self
+            .headers
+            .iter()
+            .map(|header| header.first_line)
+            .collect::<HashSet<usize>>();
+        let mut runs = Vec::new();
+        let mut run_start = None;
+        for (i, line) in self.lines.iter().enumerate() {
+            if hunk_starts.contains(&i) {
+                runs.extend(run_start.take().map(|start| start..i));
+            }
+            match (line.kind() == LineKind::Context, run_start) {
+                (true, None) => run_start = Some(i),
+                (false, Some(start)) => {
+                    runs.push(start..i);
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+        runs.extend(run_start.map(|start| start..self.lines.len()));
 
-    parse_diffs(diffs)
-}
+        let mut folds = Vec::new();
+        for run in runs {
+            let Some(threshold) = threshold else {
+                break;
+            };
+            if run.len() <= threshold.max(2 * FOLD_CONTEXT) || hidden[run.start] {
+                continue;
+            }
+            let first_line = run.start + FOLD_CONTEXT;
+            if expanded.contains(&first_line) {
+                continue;
+            }
+            let end = run.end - FOLD_CONTEXT;
+            hidden[first_line..end].fill(true);
+            folds.push(Fold {
+                row: 0,
+                first_line,
+                hidden: end - first_line,
+            });
+        }
 
-pub fn get_diffs(path: String) -> Result<(Vec<Diff>, Stats), DiffParsingError> {
-    let repo = Repository::open(path).map_err(|_| DiffParsingError)?;
-    let diffs = repo
-        .diff_index_to_workdir(None, None)
-        .map_err(|_| DiffParsingError)?;
+        let mut lines = Vec::new();
+        let mut headers = Vec::new();
+        let mut hunk_headers = Vec::new();
+        let mut original_headers = self.headers.iter().enumerate().peekable();
+        let mut pending_folds = folds.iter().peekable();
+        for (i, line) in self.lines.iter().enumerate() {
+            while let Some((hunk, header)) =
+                original_headers.next_if(|(_, header)| header.first_line <= i)
+            {
+                let mut content = header.content.clone();
+                if collapsed.contains(&hunk) {
+                    let lines = &self.lines[hunk_lines(hunk)];
+                    let count = lines.iter().filter(|line| !line.is_eof_marker()).count();
+                    content = format!("{} ({} lines hidden)", content, count);
+                }
+                hunk_headers.push((headers.len(), hunk));
+                headers.push(Header {
+                    content,
+                    first_line: lines.len(),
+                    ..header.clone()
+                });
+            }
+            if hidden[i] {
+                continue;
+            }
+            if let Some(fold) = pending_folds.next_if(|fold| fold.first_line + fold.hidden == i) {
+                // Bands are rendered as hunk headers, carrying the context of their hunk.
+                let context = headers
+                    .last()
+                    .map_or("", |header: &Header| header.context());
+                headers.push(Header {
+                    content: format!("@@ … {} unchanged lines … @@ {}", fold.hidden, context)
+                        .trim_end()
+                        .to_string(),
+                    line: line.new_lineno.unwrap_or(0),
+                    first_line: lines.len(),
+                });
+            }
+            lines.push(line.clone());
+        }
 
-    parse_diffs(diffs)
-}
+        let diff = Diff {
+            source: self.source,
+            diff_settings: self.diff_settings,
+            insertions: self.insertions,
+            deletions: self.deletions,
+            encoding: self.encoding,
+            line_ending_change: self.line_ending_change,
+            encoding_change: self.encoding_change,
+            sizes: self.sizes,
+            ..Diff::new(
+                self.old_file.clone(),
+                self.new_file.clone(),
+                self.status,
+                self.is_binary,
+                headers,
+                lines,
+            )
+        };
 
-fn parse_diffs(diffs: git2::Diff) -> Result<(Vec<Diff>, Stats), DiffParsingError> {
-    let line_groups = Rc::new(RefCell::new(Vec::new()));
-    diffs
-        .foreach(
-            &mut |_delta, _num| {
-                line_groups.borrow_mut().push(Vec::new());
-                true
-            },
-            None,
-            None,
-            Some(
-                &mut |_delta, _hunk, _line| match std::str::from_utf8(_line.content()) {
-                    Ok(c) => {
-                        let mut content = c.to_string();
-                        if content.ends_with('\n') {
-                            content.pop();
-                            if content.ends_with('\r') {
-                                content.pop();
-                            }
-                        }
+        // Each row of this diff on the folded one; hidden rows land on their band, and
+        // header rows stay right above the line they belong to.
+        let mut rows = vec![None; self.row_count()];
+        let mut kept = diff.line_rows.iter();
+        for (i, &row) in self.line_rows.iter().enumerate() {
+            if !hidden[i] {
+                if let Some(&folded_row) = kept.next() {
+                    rows[row] = Some(folded_row);
+                }
+            }
+        }
+        let hunks = hunk_headers
+            .into_iter()
+            .map(|(header, hunk)| HunkHeader {
+                row: diff.header_indices[header],
+                hunk,
+                line: self.headers[hunk].line,
+                collapsed: collapsed.contains(&hunk),
+            })
+            .collect::<Vec<HunkHeader>>();
+        for header in hunks.iter().filter(|header| header.collapsed) {
+            rows[self.header_indices[header.hunk]] = Some(header.row);
+            for i in hunk_lines(header.hunk) {
+                rows[self.line_rows[i]] = Some(header.row);
+            }
+        }
+        for fold in &mut folds {
+            let after = self.line_rows[fold.first_line + fold.hidden];
+            fold.row = rows[after].unwrap_or(0).saturating_sub(1);
+            for i in fold.first_line..fold.first_line + fold.hidden {
+                rows[self.line_rows[i]] = Some(fold.row);
+            }
+        }
+        for row in (0..rows.len()).rev() {
+            if rows[row].is_none() {
+                let below = rows.get(row + 1).copied().flatten().unwrap_or(1);
+                rows[row] = Some(below.saturating_sub(1));
+            }
+        }
+        let rows = rows.into_iter().map(Option::unwrap_or_default).collect();
 
-                        let line = Line::new(
-                            _line.old_lineno(),
-                            _line.new_lineno(),
-                            content,
-                            _line.origin(),
-                        );
+        let mut folded = FoldedDiff {
+            diff,
+            folds,
+            hunks,
+            rows,
+        };
+        folded.diff.moves = self
+            .moves
+            .iter()
+            .map(|block| MovedBlock {
+                rows: folded.row(*block.rows.start())..=folded.row(*block.rows.end()),
+                ..block.clone()
+            })
+            .collect();
+        folded
+    }
 
-                        match line_groups.borrow_mut().last_mut() {
-                            Some(last) => {
-                                last.push(line);
-                                true
-                            }
-                            None => false,
-                        }
-                    }
-                    Err(_) => false,
-                },
-            ),
-        )
-        .map_err(|_| DiffParsingError)?;
+    /// The rows of a side-by-side view: unchanged lines on both sides, and removed lines
+    /// next to the lines added in their place. Lines without a counterpart get an empty
+    /// place on the other side, so both sides stay aligned.
+    pub fn split_rows(&self) -> Vec<SplitRow> {
+        let mut rows = Vec::new();
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        let flush = |rows: &mut Vec<SplitRow>, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+            for i in 0..removed.len().max(added.len()) {
+                rows.push(SplitRow {
+                    header: None,
+                    old: removed.get(i).copied(),
+                    new: added.get(i).copied(),
+                });
+            }
+            removed.clear();
+            added.clear();
+        };
 
-    let header_groups = Rc::new(RefCell::new(Vec::new()));
-    diffs
+        let mut headers = self.headers.iter().enumerate().peekable();
+        for (i, line) in self.lines.iter().enumerate() {
+            while let Some((header, _)) = headers.next_if(|(_, header)| header.first_line <= i) {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(SplitRow {
+                    header: Some(header),
+                    old: None,
+                    new: None,
+                });
+            }
+            match line.kind() {
+                LineKind::Deletion => {
+                    // Removals after additions start a new block of replacements.
+                    if !added.is_empty() {
+                        flush(&mut rows, &mut removed, &mut added);
+                    }
+                    removed.push(i);
+                }
+                LineKind::Insertion => added.push(i),
+                LineKind::Context => {
+                    flush(&mut rows, &mut removed, &mut added);
+                    rows.push(SplitRow {
+                        header: None,
+                        old: Some(i),
+                        new: Some(i),
+                    });
+                }
+                LineKind::EofMarker | LineKind::Header => {}
+            }
+        }
+        flush(&mut rows, &mut removed, &mut added);
+        rows
+    }
+
+    /// Context of the hunk shown at `row`, if git found one for it.
+    pub fn hunk_context_at(&self, row: usize) -> Option<&str> {
+        let hunk = self
+            .header_indices
+            .iter()
+            .rposition(|&header| header <= row)?;
+        Some(self.headers.get(hunk)?.context()).filter(|context| !context.is_empty())
+    }
+
+    /// Row of the header of the first hunk below `row`, or of the last one above it if not
+    /// `forward`.
+    pub fn adjacent_hunk_row(&self, row: usize, forward: bool) -> Option<usize> {
+        match forward {
+            true => self.header_indices.iter().find(|&&header| header > row),
+            false => self.header_indices.iter().rfind(|&&header| header < row),
+        }
+        .copied()
+    }
+
+    /// Rows at which the runs of added and removed lines start.
+    pub fn change_rows(&self) -> Vec<usize> {
+        let mut changed = (self.insertion_indices.iter())
+            .chain(&self.deletion_indices)
+            .copied()
+            .collect::<Vec<usize>>();
+        changed.sort_unstable();
+        let starts = changed
+            .iter()
+            .enumerate()
+            .filter(|&(i, &row)| i == 0 || changed[i - 1] + 1 != row);
+        starts.map(|(_, &row)| row).collect()
+    }
+
+    /// Row of the first run of changed lines below `row`, or of the last one above it if not
+    /// `forward`. Past the last run it wraps around to the first, and the other way around.
+    pub fn adjacent_change_row(&self, row: usize, forward: bool) -> Option<usize> {
+        let starts = self.change_rows();
+        match forward {
+            true => starts.iter().find(|&&start| start > row).or(starts.first()),
+            false => starts.iter().rfind(|&&start| start < row).or(starts.last()),
+        }
+        .copied()
+    }
+
+    /// Number of rendered rows, counting hunk headers.
+    pub fn row_count(&self) -> usize {
+        self.header_indices.len()
+            + self.insertion_indices.len()
+            + self.deletion_indices.len()
+            + self.neutral_indices.len()
+    }
+
+    pub fn file_name(&self) -> String {
+        self.old_file
+            .path
+            .to_str()
+            .unwrap_or("Error fetching file name")
+            .to_owned()
+    }
+
+    /// Name of the file on the new side, which differs from [`Diff::file_name`] for renamed
+    /// files.
+    pub fn new_file_name(&self) -> String {
+        self.new_file
+            .path
+            .to_str()
+            .unwrap_or("Error fetching file name")
+            .to_owned()
+    }
+
+    /// Name of the file where it is now: the new one, unless the file was deleted.
+    pub fn worktree_file_name(&self) -> String {
+        match self.status {
+            Delta::Deleted => self.file_name(),
+            _ => self.new_file_name(),
+        }
+    }
+
+    /// File name relative to `prefix`, or the full name if it isn't below it.
+    pub fn file_name_without_prefix(&self, prefix: &Path) -> String {
+        match self.old_file.path.strip_prefix(prefix) {
+            Ok(path) => path
+                .to_str()
+                .unwrap_or("Error fetching file name")
+                .to_owned(),
+            _ => self.file_name(),
+        }
+    }
+
+    /// Takes the encoding from whether the sides start with a byte order mark, as returned by
+    /// [`strip_byte_order_marks`].
+    fn note_byte_order_marks(&mut self, (old, new): (Option<bool>, Option<bool>)) {
+        let encoding = |bom| match bom {
+            true => Encoding::Utf8Bom,
+            false => Encoding::Utf8,
+        };
+        if old == Some(true) || new == Some(true) {
+            self.encoding = Encoding::Utf8Bom;
+        }
+        if let (Some(old), Some(new)) = (old, new) {
+            self.encoding_change = (old != new).then(|| (encoding(old), encoding(new)));
+        }
+    }
+
+    /// Old and new mode of a file that was neither added nor deleted, if they differ, e.g.
+    /// `0o100644` and `0o100755` after `chmod +x`.
+    pub fn mode_change(&self) -> Option<(u32, u32)> {
+        let (old, new) = (u32::from(self.old_file.mode), u32::from(self.new_file.mode));
+        match self.status {
+            Delta::Added | Delta::Deleted | Delta::Untracked => None,
+            _ => Some((old, new)).filter(|_| old != new),
+        }
+    }
+
+    /// Name to list the file by, relative to `prefix` like [`Diff::file_name_without_prefix`],
+    /// e.g. "old.rs → new.rs" for renamed files.
+    pub fn display_name(&self, prefix: &Path) -> String {
+        let name = self.file_name_without_prefix(prefix);
+        if self.status != Delta::Renamed {
+            return name;
+        }
+
+        let new_path = self
+            .new_file
+            .path
+            .strip_prefix(prefix)
+            .unwrap_or(&self.new_file.path);
+        format!(
+            "{} → {}",
+            name,
+            new_path.to_str().unwrap_or("Error fetching file name")
+        )
+    }
+
+    /// How much of a renamed file's lines stayed the same, in percent, once it is loaded.
+    pub fn similarity(&self) -> Option<usize> {
+        let sizes = self
+            .sizes
+            .filter(|_| self.status == Delta::Renamed && !self.is_binary)?;
+        let total = sizes.old_lines + sizes.new_lines;
+        let unchanged = sizes.old_lines.saturating_sub(self.deletions)
+            + sizes.new_lines.saturating_sub(self.insertions);
+        Some(match total {
+            0 => 100,
+            total => unchanged * 100 / total,
+        })
+    }
+
+    /// Sizes of both versions, and how similar they are for renamed files.
+    pub fn sizes_text(&self) -> Option<String> {
+        let sizes = self.sizes?.label_text(self.is_binary);
+        Some(match self.similarity() {
+            Some(similarity) => format!("{}, renamed, {}% similar", sizes, similarity),
+            None => sizes,
+        })
+    }
+
+    /// Renders the diff as a patch that can be applied with `git apply`.
+    pub fn to_unified_string(&self) -> String {
+        self.to_string()
+    }
+
+    /// Explains why a diff without any lines has nothing to show.
+    pub fn empty_reason(&self) -> String {
+        if self.is_binary {
+            return self.binary_summary();
+        }
+        if let Some((old, new)) = self.mode_change() {
+            return format!("Only the mode changed, {:o} → {:o}", old, new);
+        }
+
+        let size = match self.status {
+            Delta::Deleted => self.old_file.size,
+            _ => self.new_file.size,
+        };
+
+        match self.status {
+            Delta::Ignored => "File is ignored",
+            _ if size == 0 => "File is empty",
+            _ if self.diff_settings.whitespace != Whitespace::Compare => "Only whitespace changed",
+            _ => "No changes in this file",
+        }
+        .to_string()
+    }
+
+    /// E.g. "Binary file — 4.1 KB → 4.5 KB (+412 bytes)", as binary files have no lines to
+    /// show.
+    fn binary_summary(&self) -> String {
+        let (old_bytes, new_bytes) = match self.sizes {
+            Some(sizes) => (sizes.old_bytes, sizes.new_bytes),
+            None => (self.old_file.size as usize, self.new_file.size as usize),
+        };
+        match self.status {
+            Delta::Added | Delta::Untracked => {
+                format!("Binary file added — {}", format_bytes(new_bytes))
+            }
+            Delta::Deleted => format!("Binary file deleted — {}", format_bytes(old_bytes)),
+            _ => format!(
+                "Binary file — {} → {} ({:+} bytes)",
+                format_bytes(old_bytes),
+                format_bytes(new_bytes),
+                new_bytes as i64 - old_bytes as i64
+            ),
+        }
+    }
+}
+
+/// Width `git diff --stat` fits its lines into.
+const STAT_WIDTH: usize = 80;
+/// Longest file name shown in full by [`stat_summary`]; longer ones keep their end.
+const STAT_NAME_WIDTH: usize = 50;
+
+/// Summarizes the diffs like `git diff --stat`: a line per file with its number of changed
+/// lines and a bar of `+` and `-`, scaled to fit, followed by the totals.
+pub fn stat_summary(diffs: &[Diff]) -> String {
+    let names = diffs
+        .iter()
+        .map(|diff| {
+            let name = diff.file_name();
+            let length = name.chars().count();
+            match length > STAT_NAME_WIDTH {
+                true => {
+                    let end = name.chars().skip(length - (STAT_NAME_WIDTH - 3));
+                    format!("...{}", end.collect::<String>())
+                }
+                false => name,
+            }
+        })
+        .collect::<Vec<String>>();
+    let name_width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let changes = |diff: &Diff| diff.insertions() + diff.deletions();
+    let max_change = diffs.iter().map(changes).max().unwrap_or(0);
+    let count_width = match diffs.iter().any(|diff| diff.is_binary) {
+        true => max_change.to_string().len().max("Bin".len()),
+        false => max_change.to_string().len(),
+    };
+    let graph_width = STAT_WIDTH
+        .saturating_sub(name_width + count_width + 4)
+        .max(10);
+    // Like git, every changed file gets at least one character of bar.
+    let scale = |count: usize| match (count, max_change > graph_width) {
+        (0, _) => 0,
+        (count, true) => 1 + count * (graph_width - 1) / max_change,
+        (count, false) => count,
+    };
+
+    let mut summary = String::new();
+    for (diff, name) in diffs.iter().zip(names) {
+        let stat = match (diff.is_binary, diff.sizes) {
+            (true, Some(sizes)) => format!(
+                "{:>count_width$} {} -> {} bytes",
+                "Bin", sizes.old_bytes, sizes.new_bytes
+            ),
+            (true, None) => format!("{:>count_width$}", "Bin"),
+            (false, _) => {
+                let insertions = scale(changes(diff)).min(scale(diff.insertions()));
+                let deletions = scale(changes(diff)) - insertions;
+                format!(
+                    "{:>count_width$} {}{}",
+                    changes(diff),
+                    "+".repeat(insertions),
+                    "-".repeat(deletions)
+                )
+            }
+        };
+        let line = format!(" {name:<name_width$} | {stat}");
+        summary.push_str(line.trim_end());
+        summary.push('\n');
+    }
+
+    let plural = |count: usize| match count {
+        1 => "",
+        _ => "s",
+    };
+    let insertions = diffs.iter().map(Diff::insertions).sum::<usize>();
+    let deletions = diffs.iter().map(Diff::deletions).sum::<usize>();
+    summary.push_str(&format!(
+        " {} file{} changed",
+        diffs.len(),
+        plural(diffs.len())
+    ));
+    if insertions > 0 || deletions == 0 {
+        summary.push_str(&format!(
+            ", {} insertion{}(+)",
+            insertions,
+            plural(insertions)
+        ));
+    }
+    if deletions > 0 || insertions == 0 {
+        summary.push_str(&format!(", {} deletion{}(-)", deletions, plural(deletions)));
+    }
+    summary.push('\n');
+    summary
+}
+
+/// A row of [`Diff::split_rows`], by indices into the diff's headers and lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitRow {
+    /// Set for the rows of hunk headers, which span both sides.
+    pub header: Option<usize>,
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+}
+
+/// Unchanged lines [`Diff::folded`] keeps at either end of a folded run.
+const FOLD_CONTEXT: usize = 3;
+
+/// A run of unchanged lines hidden behind a band.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fold {
+    /// Row of the band in the folded diff.
+    pub row: usize,
+    /// Index into the unfolded diff's lines of the first hidden line, which tells folds apart.
+    pub first_line: usize,
+    pub hidden: usize,
+}
+
+/// A hunk header of a [`FoldedDiff`], which collapses or expands its hunk when clicked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HunkHeader {
+    /// Row of the header in the folded diff.
+    pub row: usize,
+    /// Index of the hunk in the unfolded diff's headers.
+    pub hunk: usize,
+    /// First line number of the hunk on the new side.
+    pub line: u32,
+    pub collapsed: bool,
+}
+
+/// A diff with long runs of unchanged lines and collapsed hunks folded away; see
+/// [`Diff::folded`].
+#[derive(Clone)]
+pub struct FoldedDiff {
+    pub diff: Diff,
+    pub folds: Vec<Fold>,
+    pub hunks: Vec<HunkHeader>,
+    /// The row of the folded diff each row of the unfolded one is shown at.
+    rows: Vec<usize>,
+}
+
+impl FoldedDiff {
+    /// Where a row of the unfolded diff, e.g. a search result, is shown.
+    pub fn row(&self, unfolded: usize) -> usize {
+        self.rows
+            .get(unfolded)
+            .copied()
+            .unwrap_or(self.diff.row_count().saturating_sub(1))
+    }
+
+    /// The first row of the unfolded diff shown at `row`.
+    pub fn unfolded_row(&self, row: usize) -> usize {
+        self.rows
+            .iter()
+            .position(|&folded| folded >= row)
+            .unwrap_or(self.rows.len().saturating_sub(1))
+    }
+}
+
+/// Longest directory shared by all changed files.
+pub fn common_directory(diffs: &[Diff]) -> PathBuf {
+    let mut dirs = diffs
+        .iter()
+        .map(|diff| diff.old_file.path.parent().unwrap_or(Path::new("")));
+    let Some(first) = dirs.next() else {
+        return PathBuf::new();
+    };
+
+    dirs.fold(first.to_path_buf(), |common, dir| {
+        common
+            .components()
+            .zip(dir.components())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a)
+            .collect()
+    })
+}
+
+/// Digits of the largest line number, which the line number column is padded to.
+pub fn line_number_width(lines: &[Line]) -> usize {
+    let largest = lines.iter().map(Line::display_lineno).max().unwrap_or(0);
+    largest.to_string().len()
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let old_path = self
+            .old_file
+            .path
+            .to_str()
+            .unwrap_or("Error fetching file name");
+        let new_path = self
+            .new_file
+            .path
+            .to_str()
+            .unwrap_or("Error fetching file name");
+        let old_mode = u32::from(self.old_file.mode);
+        let new_mode = u32::from(self.new_file.mode);
+
+        writeln!(f, "diff --git a/{} b/{}", old_path, new_path)?;
+        match self.status {
+            Delta::Added | Delta::Untracked => writeln!(f, "new file mode {:o}", new_mode)?,
+            Delta::Deleted => writeln!(f, "deleted file mode {:o}", old_mode)?,
+            _ if old_mode != new_mode => {
+                writeln!(f, "old mode {:o}", old_mode)?;
+                writeln!(f, "new mode {:o}", new_mode)?;
+            }
+            _ => (),
+        }
+        if self.status == Delta::Renamed {
+            writeln!(f, "rename from {}", old_path)?;
+            writeln!(f, "rename to {}", new_path)?;
+        }
+
+        let old_name = match self.status {
+            Delta::Added | Delta::Untracked => "/dev/null".to_string(),
+            _ => format!("a/{}", old_path),
+        };
+        let new_name = match self.status {
+            Delta::Deleted => "/dev/null".to_string(),
+            _ => format!("b/{}", new_path),
+        };
+
+        if self.is_binary {
+            return writeln!(f, "Binary files {} and {} differ", old_name, new_name);
+        }
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(f, "--- {}", old_name)?;
+        writeln!(f, "+++ {}", new_name)?;
+
+        let mut headers = self.headers.iter().peekable();
+        for (i, line) in self.lines.iter().enumerate() {
+            while let Some(header) = headers.next_if(|header| header.first_line == i) {
+                writeln!(f, "{}", header.content)?;
+            }
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub content: String,
+    /// First line number of the hunk on the new side.
+    pub line: u32,
+    /// Index into `Diff::lines` of the first line of this hunk, which the header is shown
+    /// right above.
+    pub first_line: usize,
+}
+
+#[derive(Debug)]
+struct HeaderParserError;
+
+impl Header {
+    fn new(raw: String) -> Result<Header, HeaderParserError> {
+        let line: u32 = raw
+            .split(' ')
+            .nth(2)
+            .ok_or(HeaderParserError)?
+            .split(',')
+            .next()
+            .ok_or(HeaderParserError)?
+            .get(1..)
+            .ok_or(HeaderParserError)?
+            .parse()
+            .map_err(|_| HeaderParserError)?;
+
+        Ok(Header {
+            content: raw,
+            line,
+            first_line: 0,
+        })
+    }
+
+    /// Function or section the hunk is in, which git puts after the line ranges.
+    pub fn context(&self) -> &str {
+        split_hunk_header(&self.content).1
+    }
+}
+
+/// Splits `@@ -1,2 +1,3 @@ fn main() {` into the ranges, up to the closing `@@`, and the
+/// context after them, which is empty if git found none.
+pub fn split_hunk_header(header: &str) -> (&str, &str) {
+    match header.get(2..).and_then(|rest| rest.find("@@")) {
+        Some(end) => {
+            let (ranges, context) = header.split_at(end + 4);
+            (ranges, context.trim_start())
+        }
+        None => (header, ""),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub old_lineno: Option<u32>,
+    pub new_lineno: Option<u32>,
+    pub content: String,
+    pub origin: char,
+}
+
+impl Line {
+    fn new(
+        old_lineno: Option<u32>,
+        new_lineno: Option<u32>,
+        content: String,
+        origin: char,
+    ) -> Line {
+        Line {
+            old_lineno,
+            new_lineno,
+            content,
+            origin,
+        }
+    }
+
+    pub fn kind(&self) -> LineKind {
+        LineKind::from_origin(self.origin)
+    }
+
+    /// git2 reports "\ No newline at end of file" markers as their own lines.
+    ///
+    /// Header lines don't belong to the file either, so they count as markers too.
+    pub fn is_eof_marker(&self) -> bool {
+        matches!(self.kind(), LineKind::EofMarker | LineKind::Header)
+    }
+
+    /// The old line number for deletions, the new one otherwise.
+    pub fn display_lineno(&self) -> u32 {
+        match self.kind() {
+            LineKind::Deletion => self.old_lineno.unwrap_or(0),
+            _ => self.new_lineno.unwrap_or(0),
+        }
+    }
+}
+
+/// How a line is rendered, by the origin git2 gives it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// `' '`, unchanged on both sides.
+    Context,
+    /// `'+'`
+    Insertion,
+    /// `'-'`
+    Deletion,
+    /// `'='`, `'>'` and `'<'`: a missing newline at the end of both, the old or the new side.
+    EofMarker,
+    /// `'F'`, `'H'` and `'B'`: file, hunk and binary headers, which are only
+    /// reported when printing diffs. Headers are rendered from [`Header`]s instead.
+    Header,
+}
+
+impl LineKind {
+    pub fn from_origin(origin: char) -> LineKind {
+        match origin {
+            '+' => LineKind::Insertion,
+            '-' => LineKind::Deletion,
+            '=' | '>' | '<' => LineKind::EofMarker,
+            'F' | 'H' | 'B' => LineKind::Header,
+            // git2 documents no other origins, so anything else is shown as-is.
+            _ => LineKind::Context,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.is_eof_marker() {
+            false => write!(f, "{}{}", self.origin, self.content),
+            true => write!(f, "\\ No newline at end of file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Untracked files, which are counted apart from the changed ones: all of their lines
+    /// are new, and would otherwise outweigh the changes.
+    pub untracked_files: usize,
+    pub untracked_insertions: usize,
+}
+
+impl Stats {
+    fn new(diff_stats: DiffStats) -> Stats {
+        Stats {
+            files_changed: diff_stats.files_changed(),
+            insertions: diff_stats.insertions(),
+            deletions: diff_stats.deletions(),
+            ..Stats::default()
+        }
+    }
+
+    /// The totals of `diffs`, with the untracked files counted apart.
+    pub fn of(diffs: &[Diff]) -> Stats {
+        let (untracked, changed): (Vec<&Diff>, Vec<&Diff>) =
+            diffs.iter().partition(|diff| diff.is_untracked());
+        Stats {
+            files_changed: changed.len(),
+            insertions: changed.iter().map(|diff| diff.insertions()).sum(),
+            deletions: changed.iter().map(|diff| diff.deletions()).sum(),
+            untracked_files: untracked.len(),
+            untracked_insertions: untracked.iter().map(|diff| diff.insertions()).sum(),
+        }
+    }
+
+    /// Updates the totals for `diff` having been counted as `listed` before.
+    pub fn replace(&mut self, listed: &Diff, diff: &Diff) {
+        match diff.is_untracked() {
+            true => {
+                self.untracked_insertions =
+                    self.untracked_insertions + diff.insertions() - listed.insertions();
+            }
+            false => {
+                self.insertions = self.insertions + diff.insertions() - listed.insertions();
+                self.deletions = self.deletions + diff.deletions() - listed.deletions();
+            }
+        }
+    }
+}
+
+/// Links to files as of a commit on the repository's hosting provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Permalink {
+    web_url: String,
+    commit: String,
+    gitlab: bool,
+}
+
+impl Permalink {
+    /// Link to `lines` of the file at `path`.
+    pub fn url(&self, path: &str, lines: RangeInclusive<u32>) -> String {
+        let (first, last) = (*lines.start(), *lines.end());
+        let (blob, anchor) = match self.gitlab {
+            false if first == last => ("blob", format!("L{}", first)),
+            false => ("blob", format!("L{}-L{}", first, last)),
+            true if first == last => ("-/blob", format!("L{}", first)),
+            true => ("-/blob", format!("L{}-{}", first, last)),
+        };
+        format!(
+            "{}/{}/{}/{}#{}",
+            self.web_url, blob, self.commit, path, anchor
+        )
+    }
+}
+
+/// Permalinks to `commit`, if the `origin` remote is hosted on GitHub or GitLab.
+pub fn permalink(path: String, commit: &str) -> Option<Permalink> {
+    let repo = Repository::open(path).ok()?;
+    let remote = repo.find_remote("origin").ok()?;
+    let web_url = web_url(remote.url()?)?;
+    let commit = commit.to_string();
+
+    let gitlab = web_url.contains("gitlab");
+    if !gitlab && !web_url.contains("github") {
+        return None;
+    }
+
+    Some(Permalink {
+        web_url,
+        commit,
+        gitlab,
+    })
+}
+
+/// Turns an `https`, `ssh` or scp-like (`git@host:owner/repo`) remote URL into the repository's web page.
+fn web_url(remote_url: &str) -> Option<String> {
+    let url = remote_url.trim_end_matches('/').trim_end_matches(".git");
+    let (host, path) = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?,
+        None => url.split_once(':')?,
+    };
+    // Drop any user name, e.g. `git@`, and the port.
+    let host = host.rsplit('@').next()?.split(':').next()?;
+
+    Some(format!("https://{}/{}", host, path))
+}
+
+/// A changed file left out of a listing because it couldn't be read.
+#[derive(Debug, Clone)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub enum DiffParsingError {
+    /// The path isn't in a git repository.
+    NotARepository(PathBuf),
+    /// The repository has no working tree to compare with.
+    BareRepository(PathBuf),
+    /// The repository has no commits yet, so there's no HEAD to compare with.
+    NoCommits(PathBuf),
+    /// A file or directory couldn't be read or written.
+    Io(PathBuf, String),
+    /// A libgit2 operation failed, with its message.
+    Libgit2(String),
+    /// The revision doesn't name exactly one commit.
+    InvalidRevspec(String),
+    /// The listing was cancelled through its progress callback.
+    Cancelled,
+}
+
+impl DiffParsingError {
+    fn io(path: &Path, err: std::io::Error) -> DiffParsingError {
+        DiffParsingError::Io(path.to_path_buf(), err.to_string())
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            DiffParsingError::NotARepository(path) => {
+                format!("{} isn't in a git repository.", path.display())
+            }
+            DiffParsingError::BareRepository(path) => format!(
+                "{} is a bare repository, without a working tree to compare.",
+                path.display()
+            ),
+            DiffParsingError::NoCommits(path) => format!(
+                "{} has no commits yet, so there is no HEAD to compare with.",
+                path.display()
+            ),
+            DiffParsingError::Io(path, err) => format!("Couldn't read {}: {}", path.display(), err),
+            DiffParsingError::Libgit2(message) => format!("Git failed: {}", message),
+            DiffParsingError::InvalidRevspec(revspec) => {
+                format!("\"{}\" doesn't name a commit, or is ambiguous!", revspec)
+            }
+            DiffParsingError::Cancelled => "Cancelled.".to_string(),
+        }
+    }
+}
+
+impl From<git2::Error> for DiffParsingError {
+    fn from(err: git2::Error) -> DiffParsingError {
+        DiffParsingError::Libgit2(err.message().to_string())
+    }
+}
+
+/// Opens the repository at `path`, telling a path outside of any repository apart from
+/// other failures.
+fn open_repository(path: impl AsRef<Path>) -> Result<Repository, DiffParsingError> {
+    let path = path.as_ref();
+    Repository::open(path).map_err(|err| match err.code() {
+        git2::ErrorCode::NotFound => DiffParsingError::NotARepository(path.to_path_buf()),
+        _ => DiffParsingError::from(err),
+    })
+}
+
+/// The commit HEAD points to, which repositories without commits don't have yet.
+fn head_commit(repo: &Repository) -> Result<git2::Commit<'_>, DiffParsingError> {
+    let head = repo.head().map_err(|err| match err.code() {
+        git2::ErrorCode::UnbornBranch | git2::ErrorCode::NotFound => {
+            DiffParsingError::NoCommits(repo.workdir().unwrap_or(repo.path()).to_path_buf())
+        }
+        _ => DiffParsingError::from(err),
+    })?;
+    Ok(head.peel_to_commit()?)
+}
+
+/// The tree of HEAD, or `None` before the first commit, which leaves everything added.
+fn head_tree(repo: &Repository) -> Result<Option<git2::Tree<'_>>, DiffParsingError> {
+    match head_commit(repo) {
+        Ok(commit) => Ok(Some(commit.tree()?)),
+        Err(DiffParsingError::NoCommits(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Working directory of the repository containing `path`.
+///
+/// For a linked worktree this is the worktree's own checkout, not the main one.
+pub fn repository_root(path: &Path) -> Result<PathBuf, DiffParsingError> {
+    let repo = Repository::discover(path).map_err(|err| match err.code() {
+        git2::ErrorCode::NotFound => DiffParsingError::NotARepository(path.to_path_buf()),
+        _ => DiffParsingError::from(err),
+    })?;
+    // libgit2 reports the working directory with a trailing slash.
+    repo.workdir()
+        .map(|workdir| workdir.components().collect())
+        .ok_or(DiffParsingError::BareRepository(path.to_path_buf()))
+}
+
+/// Name of the worktree at `path` if the repository has more than one, `None` otherwise.
+pub fn worktree_name(path: String) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    if repo.is_worktree() {
+        let worktree = git2::Worktree::open_from_repository(&repo).ok()?;
+        return worktree.name().map(str::to_string);
+    }
+
+    match repo.worktrees().ok()?.is_empty() {
+        true => None,
+        false => Some("main worktree".to_string()),
+    }
+}
+
+/// Short name of the checked out branch, or the short commit id when HEAD is detached.
+pub fn branch_name(path: String) -> Option<String> {
+    let repo = Repository::open(path).ok()?;
+    let head = repo.head().ok()?;
+    match head.is_branch() {
+        true => head.shorthand().map(str::to_string),
+        false => {
+            let id = head.target()?.to_string();
+            Some(id[..7].to_string())
+        }
+    }
+}
+
+/// The checked out branch and how it compares to its upstream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchStatus {
+    /// Short name of the branch, or the short commit id when HEAD is detached.
+    pub name: String,
+    pub detached: bool,
+    pub upstream: Option<Upstream>,
+}
+
+/// The branch a local branch tracks, with the commits only either of them has.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upstream {
+    pub name: String,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+impl BranchStatus {
+    /// E.g. "On main, 2 ahead and 1 behind origin/main".
+    pub fn label_text(&self) -> String {
+        if self.detached {
+            return format!("HEAD detached at {}", self.name);
+        }
+        let Some(upstream) = &self.upstream else {
+            return format!("On {}, without an upstream", self.name);
+        };
+        let counts = match (upstream.ahead, upstream.behind) {
+            (0, 0) => "up to date with".to_string(),
+            (ahead, 0) => format!("{} ahead of", ahead),
+            (0, behind) => format!("{} behind", behind),
+            (ahead, behind) => format!("{} ahead and {} behind", ahead, behind),
+        };
+        format!("On {}, {} {}", self.name, counts, upstream.name)
+    }
+}
+
+/// What [`BranchStatus`] says about the repository at `path`, or `None` before the first
+/// commit.
+pub fn branch_status(path: String) -> Option<BranchStatus> {
+    let repo = Repository::open(path).ok()?;
+    let head = repo.head().ok()?;
+    let local = head.target()?;
+    if !head.is_branch() {
+        return Some(BranchStatus {
+            name: local.to_string()[..7].to_string(),
+            detached: true,
+            upstream: None,
+        });
+    }
+
+    let name = head.shorthand()?.to_string();
+    let upstream = repo
+        .find_branch(&name, git2::BranchType::Local)
+        .and_then(|branch| branch.upstream())
+        .ok()
+        .and_then(|upstream| {
+            let upstream_name = upstream.name().ok()??.to_string();
+            let (ahead, behind) = repo
+                .graph_ahead_behind(local, upstream.get().target()?)
+                .ok()?;
+            Some(Upstream {
+                name: upstream_name,
+                ahead,
+                behind,
+            })
+        });
+    Some(BranchStatus {
+        name,
+        detached: false,
+        upstream,
+    })
+}
+
+/// Reports how many of all files have been processed so far, returning `false` to cancel.
+///
+/// Cancelled listings return an error.
+pub type Progress<'a> = &'a mut dyn FnMut(usize, usize) -> bool;
+
+/// Changes between HEAD and the index, with only each file's lines counted; see [`load_diff`].
+pub fn list_staged_diffs(
+    path: String,
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    list_diffs_from(path, Source::Index, &[], diff_settings, progress)
+}
+
+/// Changes between the index and the working tree, with only each file's lines counted; see
+/// [`load_diff`].
+pub fn list_diffs(
+    path: String,
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    list_diffs_from(path, Source::Workdir, &[], diff_settings, progress)
+}
+
+/// Changes between HEAD and the working tree, staged or not, with only each file's lines
+/// counted; see [`load_diff`].
+pub fn list_combined_diffs(
+    path: String,
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    list_diffs_from(path, Source::Head, &[], diff_settings, progress)
+}
+
+/// What a commit is, for showing next to its changes.
+#[derive(Debug, Clone)]
+pub struct CommitInfo {
+    pub id: String,
+    pub summary: String,
+    pub author: String,
+    pub parent_count: usize,
+    /// When the commit was made, in the committer's time zone.
+    pub time: git2::Time,
+}
+
+impl CommitInfo {
+    fn new(commit: &git2::Commit) -> CommitInfo {
+        CommitInfo {
+            id: commit.id().to_string(),
+            summary: commit.summary().unwrap_or_default().to_string(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            parent_count: commit.parent_count(),
+            time: commit.time(),
+        }
+    }
+
+    /// E.g. "2023-08-14 17:05", in the time zone the commit was made in.
+    pub fn date_text(&self) -> String {
+        let seconds = self.time.seconds() + i64::from(self.time.offset_minutes()) * 60;
+        let (days, minutes) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400) / 60);
+
+        // Converts days since 1970-01-01 to a date of the proleptic Gregorian calendar,
+        // counting years from March so that leap days come last.
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = match month_from_march < 10 {
+            true => month_from_march + 3,
+            false => month_from_march - 9,
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            year,
+            month,
+            day,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+}
+
+/// The commit HEAD points to, or `None` when there are no commits yet.
+pub fn head_commit_info(path: String) -> Option<CommitInfo> {
+    let repo = Repository::open(path).ok()?;
+    let commit = head_commit(&repo).ok()?;
+    Some(CommitInfo::new(&commit))
+}
+
+/// Up to `limit` commits reachable from HEAD, newest first. Empty before the first commit.
+pub fn commit_log(path: String, limit: usize) -> Result<Vec<CommitInfo>, DiffParsingError> {
+    let repo = open_repository(path)?;
+    if head_tree(&repo)?.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    revwalk.push_head()?;
+    revwalk
+        .take(limit)
+        .map(|oid| Ok(CommitInfo::new(&repo.find_commit(oid?)?)))
+        .collect()
+}
+
+/// The commits that changed a file, and the paths it had in them.
+#[derive(Debug, Clone, Default)]
+pub struct FileLog {
+    /// Newest first.
+    pub commits: Vec<CommitInfo>,
+    /// The path of the file, followed by the ones it was renamed from if renames are followed.
+    pub paths: Vec<String>,
+}
+
+/// The most recent commits on HEAD that changed `file`, at most `limit` of them, each
+/// compared to its first parent. With `follow_renames`, the commits from before the file was
+/// renamed are listed too, like `git log --follow`. git doesn't track renames, so a commit
+/// adding the file is checked for a file it looks like it was moved from.
+pub fn file_log(
+    path: String,
+    file: &str,
+    limit: usize,
+    follow_renames: bool,
+) -> Result<FileLog, DiffParsingError> {
+    let repo = open_repository(path)?;
+    let mut log = FileLog {
+        commits: Vec::new(),
+        paths: vec![file.to_string()],
+    };
+    if head_tree(&repo)?.is_none() {
+        return Ok(log);
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    revwalk.push_head()?;
+    let mut current = file.to_string();
+    for oid in revwalk {
+        if log.commits.len() == limit {
+            break;
+        }
+        let commit = repo.find_commit(oid?)?;
+        let tree = commit.tree()?;
+        let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+        let entry_id = |tree: &git2::Tree| tree.get_path(Path::new(&current)).ok().map(|e| e.id());
+        let (id, parent_id) = (entry_id(&tree), parent_tree.as_ref().and_then(entry_id));
+        if id == parent_id {
+            continue;
+        }
+        log.commits.push(CommitInfo::new(&commit));
+
+        if let (true, Some(_), None, Some(parent_tree)) =
+            (follow_renames, id, parent_id, &parent_tree)
+        {
+            if let Some(old_path) = renamed_from(&repo, parent_tree, &tree, &current)? {
+                log.paths.push(old_path.clone());
+                current = old_path;
+            }
+        }
+    }
+    Ok(log)
+}
+
+/// The path `path` of `new_tree` had in `old_tree`, if it was moved there.
+fn renamed_from(
+    repo: &Repository,
+    old_tree: &git2::Tree,
+    new_tree: &git2::Tree,
+    path: &str,
+) -> Result<Option<String>, DiffParsingError> {
+    let mut diff = repo.diff_tree_to_tree(Some(old_tree), Some(new_tree), None)?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    let renamed = diff.deltas().find(|delta| {
+        delta.status() == Delta::Renamed && delta.new_file().path() == Some(Path::new(path))
+    });
+    Ok(renamed
+        .and_then(|delta| delta.old_file().path())
+        .map(|old_path| old_path.to_string_lossy().to_string()))
+}
+
+/// An entry of the stash list, `index` being its place in `stash@{index}`.
+#[derive(Debug, Clone)]
+pub struct StashInfo {
+    pub index: usize,
+    pub message: String,
+}
+
+pub fn list_stashes(path: String) -> Result<Vec<StashInfo>, DiffParsingError> {
+    let mut repo = open_repository(path)?;
+    let mut stashes = Vec::new();
+    repo.stash_foreach(|index, message, _| {
+        stashes.push(StashInfo {
+            index,
+            message: message.to_string(),
+        });
+        true
+    })
+    .map_err(DiffParsingError::from)?;
+
+    Ok(stashes)
+}
+
+/// What popping `stash@{index}` would change in the working tree, without applying it.
+///
+/// The working tree, including staged changes, is the old side and the stashed tree the new
+/// one. Untracked files saved in the stash are left out.
+pub fn stash_vs_worktree(
+    path: String,
+    index: usize,
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(StashInfo, Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    let mut repo = open_repository(&path)?;
+    let mut found = None;
+    repo.stash_foreach(|i, message, id| {
+        if i == index {
+            found = Some((
+                StashInfo {
+                    index,
+                    message: message.to_string(),
+                },
+                *id,
+            ));
+        }
+        i < index
+    })
+    .map_err(DiffParsingError::from)?;
+    let (info, id) = found.ok_or(DiffParsingError::InvalidRevspec(format!(
+        "stash@{{{}}}",
+        index
+    )))?;
+
+    let (diffs, stats, skipped) =
+        list_diffs_from(path, Source::Stash(id), &[], diff_settings, progress)?;
+    Ok((info, diffs, stats, skipped))
+}
+
+/// Changes a commit made, compared to its first parent, with only each file's lines counted;
+/// see [`load_diff`].
+pub fn list_commit_diffs(
+    path: String,
+    revspec: &str,
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(CommitInfo, Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    let repo = open_repository(&path)?;
+    let commit = repo
+        .revparse_single(revspec)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|_| DiffParsingError::InvalidRevspec(revspec.to_string()))?;
+    let info = CommitInfo::new(&commit);
+
+    let (diffs, stats, skipped) = list_diffs_from(
+        path,
+        Source::Commit(commit.id()),
+        &[],
+        diff_settings,
+        progress,
+    )?;
+    Ok((info, diffs, stats, skipped))
+}
+
+/// Two revisions of a repository compared with each other.
+#[derive(Debug, Clone)]
+pub struct RevisionRange {
+    /// The revisions as they were given, e.g. branch names, so a refresh looks them up again.
+    pub from: String,
+    pub to: String,
+    /// Whether the changes start at the merge-base of both rather than at `from`, like
+    /// `git diff from...to`, so that only what `to` changed since it branched off is shown.
+    pub merge_base: bool,
+    /// The only files compared, if not all of them, e.g. for the history of one file.
+    pub paths: Vec<String>,
+    /// The commit the changes start at, i.e. the merge-base if `merge_base` is set.
+    pub from_commit: CommitInfo,
+    pub to_commit: CommitInfo,
+}
+
+impl RevisionRange {
+    /// The range as git writes it, e.g. `main...feature`.
+    pub fn notation(&self) -> String {
+        let dots = match self.merge_base {
+            true => "...",
+            false => "..",
+        };
+        format!("{}{}{}", self.from, dots, self.to)
+    }
+}
+
+/// Changes from the commit `from` names, or its merge-base with `to` if `merge_base` is set,
+/// to the one `to` names, with only each file's lines counted; see [`load_diff`]. Both can be
+/// anything git resolves to a commit, e.g. ids, branch names or tags. Only `paths` are
+/// compared, unless there are none.
+pub fn list_diffs_between(
+    path: String,
+    from: &str,
+    to: &str,
+    merge_base: bool,
+    paths: &[String],
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(RevisionRange, Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    let repo = open_repository(&path)?;
+    let resolve = |revspec: &str| {
+        repo.revparse_single(revspec)
+            .and_then(|object| object.peel_to_commit())
+            .map_err(|_| DiffParsingError::InvalidRevspec(revspec.to_string()))
+    };
+    let (mut from_commit, to_commit) = (resolve(from)?, resolve(to)?);
+    if merge_base {
+        let base = repo.merge_base(from_commit.id(), to_commit.id())?;
+        from_commit = repo.find_commit(base)?;
+    }
+    let range = RevisionRange {
+        from: from.to_string(),
+        to: to.to_string(),
+        merge_base,
+        paths: paths.to_vec(),
+        from_commit: CommitInfo::new(&from_commit),
+        to_commit: CommitInfo::new(&to_commit),
+    };
+
+    let source = Source::Range(from_commit.id(), to_commit.id());
+    let (diffs, stats, skipped) = list_diffs_from(path, source, paths, diff_settings, progress)?;
+    Ok((range, diffs, stats, skipped))
+}
+
+/// Computes the lines of a diff returned by [`list_diffs`], [`list_staged_diffs`] or
+/// [`list_combined_diffs`].
+pub fn load_diff(path: String, diff: &Diff) -> Result<Diff, DiffParsingError> {
+    let repo = open_repository(path)?;
+    let mut options = diff.diff_settings.source_options(diff.source);
+    options
+        .pathspec(&diff.old_file.path)
+        .pathspec(&diff.new_file.path)
+        .disable_pathspec_match(true);
+    let diffs = source_diff(&repo, diff.source, Some(&mut options))?;
+
+    let (diffs, _) = parse_diffs(&repo, diffs, diff.source, diff.diff_settings)?;
+    diffs
+        .into_iter()
+        .find(|loaded| {
+            loaded.old_file.path == diff.old_file.path && loaded.new_file.path == diff.new_file.path
+        })
+        .ok_or(DiffParsingError::Libgit2(format!(
+            "{} has no changes anymore",
+            diff.file_name()
+        )))
+}
+
+/// The commit that last changed line `line_no` of the old side of `diff`, or `None` if the
+/// line isn't committed yet, e.g. because it was only staged.
+pub fn blame_line(
+    path: String,
+    diff: &Diff,
+    line_no: u32,
+) -> Result<Option<CommitInfo>, DiffParsingError> {
+    let repo = open_repository(path)?;
+    let newest = match diff.source {
+        Source::Commit(id) => {
+            let commit = repo.find_commit(id).map_err(DiffParsingError::from)?;
+            let Ok(parent) = commit.parent(0) else {
+                return Ok(None);
+            };
+            parent
+        }
+        Source::Range(from, _) => repo.find_commit(from)?,
+        _ => head_commit(&repo)?,
+    };
+    let Ok(entry) = newest
+        .tree()
+        .and_then(|tree| tree.get_path(&diff.old_file.path))
+    else {
+        return Ok(None);
+    };
+    let committed = repo.find_blob(entry.id()).map_err(DiffParsingError::from)?;
+
+    // The old side can be the index or the working tree, which the history doesn't know
+    // about, so the line is found in the committed version first.
+    let content = file_contents(&repo, &diff.old_file, diff.source.reads_workdir(false))?;
+    let Some(line_no) = unchanged_line(committed.content(), &content, line_no)? else {
+        return Ok(None);
+    };
+    let mut options = git2::BlameOptions::new();
+    options.newest_commit(newest.id());
+    let blame = repo
+        .blame_file(&diff.old_file.path, Some(&mut options))
+        .map_err(DiffParsingError::from)?;
+    let Some(id) = blame
+        .get_line(line_no as usize)
+        .map(|hunk| hunk.final_commit_id())
+    else {
+        return Ok(None);
+    };
+    let commit = repo.find_commit(id).map_err(DiffParsingError::from)?;
+    Ok(Some(CommitInfo::new(&commit)))
+}
+
+/// The line of `old` that line `line_no` of `new` was kept from, or `None` if it was added.
+fn unchanged_line(old: &[u8], new: &[u8], line_no: u32) -> Result<Option<u32>, DiffParsingError> {
+    let patch =
+        git2::Patch::from_buffers(old, None, new, None, None).map_err(DiffParsingError::from)?;
+    // Lines after a hunk are shifted by what it added and removed.
+    let mut offset: i64 = 0;
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_index).map_err(DiffParsingError::from)?;
+        if hunk.new_start() > line_no {
+            break;
+        }
+        for i in 0..line_count {
+            let line = patch
+                .line_in_hunk(hunk_index, i)
+                .map_err(DiffParsingError::from)?;
+            if line.new_lineno() == Some(line_no) {
+                return Ok(line.old_lineno());
+            }
+        }
+        offset = (hunk.old_start() + hunk.old_lines()) as i64
+            - (hunk.new_start() + hunk.new_lines()) as i64;
+    }
+    Ok(u32::try_from(line_no as i64 + offset).ok())
+}
+
+fn list_diffs_from(
+    path: String,
+    source: Source,
+    paths: &[String],
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    let repo = open_repository(path)?;
+    let options = &mut diff_settings.source_options(source);
+    if !paths.is_empty() {
+        for path in paths {
+            options.pathspec(path);
+        }
+        options.disable_pathspec_match(true);
+    }
+    let diffs = source_diff(&repo, source, Some(options))?;
+
+    let mut result = Vec::new();
+    let mut skipped = Vec::new();
+    let total = diffs.deltas().len();
+    for (i, delta) in diffs.deltas().enumerate() {
+        if !progress(i, total) {
+            return Err(DiffParsingError::Cancelled);
+        }
+        // Generating the patch is what tells libgit2 whether the file is binary.
+        // It is also where files that can't be read (e.g. locked ones) fail.
+        let listed = git2::Patch::from_diff(&diffs, i).and_then(|patch| {
+            let line_stats = match &patch {
+                Some(patch) => patch.line_stats()?,
+                None => (0, 0, 0),
+            };
+            Ok((patch, line_stats))
+        });
+        let (patch, (_, insertions, deletions)) = match listed {
+            Ok(listed) => listed,
+            Err(err) => {
+                skipped.push(SkippedFile {
+                    path: delta
+                        .new_file()
+                        .path()
+                        .or(delta.old_file().path())
+                        .map_or("unknown file".into(), |path| path.display().to_string()),
+                    reason: err.message().to_string(),
+                });
+                continue;
+            }
+        };
+        let delta = patch.as_ref().map_or(delta, git2::Patch::delta);
+
+        let no_path = || DiffParsingError::Libgit2("a changed file has no path".to_string());
+        let old_file = DiffFile::new(delta.old_file()).ok_or_else(no_path)?;
+        let new_file = DiffFile::new(delta.new_file()).ok_or_else(no_path)?;
+        result.push(Diff::unloaded(
+            old_file,
+            new_file,
+            delta.status(),
+            source,
+            diff_settings,
+            delta.flags().contains(DiffFlags::BINARY),
+            (insertions, deletions),
+        ));
+    }
+
+    let stats = Stats::of(&result);
+    Ok((result, stats, skipped))
+}
+
+/// The changes of `source`, with renamed files paired up rather than deleted and added.
+fn source_diff<'a>(
+    repo: &'a Repository,
+    source: Source,
+    options: Option<&mut DiffOptions>,
+) -> Result<git2::Diff<'a>, DiffParsingError> {
+    let mut diff = match source {
+        Source::Index => repo.diff_tree_to_index(head_tree(repo)?.as_ref(), None, options),
+        Source::Workdir => repo.diff_index_to_workdir(None, options),
+        Source::Head => repo.diff_tree_to_workdir_with_index(head_tree(repo)?.as_ref(), options),
+        Source::Commit(id) => {
+            let commit = repo.find_commit(id).map_err(DiffParsingError::from)?;
+            let tree = commit.tree().map_err(DiffParsingError::from)?;
+            // Root commits are diffed against an empty tree.
+            let parent_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree().map_err(DiffParsingError::from)?),
+                Err(_) => None,
+            };
+            repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), options)
+        }
+        Source::Range(from, to) => {
+            let tree = |id: Oid| repo.find_commit(id).and_then(|commit| commit.tree());
+            repo.diff_tree_to_tree(Some(&tree(from)?), Some(&tree(to)?), options)
+        }
+        Source::Stash(id) => {
+            let tree = repo
+                .find_commit(id)
+                .and_then(|commit| commit.tree())
+                .map_err(DiffParsingError::from)?;
+            let mut default_options = DiffOptions::new();
+            let options = options.unwrap_or(&mut default_options);
+            // Reversed, the working tree is the old side and the stash the new one.
+            repo.diff_tree_to_workdir_with_index(Some(&tree), Some(options.reverse(true)))
+        }
+    }?;
+    diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+    Ok(diff)
+}
+
+/// Time of the most recent commit on HEAD touching each of `files`, in seconds since the epoch.
+///
+/// Files that don't exist on HEAD are left out.
+pub fn last_commit_times(
+    path: String,
+    files: Vec<String>,
+) -> Result<HashMap<String, i64>, DiffParsingError> {
+    let repo = open_repository(path)?;
+    let head = head_commit(&repo)?.tree()?;
+
+    let mut remaining = files
+        .into_iter()
+        .filter(|file| head.get_path(Path::new(file)).is_ok())
+        .collect::<Vec<String>>();
+    let mut times = HashMap::new();
+
+    let mut revwalk = repo.revwalk().map_err(DiffParsingError::from)?;
+    revwalk.push_head().map_err(DiffParsingError::from)?;
+    for oid in revwalk {
+        if remaining.is_empty() {
+            break;
+        }
+
+        let commit = repo
+            .find_commit(oid.map_err(DiffParsingError::from)?)
+            .map_err(DiffParsingError::from)?;
+        let tree = commit.tree().map_err(DiffParsingError::from)?;
+        let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+
+        remaining.retain(|file| {
+            let entry_id = |tree: &git2::Tree| tree.get_path(Path::new(file)).ok().map(|e| e.id());
+            if entry_id(&tree) == parent_tree.as_ref().and_then(entry_id) {
+                return true;
+            }
+            times.insert(file.clone(), commit.time().seconds());
+            false
+        });
+    }
+
+    Ok(times)
+}
+
+/// Modification time of each of `files` in the working tree, in seconds since the epoch.
+pub fn modified_times(path: String, files: Vec<String>) -> HashMap<String, i64> {
+    let Ok(repo) = Repository::open(path) else {
+        return HashMap::new();
+    };
+    let Some(workdir) = repo.workdir() else {
+        return HashMap::new();
+    };
+
+    files
+        .into_iter()
+        .filter_map(|file| {
+            let modified = fs::metadata(workdir.join(&file)).ok()?.modified().ok()?;
+            let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+            Some((file, seconds as i64))
+        })
+        .collect()
+}
+
+/// Number of diffs written to temporary files, so each gets a directory of its own.
+static TEMP_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes the old and new contents of `diff` to temporary files and returns their paths.
+/// They are put in a directory no other call or instance uses, so a tool still reading the
+/// files of an earlier call doesn't see them change.
+pub fn write_sides_to_temp(
+    path: String,
+    diff: &Diff,
+) -> Result<(PathBuf, PathBuf), DiffParsingError> {
+    let repo = open_repository(path)?;
+    let old_content = file_contents(&repo, &diff.old_file, diff.source.reads_workdir(false))?;
+    let new_content = file_contents(&repo, &diff.new_file, diff.source.reads_workdir(true))?;
+
+    let count = TEMP_DIRS.fetch_add(1, Ordering::Relaxed);
+    let temp_dir = env::temp_dir().join(format!("contrast-{}-{}", process::id(), count));
+    fs::create_dir_all(&temp_dir).map_err(|err| DiffParsingError::io(&temp_dir, err))?;
+
+    let file_name = diff
+        .new_file
+        .path
+        .file_name()
+        .ok_or(DiffParsingError::Io(
+            diff.new_file.path.clone(),
+            "not a file".to_string(),
+        ))?
+        .to_string_lossy();
+    let old_path = temp_dir.join(format!("old_{}", file_name));
+    let new_path = temp_dir.join(format!("new_{}", file_name));
+
+    fs::write(&old_path, old_content).map_err(|err| DiffParsingError::io(&old_path, err))?;
+    fs::write(&new_path, new_content).map_err(|err| DiffParsingError::io(&new_path, err))?;
+
+    Ok((old_path, new_path))
+}
+
+/// Contents of one side of a diff.
+///
+/// Working tree contents are hashed but not written to the object database,
+/// so with `from_workdir` the file itself is read instead.
+fn file_contents(
+    repo: &Repository,
+    file: &DiffFile,
+    from_workdir: bool,
+) -> Result<Vec<u8>, DiffParsingError> {
+    if !file.id.is_zero() {
+        if let Ok(blob) = repo.find_blob(file.id) {
+            return Ok(blob.content().to_vec());
+        }
+    }
+
+    match repo.workdir() {
+        Some(workdir) if from_workdir && workdir.join(&file.path).is_file() => {
+            let path = workdir.join(&file.path);
+            fs::read(&path).map_err(|err| DiffParsingError::io(&path, err))
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Re-diffs a file git considered binary if it turns out to be UTF-16 text.
+///
+/// Returns the decoded diff along with its insertion and deletion counts.
+fn decode_utf16_diff(repo: &Repository, diff: &Diff) -> Option<(Diff, usize, usize)> {
+    let old_content = file_contents(repo, &diff.old_file, diff.source.reads_workdir(false)).ok()?;
+    let new_content = file_contents(repo, &diff.new_file, diff.source.reads_workdir(true)).ok()?;
+
+    let sample = match old_content.is_empty() {
+        true => &new_content,
+        false => &old_content,
+    };
+    if !matches!(
+        Encoding::detect(sample),
+        Encoding::Utf16Le | Encoding::Utf16Be
+    ) {
+        return None;
+    }
+
+    let (mut decoded, insertions, deletions) = diff_buffers(
+        diff.old_file.clone(),
+        diff.new_file.clone(),
+        diff.status,
+        (&old_content, &new_content),
+        diff.diff_settings,
+    )?;
+    decoded.source = diff.source;
+
+    Some((decoded, insertions, deletions))
+}
+
+/// Diffs two versions of a file held in memory, decoding UTF-16 contents first.
+///
+/// Returns the diff along with its insertion and deletion counts.
+fn diff_buffers(
+    old_file: DiffFile,
+    new_file: DiffFile,
+    status: Delta,
+    (old_content, new_content): (&[u8], &[u8]),
+    diff_settings: DiffSettings,
+) -> Option<(Diff, usize, usize)> {
+    let sample = match old_content.is_empty() {
+        true => new_content,
+        false => old_content,
+    };
+    let encoding = Encoding::detect(sample);
+    let (old_text, new_text) = match encoding {
+        Encoding::Utf16Le | Encoding::Utf16Be => (
+            encoding.decode(old_content).into_bytes(),
+            encoding.decode(new_content).into_bytes(),
+        ),
+        Encoding::Utf8 | Encoding::Utf8Bom => (old_content.to_vec(), new_content.to_vec()),
+    };
+    let patch = git2::Patch::from_buffers(
+        &old_text,
+        Some(&old_file.path),
+        &new_text,
+        Some(&new_file.path),
+        Some(&mut diff_settings.options()),
+    )
+    .ok()?;
+    let is_binary = patch.delta().flags().contains(DiffFlags::BINARY);
+
+    let mut headers = Vec::new();
+    let mut lines = Vec::new();
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, line_count) = patch.hunk(hunk_index).ok()?;
+        let mut header =
+            Header::new(strip_line_ending(std::str::from_utf8(hunk.header()).ok()?)).ok()?;
+        header.first_line = lines.len();
+        headers.push(header);
+
+        for line_index in 0..line_count {
+            let line = patch.line_in_hunk(hunk_index, line_index).ok()?;
+            let content = strip_line_ending(std::str::from_utf8(line.content()).ok()?);
+            lines.push(Line::new(
+                line.old_lineno(),
+                line.new_lineno(),
+                content,
+                line.origin(),
+            ));
+        }
+    }
+
+    let (_, insertions, deletions) = patch.line_stats().ok()?;
+    let byte_order_marks = strip_byte_order_marks(&mut lines);
+    let mut diff = Diff::new(old_file, new_file, status, is_binary, headers, lines);
+    diff.encoding = encoding;
+    diff.note_byte_order_marks(byte_order_marks);
+    diff.diff_settings = diff_settings;
+    diff.sizes = Some(FileSizes::measure(old_content, new_content));
+
+    Some((diff, insertions, deletions))
+}
+
+/// Changes between two directory trees that don't need to be in a repository.
+///
+/// Files are matched by their path relative to each directory; files present in only one
+/// of them are added or deleted. `.git` directories are left out.
+pub fn diff_directories(
+    old_dir: &Path,
+    new_dir: &Path,
+    diff_settings: DiffSettings,
+    progress: Progress,
+) -> Result<(Vec<Diff>, Stats, Vec<SkippedFile>), DiffParsingError> {
+    let mut paths = Vec::new();
+    walk_directory(old_dir, Path::new(""), &mut paths)
+        .map_err(|err| DiffParsingError::io(old_dir, err))?;
+    walk_directory(new_dir, Path::new(""), &mut paths)
+        .map_err(|err| DiffParsingError::io(new_dir, err))?;
+    paths.sort();
+    paths.dedup();
+
+    let mut result = Vec::new();
+    let mut skipped = Vec::new();
+    let total = paths.len();
+    for (i, path) in paths.into_iter().enumerate() {
+        if !progress(i, total) {
+            return Err(DiffParsingError::Cancelled);
+        }
+        let read = |dir: &Path| match dir.join(&path).is_file() {
+            true => fs::read(dir.join(&path)).map(Some),
+            false => Ok(None),
+        };
+        let (old_content, new_content) = match (read(old_dir), read(new_dir)) {
+            (Ok(old), Ok(new)) => (old, new),
+            (Err(err), _) | (_, Err(err)) => {
+                skipped.push(SkippedFile {
+                    path: path.display().to_string(),
+                    reason: err.to_string(),
+                });
+                continue;
+            }
+        };
+        if old_content == new_content {
+            continue;
+        }
+
+        let status = match (&old_content, &new_content) {
+            (None, _) => Delta::Added,
+            (_, None) => Delta::Deleted,
+            _ => Delta::Modified,
+        };
+        let side = |content: Option<&[u8]>| DiffFile {
+            path: path.clone(),
+            id: content
+                .and_then(|content| Oid::hash_object(git2::ObjectType::Blob, content).ok())
+                .unwrap_or_else(Oid::zero),
+            size: content.map_or(0, |content| content.len() as u64),
+            // Like git, a missing side has no mode.
+            mode: match content {
+                Some(_) => FileMode::Blob,
+                None => FileMode::Unreadable,
+            },
+        };
+        let Some((diff, _, _)) = diff_buffers(
+            side(old_content.as_deref()),
+            side(new_content.as_deref()),
+            status,
+            (
+                old_content.as_deref().unwrap_or_default(),
+                new_content.as_deref().unwrap_or_default(),
+            ),
+            diff_settings,
+        ) else {
+            skipped.push(SkippedFile {
+                path: path.display().to_string(),
+                reason: "Could not diff the file".to_string(),
+            });
+            continue;
+        };
+        result.push(diff);
+    }
+
+    let stats = Stats::of(&result);
+    Ok((result, stats, skipped))
+}
+
+/// Collects the paths of the files below `dir`, relative to the directory the walk started in.
+fn walk_directory(dir: &Path, relative: &Path, paths: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() && entry.file_name() != ".git" {
+            walk_directory(&entry.path(), &relative, paths)?;
+        } else if file_type.is_file() {
+            paths.push(relative);
+        }
+    }
+
+    Ok(())
+}
+
+fn strip_line_ending(content: &str) -> String {
+    let mut content = content.to_string();
+    if content.ends_with('\n') {
+        content.pop();
+        if content.ends_with('\r') {
+            content.pop();
+        }
+    }
+    content
+}
+
+/// Lines of a single file, along with the index of the first line of each hunk.
+#[derive(Default)]
+struct LineGroup {
+    lines: Vec<Line>,
+    hunk_starts: Vec<usize>,
+    current_hunk: Option<(u32, u32)>,
+}
+
+/// Strips the byte order mark off the first line of each side among `lines`. Returns
+/// whether each side starts with one, if its first line is among them.
+fn strip_byte_order_marks(lines: &mut [Line]) -> (Option<bool>, Option<bool>) {
+    let (mut old, mut new) = (None, None);
+    for line in lines {
+        let (old_first, new_first) = (line.old_lineno == Some(1), line.new_lineno == Some(1));
+        if !old_first && !new_first {
+            continue;
+        }
+        let has_bom = match line.content.strip_prefix('\u{feff}') {
+            Some(stripped) => {
+                line.content = stripped.to_string();
+                true
+            }
+            None => false,
+        };
+        if old_first {
+            old = Some(has_bom);
+        }
+        if new_first {
+            new = Some(has_bom);
+        }
+    }
+    (old, new)
+}
+
+fn parse_diffs(
+    repo: &Repository,
+    diffs: git2::Diff,
+    source: Source,
+    diff_settings: DiffSettings,
+) -> Result<(Vec<Diff>, Stats), DiffParsingError> {
+    let line_groups = Rc::new(RefCell::new(Vec::new()));
+    diffs
+        .foreach(
+            &mut |_delta, _num| {
+                line_groups.borrow_mut().push(LineGroup::default());
+                true
+            },
+            None,
+            None,
+            Some(
+                &mut |_delta, _hunk, _line| match std::str::from_utf8(_line.content()) {
+                    Ok(c) => {
+                        let line = Line::new(
+                            _line.old_lineno(),
+                            _line.new_lineno(),
+                            strip_line_ending(c),
+                            _line.origin(),
+                        );
+
+                        let hunk = _hunk.map(|h| (h.old_start(), h.new_start()));
+                        match line_groups.borrow_mut().last_mut() {
+                            Some(last) => {
+                                if hunk != last.current_hunk {
+                                    last.hunk_starts.push(last.lines.len());
+                                    last.current_hunk = hunk;
+                                }
+                                last.lines.push(line);
+                                true
+                            }
+                            None => false,
+                        }
+                    }
+                    Err(_) => false,
+                },
+            ),
+        )
+        .map_err(DiffParsingError::from)?;
+
+    let header_groups = Rc::new(RefCell::new(Vec::new()));
+    diffs
         .foreach(
             &mut |_delta, _num| {
                 header_groups.borrow_mut().push(Vec::new());
                 true
-            },
-            None,
-            Some(&mut |_delta, _hunk| {
-                let mut content = std::str::from_utf8(_hunk.header()).unwrap().to_string();
-                if content.ends_with('\n') {
-                    content.pop();
-                    if content.ends_with('\r') {
-                        content.pop();
-                    }
-                }
+            },
+            None,
+            Some(&mut |_delta, _hunk| {
+                let content = strip_line_ending(std::str::from_utf8(_hunk.header()).unwrap());
+
+                match Header::new(content) {
+                    Ok(header) => match header_groups.borrow_mut().last_mut() {
+                        Some(last) => {
+                            last.push(header);
+                            true
+                        }
+                        None => false,
+                    },
+                    Err(_) => false,
+                }
+            }),
+            None,
+        )
+        .map_err(DiffParsingError::from)?;
+
+    let mut result = Vec::new();
+    diffs
+        .foreach(
+            &mut |_delta, _num| {
+                let Some(old_file) = DiffFile::new(_delta.old_file()) else {
+                    return false;
+                };
+
+                let Some(new_file) = DiffFile::new(_delta.new_file()) else {
+                    return false;
+                };
+                let mut hg = header_groups.borrow_mut();
+                let Some(headers) = hg.first() else {
+                    return false;
+                };
+
+                let mut lg = line_groups.borrow_mut();
+                let Some(line_group) = lg.first() else {
+                    return false;
+                };
+
+                let mut headers = headers.to_vec();
+                for (header, first_line) in headers.iter_mut().zip(&line_group.hunk_starts) {
+                    header.first_line = *first_line;
+                }
+
+                let mut lines = line_group.lines.to_vec();
+                let byte_order_marks = strip_byte_order_marks(&mut lines);
+                let mut diff = Diff::new(
+                    old_file,
+                    new_file,
+                    _delta.status(),
+                    _delta.flags().contains(DiffFlags::BINARY),
+                    headers,
+                    lines,
+                );
+                diff.note_byte_order_marks(byte_order_marks);
+                diff.source = source;
+                diff.diff_settings = diff_settings;
+                result.push(diff);
+
+                hg.remove(0);
+                lg.remove(0);
+                true
+            },
+            None,
+            None,
+            None,
+        )
+        .map_err(DiffParsingError::from)?;
+
+    let mut stats = Stats::new(diffs.stats().map_err(DiffParsingError::from)?);
+    for diff in result.iter_mut().filter(|diff| diff.is_binary) {
+        if let Some((decoded, insertions, deletions)) = decode_utf16_diff(repo, diff) {
+            *diff = decoded;
+            stats.insertions += insertions;
+            stats.deletions += deletions;
+        }
+    }
+
+    for diff in result.iter_mut() {
+        diff.line_ending_change = line_ending_change(repo, diff);
+        if diff.sizes.is_none() {
+            diff.sizes = file_contents(repo, &diff.old_file, diff.source.reads_workdir(false))
+                .and_then(|old| {
+                    Ok(FileSizes::measure(
+                        &old,
+                        &file_contents(repo, &diff.new_file, diff.source.reads_workdir(true))?,
+                    ))
+                })
+                .ok();
+        }
+    }
+
+    Ok((result, stats))
+}
+
+/// Checks whether every removed line was added back, differing only in its line ending.
+fn line_ending_change(repo: &Repository, diff: &Diff) -> Option<(LineEnding, LineEnding)> {
+    let removed = diff
+        .lines
+        .iter()
+        .filter(|line| line.kind() == LineKind::Deletion);
+    let added = diff
+        .lines
+        .iter()
+        .filter(|line| line.kind() == LineKind::Insertion);
+    if diff.deletions == 0
+        || !removed
+            .map(|line| &line.content)
+            .eq(added.map(|line| &line.content))
+    {
+        return None;
+    }
+
+    let old_content = file_contents(repo, &diff.old_file, diff.source.reads_workdir(false)).ok()?;
+    let new_content = file_contents(repo, &diff.new_file, diff.source.reads_workdir(true)).ok()?;
+    let normalize = |content: &[u8]| String::from_utf8_lossy(content).replace("\r\n", "\n");
+    if old_content == new_content || normalize(&old_content) != normalize(&new_content) {
+        return None;
+    }
+
+    Some((
+        LineEnding::detect(&old_content),
+        LineEnding::detect(&new_content),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let (diffs, _) = get_diffs(".".to_owned()).unwrap();
+        for diff in diffs {
+            println!("{:#?}", diff);
+        }
+    }
+
+    #[test]
+    fn common_directory_of_diffs() {
+        let diff = |path: &str| {
+            let file = DiffFile {
+                path: PathBuf::from(path),
+                id: Oid::zero(),
+                size: 0,
+                mode: FileMode::Blob,
+            };
+            Diff::new(
+                file.clone(),
+                file,
+                Delta::Modified,
+                false,
+                Vec::new(),
+                Vec::new(),
+            )
+        };
+
+        let diffs = vec![diff("src/ui/code.rs"), diff("src/ui/mod.rs")];
+        assert_eq!(common_directory(&diffs), PathBuf::from("src/ui"));
+
+        let diffs = vec![diff("src/ui/code.rs"), diff("src/git.rs")];
+        assert_eq!(common_directory(&diffs), PathBuf::from("src"));
+
+        let diffs = vec![diff("src/git.rs"), diff("README.md")];
+        assert_eq!(common_directory(&diffs), PathBuf::new());
+    }
+
+    /// Lists the working tree changes and loads every one of them.
+    fn get_diffs(path: String) -> Result<(Vec<Diff>, Stats), DiffParsingError> {
+        let (diffs, _, _) = list_diffs(path.clone(), DiffSettings::default(), &mut |_, _| true)?;
+        let diffs = diffs
+            .iter()
+            .map(|diff| load_diff(path.clone(), diff))
+            .collect::<Result<Vec<Diff>, DiffParsingError>>()?;
+
+        let stats = Stats::of(&diffs);
+        Ok((diffs, stats))
+    }
+
+    fn test_repo(name: &str, files: &[(&str, &[u8])]) -> (PathBuf, Repository) {
+        let path = env::temp_dir().join(format!("contrast_test_{}", name));
+        let _ = fs::remove_dir_all(&path);
+        let repo = Repository::init(&path).unwrap();
+        commit_files(&repo, &path, files, "Initial");
+
+        (path, repo)
+    }
+
+    /// Writes `files` and commits them on top of HEAD, if there is one.
+    fn commit_files<C: AsRef<[u8]>>(
+        repo: &Repository,
+        path: &Path,
+        files: &[(&str, C)],
+        message: &str,
+    ) -> Oid {
+        let mut index = repo.index().unwrap();
+        for (file, content) in files {
+            fs::write(path.join(file), content).unwrap();
+            index.add_path(Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn combined_diffs_have_staged_and_unstaged_changes() {
+        let (path, repo) = test_repo("combined", &[("a.txt", b"one\n"), ("b.txt", b"two\n")]);
+        fs::write(path.join("a.txt"), "1\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(path.join("b.txt"), "2\n").unwrap();
+
+        let path = path.to_str().unwrap().to_string();
+        let names_and_insertions = |listed: Result<(Vec<Diff>, Stats, _), _>| {
+            let (diffs, stats, _) = listed.unwrap();
+            let names = diffs.iter().map(Diff::file_name).collect::<Vec<_>>();
+            (names, stats.insertions)
+        };
+        let settings = DiffSettings::default();
+        assert_eq!(
+            names_and_insertions(list_staged_diffs(path.clone(), settings, &mut |_, _| true)),
+            (vec!["a.txt".to_string()], 1)
+        );
+        assert_eq!(
+            names_and_insertions(list_diffs(path.clone(), settings, &mut |_, _| true)),
+            (vec!["b.txt".to_string()], 1)
+        );
+        assert_eq!(
+            names_and_insertions(list_combined_diffs(path, settings, &mut |_, _| true)),
+            (vec!["a.txt".to_string(), "b.txt".to_string()], 2)
+        );
+    }
+
+    #[test]
+    fn sides_are_written_to_a_directory_per_call() {
+        let (path, _repo) = test_repo("temp_sides", &[("a.txt", b"one\n")]);
+        fs::write(path.join("a.txt"), "1\n").unwrap();
+
+        let path = path.display().to_string();
+        let settings = DiffSettings::default();
+        let (diffs, _, _) = list_diffs(path.clone(), settings, &mut |_, _| true).unwrap();
+        let (old, new) = write_sides_to_temp(path.clone(), &diffs[0]).unwrap();
+        let (old_again, _) = write_sides_to_temp(path, &diffs[0]).unwrap();
+
+        assert_eq!(fs::read_to_string(&old).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(&new).unwrap(), "1\n");
+        assert_eq!(old.parent(), new.parent());
+        assert_ne!(old.parent(), old_again.parent());
+    }
+
+    #[test]
+    fn untracked_files_are_listed_when_asked_for() {
+        let (path, _repo) = test_repo("untracked", &[("a.txt", b"one\n")]);
+        fs::write(path.join("a.txt"), "1\n").unwrap();
+        fs::create_dir(path.join("new")).unwrap();
+        fs::write(path.join("new/b.txt"), "two\nlines\n").unwrap();
+
+        let path = path.display().to_string();
+        let list = |include_untracked: bool| {
+            let diff_settings = DiffSettings {
+                include_untracked,
+                ..DiffSettings::default()
+            };
+            list_diffs(path.clone(), diff_settings, &mut |_, _| true).unwrap()
+        };
+        assert_eq!(list(false).0.len(), 1);
+
+        let (diffs, stats, _) = list(true);
+        let names = diffs.iter().map(Diff::file_name).collect::<Vec<_>>();
+        assert_eq!(names, ["a.txt", "new/b.txt"]);
+        assert!(diffs[1].is_untracked() && !diffs[0].is_untracked());
+        // Untracked files are counted apart from the changes.
+        assert_eq!(
+            (stats.files_changed, stats.insertions, stats.deletions),
+            (1, 1, 1)
+        );
+        assert_eq!((stats.untracked_files, stats.untracked_insertions), (1, 2));
+
+        let loaded = load_diff(path, &diffs[1]).unwrap();
+        assert!(loaded
+            .lines
+            .iter()
+            .all(|line| line.kind() == LineKind::Insertion));
+        assert_eq!(loaded.lines.len(), 2);
+        assert!(loaded.to_string().contains("--- /dev/null"));
+    }
+
+    #[test]
+    fn stats_add_up_the_files() {
+        let (path, repo) = test_repo("stats", &[("a.txt", b"1\n2\n3\n"), ("b.txt", b"x\n")]);
+        fs::write(path.join("a.txt"), "1\ntwo\n3\n4\n").unwrap();
+        fs::write(path.join("b.txt"), "").unwrap();
+
+        let (diffs, stats, _) = list_diffs(
+            path.display().to_string(),
+            DiffSettings::default(),
+            &mut |_, _| true,
+        )
+        .unwrap();
+        let counts = diffs
+            .iter()
+            .map(|diff| (diff.insertions(), diff.deletions()))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [(2, 1), (0, 1)]);
+        assert_eq!(stats.insertions, counts.iter().map(|count| count.0).sum());
+        assert_eq!(stats.deletions, counts.iter().map(|count| count.1).sum());
+
+        // And they are what git counts.
+        let git_stats = Stats::new(
+            repo.diff_index_to_workdir(None, None)
+                .unwrap()
+                .stats()
+                .unwrap(),
+        );
+        assert_eq!(
+            (stats.files_changed, stats.insertions, stats.deletions),
+            (
+                git_stats.files_changed,
+                git_stats.insertions,
+                git_stats.deletions
+            )
+        );
+    }
+
+    #[test]
+    fn failures_say_what_went_wrong() {
+        let plain = env::temp_dir().join("contrast_test_not_a_repository");
+        let _ = fs::remove_dir_all(&plain);
+        fs::create_dir_all(&plain).unwrap();
+        assert!(matches!(
+            list_diffs(plain.display().to_string(), DiffSettings::default(), &mut |_, _| true),
+            Err(DiffParsingError::NotARepository(path)) if path == plain
+        ));
+
+        let empty = env::temp_dir().join("contrast_test_no_commits");
+        let _ = fs::remove_dir_all(&empty);
+        Repository::init(&empty).unwrap();
+        let times = last_commit_times(empty.display().to_string(), Vec::new());
+        assert!(matches!(times, Err(DiffParsingError::NoCommits(_))));
+        assert!(times.unwrap_err().message().contains("no commits yet"));
+    }
+
+    #[test]
+    fn repositories_without_commits_can_be_opened() {
+        let path = env::temp_dir().join("contrast_test_unborn_head");
+        let _ = fs::remove_dir_all(&path);
+        let repo = Repository::init(&path).unwrap();
+        fs::write(path.join("a.txt"), "a\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+
+        let path = path.display().to_string();
+        assert!(head_commit_info(path.clone()).is_none());
+        assert!(commit_log(path.clone(), 10).unwrap().is_empty());
+        // Everything staged is new, as there is nothing to compare with.
+        let (diffs, _, _) =
+            list_staged_diffs(path.clone(), DiffSettings::default(), &mut |_, _| true).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].status, Delta::Added);
+        let (diffs, _, _) =
+            list_combined_diffs(path, DiffSettings::default(), &mut |_, _| true).unwrap();
+        assert_eq!(diffs.len(), 1);
+    }
+
+    #[test]
+    fn branches_are_compared_to_their_upstream() {
+        let (path, repo) = test_repo("branch_status", &[("a.txt", b"a\n")]);
+        let path_string = path.display().to_string();
+        let status = branch_status(path_string.clone()).unwrap();
+        assert_eq!(status.upstream, None);
+        assert!(status.label_text().ends_with("without an upstream"));
+
+        let initial = repo.head().unwrap().target().unwrap();
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        let upstream_ref = format!("refs/remotes/origin/{}", status.name);
+        repo.reference(&upstream_ref, initial, false, "test")
+            .unwrap();
+        let mut branch = repo
+            .find_branch(&status.name, git2::BranchType::Local)
+            .unwrap();
+        branch
+            .set_upstream(Some(&format!("origin/{}", status.name)))
+            .unwrap();
+
+        let second = commit_files::<&str>(&repo, &path, &[], "Second");
+        let status = branch_status(path_string.clone()).unwrap();
+        assert_eq!(
+            status.label_text(),
+            format!("On {0}, 1 ahead of origin/{0}", status.name)
+        );
+
+        repo.set_head_detached(second).unwrap();
+        let status = branch_status(path_string).unwrap();
+        assert_eq!(
+            status.label_text(),
+            format!("HEAD detached at {}", &second.to_string()[..7])
+        );
+    }
+
+    #[test]
+    fn commit_dates_are_in_their_time_zone() {
+        let (path, repo) = test_repo("head_info", &[("a.txt", b"a\n")]);
+        let info = head_commit_info(path.display().to_string()).unwrap();
+        assert_eq!(info.id, repo.head().unwrap().target().unwrap().to_string());
+
+        let date = |seconds, offset| {
+            let info = CommitInfo {
+                time: git2::Time::new(seconds, offset),
+                ..info.clone()
+            };
+            info.date_text()
+        };
+        assert_eq!(date(0, 0), "1970-01-01 00:00");
+        assert_eq!(date(1_692_032_700, 120), "2023-08-14 19:05");
+        // Leap day, an hour before midnight UTC but already the next day in UTC+2.
+        assert_eq!(date(951_865_200, 0), "2000-02-29 23:00");
+        assert_eq!(date(951_865_200, 120), "2000-03-01 01:00");
+    }
+
+    #[test]
+    fn unified_string_applies() {
+        let (path, repo) = test_repo(
+            "unified",
+            &[
+                ("a.txt", b"one\ntwo\nthree\n"),
+                ("b.txt", b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n"),
+            ],
+        );
+        fs::write(path.join("a.txt"), "one\n2\nthree\nfour").unwrap();
+        fs::write(path.join("b.txt"), "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n").unwrap();
+
+        let (diffs, _) = get_diffs(path.to_str().unwrap().to_owned()).unwrap();
+        let patch: String = diffs.iter().map(Diff::to_unified_string).collect();
+
+        let patch = git2::Diff::from_buffer(patch.as_bytes()).unwrap();
+        repo.apply(&patch, git2::ApplyLocation::Index, None)
+            .unwrap();
+
+        let index = repo.index().unwrap();
+        for file in ["a.txt", "b.txt"] {
+            let entry = index.get_path(Path::new(file), 0).unwrap();
+            let blob = repo.find_blob(entry.id).unwrap();
+            assert_eq!(blob.content(), fs::read(path.join(file)).unwrap());
+        }
+    }
+
+    #[test]
+    fn renamed_files_are_paired_up() {
+        let content = (1..=10)
+            .map(|i| format!("line {}\n", i))
+            .collect::<String>();
+        let (path, repo) = test_repo("rename", &[("old.txt", content.as_bytes())]);
+        fs::remove_file(path.join("old.txt")).unwrap();
+        fs::write(path.join("new.txt"), content.replace("line 5\n", "five\n")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("old.txt")).unwrap();
+        index.add_path(Path::new("new.txt")).unwrap();
+        index.write().unwrap();
+
+        let path = path.to_str().unwrap().to_string();
+        let (diffs, stats, _) =
+            list_staged_diffs(path.clone(), DiffSettings::default(), &mut |_, _| true).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].display_name(Path::new("")), "old.txt → new.txt");
+        assert_eq!(diffs[0].file_name(), "old.txt");
+        assert_eq!(diffs[0].new_file_name(), "new.txt");
+        assert_eq!(diffs[0].worktree_file_name(), "new.txt");
+        assert_eq!((stats.insertions, stats.deletions), (1, 1));
+
+        let loaded = load_diff(path, &diffs[0]).unwrap();
+        assert_eq!(loaded.similarity(), Some(90));
+        assert!(loaded.to_unified_string().contains("rename to new.txt\n"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn mode_changes_are_listed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (path, _repo) = test_repo("mode", &[("run.sh", b"echo hi\n")]);
+        fs::set_permissions(path.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = path.to_str().unwrap().to_string();
+        let (diffs, _, _) =
+            list_diffs(path.clone(), DiffSettings::default(), &mut |_, _| true).unwrap();
+        assert_eq!(diffs[0].mode_change(), Some((0o100644, 0o100755)));
+        let loaded = load_diff(path, &diffs[0]).unwrap();
+        assert!(loaded.lines.is_empty());
+        assert_eq!(
+            loaded.empty_reason(),
+            "Only the mode changed, 100644 → 100755"
+        );
+    }
+
+    #[test]
+    fn hunks_without_context_keep_their_headers() {
+        let content = (1..=6).map(|i| format!("{}\n", i)).collect::<String>();
+        let (path, repo) = test_repo("no_context", &[("a.txt", content.as_bytes())]);
+        fs::write(path.join("a.txt"), "1\ntwo\n3\nfour\n5\n6\n").unwrap();
+
+        let settings = DiffSettings {
+            context_lines: 0,
+            ..DiffSettings::default()
+        };
+        let (diffs, _, _) =
+            list_diffs(path.to_str().unwrap().to_string(), settings, &mut |_, _| {
+                true
+            })
+            .unwrap();
+        let diff = load_diff(path.to_str().unwrap().to_string(), &diffs[0]).unwrap();
+        // Two hunks with a single unchanged line between them, and no context in either.
+        assert_eq!(diff.header_indices, vec![0, 3]);
+        assert_eq!(diff.line_rows, vec![1, 2, 4, 5]);
+        assert!(diff
+            .lines
+            .iter()
+            .all(|line| line.kind() != LineKind::Context));
 
-                match Header::new(content) {
-                    Ok(header) => match header_groups.borrow_mut().last_mut() {
-                        Some(last) => {
-                            last.push(header);
-                            true
-                        }
-                        None => false,
-                    },
-                    Err(_) => false,
-                }
-            }),
-            None,
+        let patch = git2::Diff::from_buffer(diff.to_unified_string().as_bytes()).unwrap();
+        repo.apply(&patch, git2::ApplyLocation::Index, None)
+            .unwrap();
+    }
+
+    #[test]
+    fn headers_of_deleted_files_stay_in_place() {
+        let (path, _repo) = test_repo("deleted_header", &[("a.txt", b"one\ntwo\n")]);
+        fs::remove_file(path.join("a.txt")).unwrap();
+
+        let (diffs, _, _) = list_diffs(
+            path.to_str().unwrap().to_string(),
+            DiffSettings::default(),
+            &mut |_, _| true,
         )
-        .map_err(|_| DiffParsingError)?;
+        .unwrap();
+        let diff = load_diff(path.to_str().unwrap().to_string(), &diffs[0]).unwrap();
+        // No line has a new line number, and the hunk starts at line 0 of the new side.
+        assert!(diff.lines.iter().all(|line| line.new_lineno.is_none()));
+        assert_eq!(diff.header_indices, vec![0]);
+        assert_eq!(diff.line_rows, vec![1, 2]);
+        for column in [&diff.content, &diff.origins_content, &diff.lines_content] {
+            assert_eq!(column.lines().count(), 3);
+        }
+    }
 
-    let mut result = Vec::new();
-    diffs
-        .foreach(
-            &mut |_delta, _num| {
-                let Some(old_file) = _delta.old_file().path() else {
-                    return false;
-                };
+    #[test]
+    fn whitespace_changes_can_be_ignored() {
+        let (path, _repo) = test_repo(
+            "whitespace",
+            &[("a.txt", b"fn main() {\n    let x = 1;\n}\n")],
+        );
+        fs::write(path.join("a.txt"), "fn main() {\n  let  x = 1;  \n}\n").unwrap();
 
-                let Some(new_file) = _delta.new_file().path() else {
-                    return false;
-                };
-                let mut hg = header_groups.borrow_mut();
-                let Some(headers) = hg.first() else {
-                    return false;
-                };
+        let list = |whitespace| {
+            let settings = DiffSettings {
+                whitespace,
+                ..DiffSettings::default()
+            };
+            let (diffs, stats, _) =
+                list_diffs(path.to_str().unwrap().to_string(), settings, &mut |_, _| {
+                    true
+                })
+                .unwrap();
+            (diffs, stats.insertions, stats.deletions)
+        };
+        assert_eq!(list(Whitespace::Compare).1, 1);
+        let (diffs, insertions, deletions) = list(Whitespace::IgnoreAmount);
+        assert_eq!((insertions, deletions), (0, 0));
+        let diff = load_diff(path.to_str().unwrap().to_string(), &diffs[0]).unwrap();
+        assert_eq!(diff.empty_reason(), "Only whitespace changed");
 
-                let mut lg = line_groups.borrow_mut();
-                let Some(lines) = lg.first() else {
-                    return false;
-                };
+        // Only ignoring all whitespace also ignores it where there was none.
+        fs::write(path.join("a.txt"), "fn main(){\n    let x = 1;\n}\n").unwrap();
+        assert_eq!(list(Whitespace::IgnoreAmount).1, 1);
+        assert_eq!(list(Whitespace::IgnoreAll).1, 0);
+    }
 
-                let diff = Diff::new(
-                    old_file.to_path_buf(),
-                    new_file.to_path_buf(),
-                    headers.to_vec(),
-                    lines.to_vec(),
-                );
-                result.push(diff);
+    #[test]
+    fn binary_files_are_marked_in_patches() {
+        let (path, _repo) = test_repo("binary_patch", &[("image.bin", b"\0\x01\x02")]);
+        fs::write(path.join("image.bin"), b"\0\x03\x04\x05").unwrap();
 
-                hg.remove(0);
-                lg.remove(0);
+        let (diffs, _) = get_diffs(path.to_str().unwrap().to_owned()).unwrap();
+        assert_eq!(
+            diffs[0].to_unified_string(),
+            "diff --git a/image.bin b/image.bin\n\
+             Binary files a/image.bin and b/image.bin differ\n"
+        );
+        assert_eq!(
+            load_diff(path.to_str().unwrap().to_owned(), &diffs[0])
+                .unwrap()
+                .empty_reason(),
+            "Binary file — 3 B → 4 B (+1 bytes)"
+        );
+    }
+
+    #[test]
+    fn utf16_files_are_decoded() {
+        let utf16 = |text: &str| {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        };
+        let (path, _repo) = test_repo("utf16", &[("a.txt", &utf16("one\ntwo\n"))]);
+        fs::write(path.join("a.txt"), utf16("one\nzwei\n")).unwrap();
+
+        let (diffs, stats) = get_diffs(path.to_str().unwrap().to_owned()).unwrap();
+        let diff = &diffs[0];
+        assert_eq!(diff.encoding, Encoding::Utf16Le);
+        assert!(!diff.is_binary);
+        assert!(diff
+            .lines
+            .iter()
+            .any(|line| line.origin == '+' && line.content == "zwei"));
+        assert_eq!((stats.insertions, stats.deletions), (1, 1));
+    }
+
+    #[test]
+    fn missing_trailing_newline_adds_no_rows() {
+        let (path, _repo) = test_repo("trailing_newline", &[("a.txt", b"one\ntwo\n")]);
+        fs::write(path.join("a.txt"), "one\nzwei").unwrap();
+
+        let (diffs, _) = get_diffs(path.to_str().unwrap().to_owned()).unwrap();
+        let diff = &diffs[0];
+
+        // One header, "one", "-two" and "+zwei".
+        assert_eq!(diff.row_count(), 4);
+        assert_eq!(diff.content.lines().count(), diff.row_count());
+        assert_eq!(diff.origins_content.lines().count(), diff.row_count());
+        assert_eq!(diff.lines_content.lines().count(), diff.row_count());
+        assert_eq!(diff.line_rows.len(), diff.lines.len());
+    }
+
+    #[test]
+    fn listed_diffs_load_on_demand() {
+        let (path, _repo) = test_repo("lazy", &[("a.txt", b"one\ntwo\nthree\n")]);
+        fs::write(path.join("a.txt"), "one\n2\nthree\nfour\n").unwrap();
+        let path = path.to_str().unwrap().to_owned();
+
+        let (listed, stats, _) =
+            list_diffs(path.clone(), DiffSettings::default(), &mut |_, _| true).unwrap();
+        let diff = &listed[0];
+        assert!(!diff.is_loaded());
+        assert!(diff.lines.is_empty());
+        assert_eq!((diff.insertions(), diff.deletions()), (2, 1));
+        assert_eq!(
+            (stats.files_changed, stats.insertions, stats.deletions),
+            (1, 2, 1)
+        );
+
+        let loaded = load_diff(path.clone(), diff).unwrap();
+        let (eager, _) = get_diffs(path).unwrap();
+        assert!(loaded.is_loaded());
+        assert_eq!(loaded.content, eager[0].content);
+        assert_eq!((loaded.insertions(), loaded.deletions()), (2, 1));
+    }
+
+    #[test]
+    fn line_ending_only_changes_are_detected() {
+        let (path, _repo) = test_repo(
+            "line_endings",
+            &[("a.txt", b"one\r\ntwo\r\n"), ("b.txt", b"one\r\ntwo\r\n")],
+        );
+        fs::write(path.join("a.txt"), "one\ntwo\n").unwrap();
+        fs::write(path.join("b.txt"), "one\nzwei\n").unwrap();
+
+        let (diffs, _) = get_diffs(path.to_str().unwrap().to_owned()).unwrap();
+        assert_eq!(
+            diffs[0].line_ending_change,
+            Some((LineEnding::Crlf, LineEnding::Lf))
+        );
+        assert_eq!(diffs[1].line_ending_change, None);
+    }
+
+    #[test]
+    fn byte_order_marks_are_only_stripped_from_first_lines() {
+        let (path, _repo) = test_repo(
+            "byte_order_marks",
+            &[
+                ("a.txt", "one\ntwo\n".as_bytes()),
+                ("b.txt", "\u{feff}one\ntwo\n".as_bytes()),
+            ],
+        );
+        fs::write(path.join("a.txt"), "\u{feff}one\ntwo\n").unwrap();
+        fs::write(path.join("b.txt"), "\u{feff}one\n\u{feff}zwei\n").unwrap();
+
+        let (diffs, _) = get_diffs(path.to_str().unwrap().to_owned()).unwrap();
+        assert_eq!(
+            diffs[0].encoding_change,
+            Some((Encoding::Utf8, Encoding::Utf8Bom))
+        );
+        assert_eq!(diffs[0].lines[1].content, "one");
+
+        // Only the mark at the start of the file is one.
+        assert_eq!(diffs[1].encoding_change, None);
+        assert_eq!(diffs[1].encoding, Encoding::Utf8Bom);
+        let added = diffs[1].lines.iter().find(|line| line.origin == '+');
+        assert_eq!(added.unwrap().content, "\u{feff}zwei");
+    }
+
+    #[test]
+    fn linked_worktrees_diff_their_own_checkout() {
+        let (path, repo) = test_repo("worktree_main", &[("a.txt", b"one\n")]);
+        let worktree_path = env::temp_dir().join("contrast_test_worktree_linked");
+        let _ = fs::remove_dir_all(&worktree_path);
+        repo.worktree("linked", &worktree_path, None).unwrap();
+        fs::write(worktree_path.join("a.txt"), "two\n").unwrap();
+
+        let root = repository_root(&worktree_path.join(".")).unwrap();
+        assert_eq!(
+            fs::canonicalize(&root).unwrap(),
+            fs::canonicalize(&worktree_path).unwrap()
+        );
+
+        let worktree = root.to_str().unwrap().to_owned();
+        let (diffs, _, _) =
+            list_diffs(worktree.clone(), DiffSettings::default(), &mut |_, _| true).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(worktree_name(worktree), Some("linked".to_string()));
+
+        let main = path.to_str().unwrap().to_owned();
+        let (diffs, _, _) =
+            list_diffs(main.clone(), DiffSettings::default(), &mut |_, _| true).unwrap();
+        assert!(diffs.is_empty());
+        assert_eq!(worktree_name(main), Some("main worktree".to_string()));
+    }
+
+    #[test]
+    fn remote_urls_map_to_permalinks() {
+        for remote in [
+            "git@github.com:owner/repo.git",
+            "https://github.com/owner/repo.git",
+            "ssh://git@github.com:22/owner/repo",
+        ] {
+            assert_eq!(
+                web_url(remote),
+                Some("https://github.com/owner/repo".to_string())
+            );
+        }
+
+        let github = Permalink {
+            web_url: "https://github.com/owner/repo".to_string(),
+            commit: "abc".to_string(),
+            gitlab: false,
+        };
+        assert_eq!(
+            github.url("src/git.rs", 3..=5),
+            "https://github.com/owner/repo/blob/abc/src/git.rs#L3-L5"
+        );
+
+        let gitlab = Permalink {
+            web_url: "https://gitlab.com/owner/repo".to_string(),
+            gitlab: true,
+            ..github
+        };
+        assert_eq!(
+            gitlab.url("src/git.rs", 3..=5),
+            "https://gitlab.com/owner/repo/-/blob/abc/src/git.rs#L3-5"
+        );
+    }
+
+    #[test]
+    fn commits_are_diffed_against_their_parent() {
+        let (path, repo) = test_repo("commit", &[("a.txt", b"one\ntwo\n")]);
+        commit_files(&repo, &path, &[("a.txt", "one\n2\n")], "Second");
+
+        let path = path.to_str().unwrap().to_string();
+        let (info, diffs, stats, _) = list_commit_diffs(
+            path.clone(),
+            "HEAD",
+            DiffSettings::default(),
+            &mut |_, _| true,
+        )
+        .unwrap();
+        assert_eq!(info.summary, "Second");
+        assert_eq!((stats.insertions, stats.deletions), (1, 1));
+        let loaded = load_diff(path.clone(), &diffs[0]).unwrap();
+        assert!(loaded.lines.iter().any(|line| line.content == "2"));
+
+        assert!(matches!(
+            list_commit_diffs(path, "nonexistent", DiffSettings::default(), &mut |_, _| {
                 true
-            },
-            None,
-            None,
-            None,
+            }),
+            Err(DiffParsingError::InvalidRevspec(_))
+        ));
+    }
+
+    #[test]
+    fn revisions_are_compared_with_each_other() {
+        let (path, repo) = test_repo("range", &[("a.txt", b"one\ntwo\n")]);
+        for (content, message) in [("one\n2\n", "Second"), ("1\n2\n", "Third")] {
+            commit_files(&repo, &path, &[("a.txt", content)], message);
+        }
+
+        let path = path.to_str().unwrap().to_string();
+        let log = commit_log(path.clone(), 2).unwrap();
+        let summaries = log.iter().map(|commit| commit.summary.as_str());
+        assert_eq!(summaries.collect::<Vec<_>>(), ["Third", "Second"]);
+
+        let (range, diffs, stats, _) = list_diffs_between(
+            path.clone(),
+            "HEAD~2",
+            "HEAD",
+            false,
+            &[],
+            DiffSettings::default(),
+            &mut |_, _| true,
         )
-        .map_err(|_| DiffParsingError)?;
+        .unwrap();
+        assert_eq!(range.from_commit.summary, "Initial");
+        assert_eq!(range.to_commit.summary, "Third");
+        assert_eq!((stats.insertions, stats.deletions), (2, 2));
+        let loaded = load_diff(path.clone(), &diffs[0]).unwrap();
+        assert!(loaded.lines.iter().any(|line| line.content == "1"));
 
-    Ok((
-        result,
-        Stats::new(diffs.stats().map_err(|_| DiffParsingError)?),
-    ))
-}
+        // HEAD~2 is all HEAD and HEAD~2 have in common, so HEAD~2 changed nothing since.
+        let (range, diffs, _, _) = list_diffs_between(
+            path.clone(),
+            "HEAD",
+            "HEAD~2",
+            true,
+            &[],
+            DiffSettings::default(),
+            &mut |_, _| true,
+        )
+        .unwrap();
+        assert_eq!(range.from_commit.summary, "Initial");
+        assert_eq!(range.notation(), "HEAD...HEAD~2");
+        assert!(diffs.is_empty());
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert!(matches!(
+            list_diffs_between(
+                path,
+                "HEAD",
+                "nonexistent",
+                false,
+                &[],
+                DiffSettings::default(),
+                &mut |_, _| true
+            ),
+            Err(DiffParsingError::InvalidRevspec(revspec)) if revspec == "nonexistent"
+        ));
+    }
 
     #[test]
-    fn it_works() {
-        let (diffs, _) = get_diffs(".".to_owned()).unwrap();
-        for diff in diffs {
-            println!("{:#?}", diff);
+    fn file_history_is_compared_without_other_files() {
+        let (path, repo) = test_repo("file log", &[("a.txt", b"one\n"), ("b.txt", b"two\n")]);
+        for (file, content, message) in [
+            ("a.txt", "1\n", "Change a"),
+            ("b.txt", "2\n", "Change b"),
+            ("a.txt", "1\n2\n", "Change a again"),
+        ] {
+            commit_files(&repo, &path, &[(file, content)], message);
+        }
+
+        let path = path.to_str().unwrap().to_string();
+        let log = file_log(path.clone(), "a.txt", 10, false).unwrap().commits;
+        let summaries = log.iter().map(|commit| commit.summary.as_str());
+        assert_eq!(
+            summaries.collect::<Vec<_>>(),
+            ["Change a again", "Change a", "Initial"]
+        );
+        let newest = file_log(path.clone(), "a.txt", 1, false).unwrap();
+        assert_eq!(newest.commits.len(), 1);
+
+        let (range, diffs, stats, _) = list_diffs_between(
+            path,
+            &log[2].id,
+            &log[0].id,
+            false,
+            &["a.txt".to_string()],
+            DiffSettings::default(),
+            &mut |_, _| true,
+        )
+        .unwrap();
+        assert_eq!(range.paths, ["a.txt"]);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].file_name(), "a.txt");
+        assert_eq!((stats.insertions, stats.deletions), (2, 1));
+    }
+
+    #[test]
+    fn renames_are_followed_in_file_history() {
+        let content = "one\ntwo\nthree\nfour\nfive\n";
+        let (path, repo) = test_repo("follow", &[("old.txt", content.as_bytes())]);
+        let changed = "one\ntwo\nthree\nfour\n5\n";
+        commit_files(&repo, &path, &[("old.txt", changed)], "Change");
+        fs::remove_file(path.join("old.txt")).unwrap();
+        let mut index = repo.index().unwrap();
+        index.remove_path(Path::new("old.txt")).unwrap();
+        index.write().unwrap();
+        commit_files(&repo, &path, &[("new.txt", changed)], "Rename");
+        let again = "1\ntwo\nthree\nfour\n5\n";
+        commit_files(&repo, &path, &[("new.txt", again)], "Change again");
+
+        let path = path.to_str().unwrap().to_string();
+        let log = file_log(path.clone(), "new.txt", 10, false).unwrap();
+        assert_eq!(log.commits.len(), 2);
+        assert_eq!(log.paths, ["new.txt"]);
+
+        let log = file_log(path.clone(), "new.txt", 10, true).unwrap();
+        let summaries = log.commits.iter().map(|commit| commit.summary.as_str());
+        assert_eq!(
+            summaries.collect::<Vec<_>>(),
+            ["Change again", "Rename", "Change", "Initial"]
+        );
+        assert_eq!(log.paths, ["new.txt", "old.txt"]);
+
+        // Both paths are compared, so the file shows up as renamed rather than added.
+        let (_, diffs, _, _) = list_diffs_between(
+            path,
+            &log.commits[3].id,
+            &log.commits[0].id,
+            false,
+            &log.paths,
+            DiffSettings::default(),
+            &mut |_, _| true,
+        )
+        .unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].status, Delta::Renamed);
+    }
+
+    #[test]
+    fn stashes_are_compared_to_the_worktree() {
+        let (path, mut repo) = test_repo("stash", &[("a.txt", b"one\ntwo\n")]);
+        fs::write(path.join("a.txt"), "one\nstashed\n").unwrap();
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        repo.stash_save(&signature, "Try something", None).unwrap();
+        fs::write(path.join("a.txt"), "one\nedited\n").unwrap();
+
+        let path = path.to_str().unwrap().to_string();
+        let stashes = list_stashes(path.clone()).unwrap();
+        assert_eq!(stashes.len(), 1);
+        assert!(stashes[0].message.contains("Try something"));
+
+        let (_, diffs, stats, _) =
+            stash_vs_worktree(path.clone(), 0, DiffSettings::default(), &mut |_, _| true).unwrap();
+        assert_eq!((stats.insertions, stats.deletions), (1, 1));
+        let loaded = load_diff(path.clone(), &diffs[0]).unwrap();
+        let changed = loaded
+            .lines
+            .iter()
+            .filter(|line| line.kind() != LineKind::Context)
+            .map(|line| (line.origin, line.content.as_str()))
+            .collect::<Vec<(char, &str)>>();
+        assert_eq!(changed, vec![('-', "edited"), ('+', "stashed")]);
+        assert_eq!(
+            fs::read_to_string(Path::new(&path).join("a.txt")).unwrap(),
+            "one\nedited\n"
+        );
+
+        assert!(stash_vs_worktree(path, 1, DiffSettings::default(), &mut |_, _| true).is_err());
+    }
+
+    #[test]
+    fn lines_are_blamed_on_their_commit() {
+        let (path, repo) = test_repo("blame", &[("a.txt", b"one\ntwo\nthree\n")]);
+        fs::write(path.join("a.txt"), "zero\none\ntwo\nthree\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(path.join("a.txt"), "zero\none\n2\nthree\n").unwrap();
+
+        let path = path.to_str().unwrap().to_string();
+        let (diffs, _, _) =
+            list_diffs(path.clone(), DiffSettings::default(), &mut |_, _| true).unwrap();
+        let blamed = |line_no| blame_line(path.clone(), &diffs[0], line_no).unwrap();
+        assert_eq!(
+            blamed(2).map(|commit| commit.summary),
+            Some("Initial".to_string())
+        );
+        // Only staged so far.
+        assert!(blamed(1).is_none());
+    }
+
+    #[test]
+    fn file_sizes_count_lines_and_bytes() {
+        let sizes = FileSizes::measure(b"one\ntwo\n", b"one\n2\nthree");
+        assert_eq!((sizes.old_lines, sizes.new_lines), (2, 3));
+        assert_eq!(
+            sizes.label_text(false),
+            "2 → 3 lines (+1), 8 B → 11 B".to_string()
+        );
+        assert_eq!(sizes.label_text(true), "8 B → 11 B".to_string());
+        assert_eq!(count_lines(b"\xFF\xFEa\0\n\0b\0"), 2);
+        assert_eq!(format_bytes(4198), "4.1 KB");
+    }
+
+    #[test]
+    fn directories_are_compared_file_by_file() {
+        let root = env::temp_dir().join("contrast_test_directories");
+        let _ = fs::remove_dir_all(&root);
+        let (old_dir, new_dir) = (root.join("old"), root.join("new"));
+        for (dir, files) in [
+            (
+                &old_dir,
+                [
+                    ("same.txt", "same\n"),
+                    ("src/changed.txt", "one\ntwo\n"),
+                    ("removed.txt", "gone\n"),
+                ],
+            ),
+            (
+                &new_dir,
+                [
+                    ("same.txt", "same\n"),
+                    ("src/changed.txt", "one\n2\n"),
+                    ("added.txt", "new\n"),
+                ],
+            ),
+        ] {
+            for (file, content) in files {
+                let path = dir.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
         }
+
+        let (diffs, stats, skipped) =
+            diff_directories(&old_dir, &new_dir, DiffSettings::default(), &mut |_, _| {
+                true
+            })
+            .unwrap();
+        assert!(skipped.is_empty());
+        let statuses = diffs
+            .iter()
+            .map(|diff| (diff.file_name(), diff.status))
+            .collect::<Vec<(String, Delta)>>();
+        assert_eq!(
+            statuses,
+            vec![
+                ("added.txt".to_string(), Delta::Added),
+                ("removed.txt".to_string(), Delta::Deleted),
+                (
+                    Path::new("src").join("changed.txt").display().to_string(),
+                    Delta::Modified
+                ),
+            ]
+        );
+        assert_eq!((stats.insertions, stats.deletions), (2, 2));
+        assert!(diffs.iter().all(Diff::is_loaded));
+    }
+
+    #[test]
+    fn every_line_origin_is_classified() {
+        let kinds = [' ', '+', '-', '=', '>', '<', 'F', 'H', 'B']
+            .into_iter()
+            .map(LineKind::from_origin)
+            .collect::<Vec<LineKind>>();
+        assert_eq!(
+            kinds,
+            vec![
+                LineKind::Context,
+                LineKind::Insertion,
+                LineKind::Deletion,
+                LineKind::EofMarker,
+                LineKind::EofMarker,
+                LineKind::EofMarker,
+                LineKind::Header,
+                LineKind::Header,
+                LineKind::Header,
+            ]
+        );
     }
 
     #[test]
     fn parse_header() {
         let header =
             Header::new("@@ -209,6 +222,33 @@ impl fmt::Display for Diff {".to_string()).unwrap();
-        assert_eq!(header.line, 222)
+        assert_eq!(header.line, 222);
+        assert_eq!(header.context(), "impl fmt::Display for Diff {");
+        assert_eq!(split_hunk_header("@@ -1 +1 @@"), ("@@ -1 +1 @@", ""));
+    }
+
+    #[test]
+    fn line_numbers_are_padded_to_the_widest() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -9,2 +9,2 @@\n nine\n-ten\n+10\n\
+             @@ -99,2 +99,2 @@\n ninety-nine\n-hundred\n+100\n",
+        )
+        .unwrap();
+
+        let numbers = diffs[0].lines_content.lines().collect::<Vec<&str>>();
+        // Hunk headers have no line number.
+        assert_eq!(
+            numbers,
+            vec![" ", "  9", " 10", " 10", " ", " 99", "100", "100"]
+        );
+        assert_eq!(line_number_width(&diffs[0].lines[..2]), 2);
+    }
+
+    #[test]
+    fn last_lines_without_a_newline_are_described() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n\\ No newline at end of file\n",
+        )
+        .unwrap();
+
+        assert_eq!(diffs[0].row_count(), 4);
+        assert_eq!(
+            diffs[0].row_description(3).as_deref(),
+            Some("added line 2: 2")
+        );
+        assert_eq!(diffs[0].row_description(4), None);
+    }
+
+    #[test]
+    fn split_rows_pair_removed_and_added_lines() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,4 +1,4 @@\n one\n-two\n-three\n+2\n four\n+five\n",
+        )
+        .unwrap();
+
+        let rows = diffs[0]
+            .split_rows()
+            .into_iter()
+            .map(|row| (row.header, row.old, row.new))
+            .collect::<Vec<(Option<usize>, Option<usize>, Option<usize>)>>();
+        assert_eq!(
+            rows,
+            vec![
+                (Some(0), None, None),
+                (None, Some(0), Some(0)),
+                (None, Some(1), Some(3)),
+                (None, Some(2), None),
+                (None, Some(4), Some(4)),
+                (None, None, Some(5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_unchanged_runs_are_folded() {
+        let context = (2..=15).map(|n| format!(" {}\n", n)).collect::<String>();
+        let (diffs, _) = crate::patch::parse(&format!(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,17 +1,17 @@ fn main() {{\n 0\n-1\n+one\n{}-16\n+sixteen\n",
+            context
+        ))
+        .unwrap();
+        let diff = &diffs[0];
+
+        let folded = diff.folded(Some(10), &HashSet::new(), &HashSet::new());
+        assert_eq!(
+            folded.folds,
+            vec![Fold {
+                row: 7,
+                first_line: 6,
+                hidden: 8,
+            }]
+        );
+        assert_eq!(folded.diff.row_count(), diff.row_count() - 7);
+        assert_eq!(
+            folded.diff.headers[1].content,
+            "@@ … 8 unchanged lines … @@ fn main() {"
+        );
+        // The first hidden row lands on the band, the rows after it move up.
+        assert_eq!(folded.row(diff.line_rows[6]), 7);
+        assert_eq!(folded.row(diff.line_rows[14]), 8);
+        assert_eq!(folded.unfolded_row(8), diff.line_rows[14]);
+        assert_eq!(
+            folded.diff.row_description(8).as_deref(),
+            Some("line 14: 13")
+        );
+        assert_eq!(folded.diff.hunk_context_at(8), Some("fn main() {"));
+
+        assert!(diff
+            .folded(Some(20), &HashSet::new(), &HashSet::new())
+            .folds
+            .is_empty());
+        assert!(diff
+            .folded(Some(10), &HashSet::from([6]), &HashSet::new())
+            .folds
+            .is_empty());
+    }
+
+    #[test]
+    fn hunks_are_stepped_through() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n-one\n+1\n two\n\
+             @@ -9,2 +9,2 @@\n nine\n-ten\n+10\n",
+        )
+        .unwrap();
+        let diff = &diffs[0];
+        assert_eq!(diff.adjacent_hunk_row(0, true), Some(4));
+        assert_eq!(diff.adjacent_hunk_row(4, true), None);
+        assert_eq!(diff.adjacent_hunk_row(6, false), Some(4));
+        assert_eq!(diff.adjacent_hunk_row(4, false), Some(0));
+    }
+
+    #[test]
+    fn changes_are_stepped_through() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,4 +1,4 @@\n-one\n+1\n two\n three\n-four\n+4\n",
+        )
+        .unwrap();
+        let diff = &diffs[0];
+        // The removed and added line next to each other are one change.
+        assert_eq!(diff.change_rows(), [1, 5]);
+        assert_eq!(diff.adjacent_change_row(0, true), Some(1));
+        assert_eq!(diff.adjacent_change_row(1, true), Some(5));
+        assert_eq!(diff.adjacent_change_row(5, true), Some(1));
+        assert_eq!(diff.adjacent_change_row(3, false), Some(1));
+        assert_eq!(diff.adjacent_change_row(1, false), Some(5));
+    }
+
+    #[test]
+    fn removed_lines_open_at_the_line_after_them() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,3 +1,2 @@\n one\n-two\n three\n-four\n",
+        )
+        .unwrap();
+        let diff = &diffs[0];
+        assert_eq!(diff.new_line_near(1), Some(1));
+        assert_eq!(diff.new_line_near(2), Some(2));
+        assert_eq!(diff.new_line_near(4), Some(2));
+    }
+
+    #[test]
+    fn lines_are_copied_without_headers() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n-one\n+1\n two\n\
+             @@ -9,1 +9,1 @@\n-ten\n+10\n",
+        )
+        .unwrap();
+        let diff = &diffs[0];
+        assert_eq!(diff.copied_text(2..=2, false), "1");
+        assert_eq!(diff.copied_text(1..=6, false), "one\n1\ntwo\nten\n10");
+        assert_eq!(diff.copied_text(1..=6, true), "-one\n+1\n two\n-ten\n+10");
+    }
+
+    #[test]
+    fn collapsed_hunks_keep_their_header() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n-one\n+1\n two\n\
+             @@ -9,2 +9,2 @@\n nine\n-ten\n+10\n",
+        )
+        .unwrap();
+        let diff = &diffs[0];
+        // Hunks starting with a change get a header row too.
+        assert_eq!(diff.header_indices, vec![0, 4]);
+
+        let folded = diff.folded(None, &HashSet::new(), &HashSet::from([0]));
+        assert_eq!(
+            folded.hunks,
+            vec![
+                HunkHeader {
+                    row: 0,
+                    hunk: 0,
+                    line: 1,
+                    collapsed: true,
+                },
+                HunkHeader {
+                    row: 1,
+                    hunk: 1,
+                    line: 9,
+                    collapsed: false,
+                },
+            ]
+        );
+        assert_eq!(
+            folded.diff.headers[0].content,
+            "@@ -1,2 +1,2 @@ (3 lines hidden)"
+        );
+        assert_eq!(folded.diff.row_count(), diff.row_count() - 3);
+        // Hidden rows land on the header of their hunk.
+        assert_eq!(folded.row(diff.line_rows[2]), 0);
+        assert_eq!(folded.row(diff.line_rows[4]), 3);
+    }
+
+    #[test]
+    fn stat_summary_looks_like_git() {
+        let (diffs, _) = crate::patch::parse(
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n+3\n-4\n\
+             --- a/README.md\n+++ b/README.md\n@@ -1 +1,2 @@\n readme\n+more\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            stat_summary(&diffs),
+            " src/main.rs | 4 ++--\n README.md   | 1 +\n 2 files changed, 3 insertions(+), 2 deletions(-)\n"
+        );
+        assert_eq!(
+            stat_summary(&diffs[1..]),
+            " README.md | 1 +\n 1 file changed, 1 insertion(+)\n"
+        );
     }
 }
@@ -1,17 +1,38 @@
 use std::{
-    env,
-    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
     thread,
+    time::{Duration, Instant},
 };
 
-use data::{AppData, ControlData, Message};
+use data::{
+    AppData, BlameState, ControlData, DiffType, FileHistory, FileHistoryState, FileTimes,
+    LineBlame, Load, Location, Message, ViewSnapshot, LOG_LENGTH,
+};
+use session::{RecentProjects, Session};
+use settings::Settings;
 
 use eframe::egui;
-use egui::Context;
+use egui::{Context, Key, PointerButton};
 
+mod annotations;
 mod data;
+mod external;
+mod file_tree;
 mod git;
+mod moves;
+mod patch;
+mod search;
+mod session;
+mod settings;
 mod ui;
+mod word_diff;
+mod wrap;
 
 fn main() -> Result<(), eframe::Error> {
     if env::var("PROFILING").is_ok() {
@@ -20,73 +41,1342 @@ fn main() -> Result<(), eframe::Error> {
 
     env_logger::init();
 
+    // `contrast path/to/repo` opens the repository right away.
+    let path = env::args_os().nth(1).map(PathBuf::from);
+
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(320.0, 240.0)),
         ..Default::default()
     };
 
-    eframe::run_native("Contrast", options, Box::new(|_cc| Box::new(MyApp::new())))
+    eframe::run_native(
+        "Contrast",
+        options,
+        Box::new(|cc| Box::new(MyApp::new(cc, path))),
+    )
 }
 
+/// Listings shorter than this don't show any progress.
+const PROGRESS_DELAY: Duration = Duration::from_millis(300);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
 struct MyApp {
+    ctx: Context,
     app_data: Option<AppData>,
     control_data: ControlData,
     sender: Sender<Message>,
     receiver: Receiver<Message>,
+    /// Last title given to the window, so it is only set when it changes.
+    window_title: String,
+    /// The last session, while it's being opened again.
+    restoring: Option<Session>,
 }
 
 impl MyApp {
-    fn new() -> MyApp {
+    /// Opens `path` if given, and otherwise what was open when the app was last closed.
+    fn new(cc: &eframe::CreationContext, path: Option<PathBuf>) -> MyApp {
         let (sender, receiver) = mpsc::channel();
 
-        MyApp {
+        let settings: Settings = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, settings::STORAGE_KEY))
+            .unwrap_or_default();
+
+        let session: Session = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, session::STORAGE_KEY))
+            .unwrap_or_default();
+
+        let recent_projects: RecentProjects = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, session::RECENT_STORAGE_KEY))
+            .unwrap_or_default();
+
+        let mut app = MyApp {
+            ctx: cc.egui_ctx.clone(),
             app_data: None,
-            control_data: ControlData::default(),
+            control_data: ControlData {
+                settings,
+                recent_projects,
+                ..Default::default()
+            },
             sender,
             receiver,
+            window_title: "Contrast".to_string(),
+            restoring: None,
+        };
+        match path {
+            Some(path) => app.handle_message(Message::LoadDiff(path)),
+            None => app.restore(session),
+        }
+        app
+    }
+
+    /// Opens what was shown when the app was last closed. Sessions whose repository or
+    /// directories are gone are dropped, saying so.
+    fn restore(&mut self, session: Session) {
+        let Some(message) = session.open_message() else {
+            return;
+        };
+        let missing = session.missing_paths();
+        if !missing.is_empty() {
+            let missing = missing
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            return self.handle_message(Message::ShowError(format!(
+                "The last session wasn't reopened, as {} no longer exists.",
+                missing
+            )));
+        }
+
+        self.restoring = Some(session);
+        self.handle_message(message);
+    }
+
+    /// Shows the restored session's commit or stash once its repository is open, and then
+    /// selects the file that was selected.
+    fn continue_restoring(&mut self) {
+        let (Some(session), Some(app_data)) = (self.restoring.take(), &self.app_data) else {
+            return;
+        };
+        if let Some(message) = session.follow_up_message() {
+            self.restoring = Some(Session {
+                view: None,
+                ..session
+            });
+            return self.handle_message(message);
+        }
+
+        if app_data.shows_working_tree() {
+            self.control_data.diff_type = session.diff_type.clone();
+        }
+        let selected = session.selected_file.and_then(|file_name| {
+            app_data
+                .diff_data(&self.control_data.diff_type)
+                .diffs
+                .iter()
+                .position(|diff| diff.file_name() == file_name)
+        });
+        if let Some(i) = selected {
+            self.control_data.selected_diff_index = i;
+        }
+        self.start_search();
+    }
+
+    /// E.g. "Contrast — myproject (main)" for the open repository.
+    fn window_title(&self) -> String {
+        let Some(app_data) = self
+            .app_data
+            .as_ref()
+            .filter(|_| self.control_data.settings.repository_in_title)
+        else {
+            return "Contrast".to_string();
+        };
+
+        let name = Path::new(&app_data.project_path)
+            .file_name()
+            .map_or(app_data.project_path.clone(), |name| {
+                name.to_string_lossy().to_string()
+            });
+        let source = match (&app_data.commit, &app_data.stash, &app_data.revisions) {
+            (Some(commit), _, _) => Some(commit.id[..7].to_string()),
+            (None, Some(stash), _) => Some(format!("stash@{{{}}}", stash.index)),
+            (None, None, Some(range)) => Some(range.notation()),
+            (None, None, None) => app_data.branch.clone(),
+        };
+        match source {
+            Some(source) => format!("Contrast — {} ({})", name, source),
+            None => format!("Contrast — {}", name),
         }
     }
 
     fn handle_messages(&mut self) {
-        match self.receiver.try_recv() {
-            Ok(msg) => match msg {
-                Message::LoadDiff(path) => {
-                    let s = self.sender.clone();
-                    thread::spawn(move || match AppData::from_pathbuf(path) {
-                        Ok(app_data) => s.send(Message::UpdateAppData(app_data)),
-                        Err(_) => s.send(Message::ShowError("Error loading diff!".to_string())),
+        loop {
+            match self.receiver.try_recv() {
+                Ok(msg) => self.handle_message(msg),
+                Err(err) => match err {
+                    TryRecvError::Disconnected => {
+                        self.control_data.error_information = "Thread disconnected!".to_string();
+                        self.control_data.show_err_dialog = true;
+                        return;
+                    }
+                    TryRecvError::Empty => return,
+                },
+            }
+        }
+    }
+
+    fn handle_message(&mut self, msg: Message) {
+        match msg {
+            Message::LoadDiff(path) => {
+                let s = self.sender.clone();
+                let (id, cancel) = self.start_load(Load::OPENING);
+                self.control_data.recent_projects.opening = Some(path.display().to_string());
+                let mut progress = self.progress_reporter(id, cancel.clone());
+                let diff_settings = self.control_data.settings.diff_settings();
+                thread::spawn(move || {
+                    match AppData::from_pathbuf(path, diff_settings, &mut progress) {
+                        Ok(app_data) => s.send(Message::UpdateAppData(id, Box::new(app_data))),
+                        Err(_) if cancel.load(Ordering::Relaxed) => Ok(()),
+                        Err(err) => s.send(Message::LoadFailed(id, err.message())),
+                    }
+                });
+            }
+            Message::CompareDirectories(old_dir, new_dir) => {
+                let s = self.sender.clone();
+                let (id, cancel) = self.start_load("Comparing folders…");
+                let mut progress = self.progress_reporter(id, cancel.clone());
+                let diff_settings = self.control_data.settings.diff_settings();
+                thread::spawn(move || {
+                    match AppData::from_directories(old_dir, new_dir, diff_settings, &mut progress)
+                    {
+                        Ok(app_data) => s.send(Message::UpdateAppData(id, Box::new(app_data))),
+                        Err(_) if cancel.load(Ordering::Relaxed) => Ok(()),
+                        Err(err) => s.send(Message::LoadFailed(id, err.message())),
+                    }
+                });
+            }
+            Message::ViewCommit(revspec) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let project_path = app_data.project_path.clone();
+                let s = self.sender.clone();
+                let (id, cancel) = self.start_load("Loading commit…");
+                let mut progress = self.progress_reporter(id, cancel.clone());
+                let diff_settings = self.control_data.settings.diff_settings();
+                thread::spawn(move || {
+                    match AppData::from_commit(
+                        project_path,
+                        revspec.trim(),
+                        diff_settings,
+                        &mut progress,
+                    ) {
+                        Ok(app_data) => s.send(Message::UpdateAppData(id, Box::new(app_data))),
+                        Err(_) if cancel.load(Ordering::Relaxed) => Ok(()),
+                        Err(err) => s.send(Message::LoadFailed(id, err.message())),
+                    }
+                });
+            }
+            Message::CompareRevisions(from, to, merge_base, paths) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let project_path = app_data.project_path.clone();
+                let s = self.sender.clone();
+                let (id, cancel) = self.start_load("Comparing revisions…");
+                let mut progress = self.progress_reporter(id, cancel.clone());
+                let diff_settings = self.control_data.settings.diff_settings();
+                thread::spawn(move || {
+                    match AppData::from_revisions(
+                        project_path,
+                        (from.trim(), to.trim()),
+                        merge_base,
+                        &paths,
+                        diff_settings,
+                        &mut progress,
+                    ) {
+                        Ok(app_data) => s.send(Message::UpdateAppData(id, Box::new(app_data))),
+                        Err(_) if cancel.load(Ordering::Relaxed) => Ok(()),
+                        Err(err) => s.send(Message::LoadFailed(id, err.message())),
+                    }
+                });
+            }
+            Message::RemoveRecentProject(path) => self.control_data.recent_projects.remove(&path),
+            Message::ClearRecentProjects => {
+                self.control_data.recent_projects = RecentProjects::default()
+            }
+            Message::CompareStash(index) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let project_path = app_data.project_path.clone();
+                let s = self.sender.clone();
+                let (id, cancel) = self.start_load("Loading stash…");
+                let mut progress = self.progress_reporter(id, cancel.clone());
+                let diff_settings = self.control_data.settings.diff_settings();
+                thread::spawn(move || {
+                    match AppData::from_stash(project_path, index, diff_settings, &mut progress) {
+                        Ok(app_data) => s.send(Message::UpdateAppData(id, Box::new(app_data))),
+                        Err(_) if cancel.load(Ordering::Relaxed) => Ok(()),
+                        Err(err) => s.send(Message::LoadFailed(id, err.message())),
+                    }
+                });
+            }
+            Message::Refresh => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                if app_data.pasted {
+                    return;
+                }
+                let project_path = PathBuf::from(&app_data.project_path);
+                let message = match (
+                    &app_data.compared_directory,
+                    &app_data.commit,
+                    &app_data.stash,
+                    &app_data.revisions,
+                ) {
+                    (Some(old_dir), _, _, _) => {
+                        Message::CompareDirectories(PathBuf::from(old_dir), project_path)
+                    }
+                    (None, Some(commit), _, _) => Message::ViewCommit(commit.id.clone()),
+                    (None, None, Some(stash), _) => Message::CompareStash(stash.index),
+                    (None, None, None, Some(range)) => Message::CompareRevisions(
+                        range.from.clone(),
+                        range.to.clone(),
+                        range.merge_base,
+                        range.paths.clone(),
+                    ),
+                    (None, None, None, None) => Message::LoadDiff(project_path),
+                };
+                self.handle_message(message);
+            }
+            Message::UpdateLoadProgress(id, done, total) => {
+                if self.is_current_load(id) {
+                    self.control_data.load_progress = Some((done, total))
+                }
+            }
+            Message::LoadFailed(id, error) => {
+                if self.is_current_load(id) {
+                    self.control_data.load = None;
+                    self.control_data.load_progress = None;
+                    self.restoring = None;
+                    self.control_data.recent_projects.opening_failed();
+                    self.handle_message(Message::ShowError(error));
+                }
+            }
+            Message::CancelLoad => {
+                if let Some(load) = self.control_data.load.take() {
+                    load.cancel.store(true, Ordering::Relaxed);
+                }
+                self.control_data.load_progress = None;
+            }
+            Message::UpdateAppData(id, app_data) => {
+                // Results of cancelled or replaced listings leave the shown diffs alone.
+                if !self.is_current_load(id) {
+                    return;
+                }
+                self.control_data.load = None;
+                self.control_data.load_progress = None;
+                if !app_data.shows_working_tree() {
+                    self.control_data.diff_type = DiffType::Modified;
+                }
+                if app_data.compared_directory.is_none() && !app_data.pasted {
+                    self.control_data
+                        .recent_projects
+                        .add(&app_data.project_path);
+                }
+                let mut app_data = app_data;
+                app_data.apply_ignores(&self.control_data.settings, self.control_data.show_ignored);
+                app_data.detect_moves(self.control_data.settings.detect_moves);
+                let snapshot = self
+                    .app_data
+                    .as_ref()
+                    .filter(|old| ViewSnapshot::is_refresh(old, &app_data))
+                    .map(|old| {
+                        ViewSnapshot::take(old, &self.control_data, ui::top_row(&self.ctx, 0))
                     });
+                match snapshot {
+                    Some(snapshot) => snapshot.restore(&app_data, &mut self.control_data),
+                    None => self.control_data.viewed.clear(),
                 }
-                Message::UpdateAppData(app_data) => self.app_data = Some(app_data),
-                Message::ChangeDiffType(diff_type) => self.control_data.diff_type = diff_type,
-                Message::ChangeSelectedDiffIndex(i) => self.control_data.selected_diff_index = i,
-                Message::ShowError(error) => {
-                    self.control_data.error_information = error;
-                    self.control_data.show_err_dialog = true;
+                self.app_data = Some(*app_data);
+                self.control_data.loading_diffs.clear();
+                self.control_data.history.clear();
+                self.start_search();
+                self.load_file_times();
+                self.continue_restoring();
+            }
+            Message::UpdateFileTimes(file_times) => self.control_data.file_times = file_times,
+            Message::UpdateDiff(diff_type, i, diff) => {
+                self.control_data
+                    .loading_diffs
+                    .remove(&(diff_type.clone(), i));
+                if let Some(app_data) = &mut self.app_data {
+                    let diff_data = app_data.diff_data_mut(&diff_type);
+                    diff_data.update_diff(i, *diff);
+                    diff_data.detect_moves(self.control_data.settings.detect_moves);
                 }
-                Message::CloseError => {
-                    self.control_data.error_information = "".to_string();
-                    self.control_data.show_err_dialog = false;
+            }
+            Message::DiffLoadFailed(diff_type, i, file_name, error) => {
+                self.control_data
+                    .loading_diffs
+                    .remove(&(diff_type.clone(), i));
+                if let Some(app_data) = &mut self.app_data {
+                    let diff_data = app_data.diff_data_mut(&diff_type);
+                    diff_data.set_load_error(i, &file_name, Some(error));
+                    diff_data.detect_moves(self.control_data.settings.detect_moves);
                 }
-            },
-            Err(err) => match err {
-                TryRecvError::Disconnected => {
-                    self.control_data.error_information = "Thread disconnected!".to_string();
-                    self.control_data.show_err_dialog = true;
+            }
+            Message::RetryDiff(i) => {
+                // Picked up again by the next call to `load_visible_diffs`.
+                if let Some(app_data) = &mut self.app_data {
+                    let diff_data = app_data.diff_data_mut(&self.control_data.diff_type);
+                    if let Some(file_name) = diff_data.diffs.get(i).map(git::Diff::file_name) {
+                        diff_data.set_load_error(i, &file_name, None);
+                    }
+                }
+            }
+            Message::ChangeDiffType(diff_type) => {
+                let current = self.current_location();
+                self.control_data.history.push(current);
+                // The selected file stays selected if it has changes of the new type too.
+                let selected = self.control_data.selected_diff_index;
+                self.control_data.selected_diff_index =
+                    self.diff_index_in(selected, &diff_type).unwrap_or(0);
+                self.keep_secondary_diff(&diff_type);
+                self.control_data.diff_type = diff_type;
+                self.start_search();
+            }
+            Message::ChangeSelectedDiff(diff_type, i) => {
+                let current = self.current_location();
+                self.control_data.history.push(current);
+                if diff_type != self.control_data.diff_type {
+                    self.keep_secondary_diff(&diff_type);
+                    self.control_data.diff_type = diff_type;
+                    self.start_search();
+                }
+                self.control_data.selected_diff_index = i;
+            }
+            Message::ChangeSelectedDiffIndex(i) => {
+                let current = self.current_location();
+                self.control_data.history.push(current);
+                self.control_data.selected_diff_index = i;
+            }
+            Message::ChangeFileFilter(filter) => {
+                self.control_data.file_filter = filter;
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                // Files hidden by the filter aren't left selected.
+                let order = (self.control_data)
+                    .listed_order(&app_data.diff_data(&self.control_data.diff_type).diffs);
+                if !order.contains(&self.control_data.selected_diff_index) {
+                    if let Some(&first) = order.first() {
+                        self.handle_message(Message::ChangeSelectedDiffIndex(first));
+                    }
+                }
+            }
+            Message::ShowError(error) => {
+                self.control_data.last_error = Some(error.clone());
+                self.control_data.error_information = error;
+                self.control_data.show_err_dialog = true;
+            }
+            Message::CopyUnifiedDiff => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let diffs = app_data
+                    .diff_data(&self.control_data.diff_type)
+                    .diffs
+                    .clone();
+                self.export_patch(diffs, None);
+            }
+            Message::SavePatch(path) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let diffs = app_data
+                    .diff_data(&self.control_data.diff_type)
+                    .diffs
+                    .clone();
+                self.export_patch(diffs, Some(path));
+            }
+            Message::CopyPatch(i) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let diffs = &app_data.diff_data(&self.control_data.diff_type).diffs;
+                self.export_patch(diffs.get(i).cloned().into_iter().collect(), None);
+            }
+            Message::LoadAnnotations(path) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let project_path = app_data.project_path.clone();
+                let s = self.sender.clone();
+                let ctx = self.ctx.clone();
+                thread::spawn(move || {
+                    let result = match annotations::load(&path, &project_path) {
+                        Ok(annotations) => s.send(Message::UpdateAnnotations(annotations)),
+                        Err(err) => s.send(Message::ShowError(format!(
+                            "Could not read annotations: {}",
+                            err
+                        ))),
+                    };
+                    ctx.request_repaint();
+                    result
+                });
+            }
+            Message::UpdateAnnotations(annotations) => self.control_data.annotations = annotations,
+            Message::CopyText(text) => self.ctx.output_mut(|o| o.copied_text = text),
+            Message::BlameLine(diff, line_no) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                self.control_data.blame = Some(LineBlame {
+                    file_name: diff.file_name(),
+                    line_no,
+                    state: BlameState::Loading,
+                });
+                let project_path = app_data.project_path.clone();
+                let s = self.sender.clone();
+                let ctx = self.ctx.clone();
+                thread::spawn(move || {
+                    let state = match git::blame_line(project_path, &diff, line_no) {
+                        Ok(Some(commit)) => BlameState::Commit(commit),
+                        Ok(None) => BlameState::Uncommitted,
+                        Err(err) => BlameState::Failed(err.message()),
+                    };
+                    let result = s.send(Message::UpdateBlame(state));
+                    ctx.request_repaint();
+                    result
+                });
+            }
+            Message::UpdateBlame(state) => {
+                if let Some(blame) = &mut self.control_data.blame {
+                    blame.state = state;
                 }
-                TryRecvError::Empty => (),
+            }
+            Message::CloseBlame => self.control_data.blame = None,
+            Message::ShowFileHistory(i) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let Some(diff) = app_data
+                    .diff_data(&self.control_data.diff_type)
+                    .diffs
+                    .get(i)
+                else {
+                    return;
+                };
+                if app_data.pasted || app_data.compared_directory.is_some() {
+                    return self.handle_message(Message::ShowError(
+                        "Only files of a repository have a history!".to_string(),
+                    ));
+                }
+                self.control_data.file_history = Some(FileHistory {
+                    file_name: diff.file_name(),
+                    follow_renames: self.control_data.settings.follow_renames,
+                    state: FileHistoryState::Loading,
+                });
+                self.load_file_history();
+            }
+            Message::FollowRenames(follow_renames) => {
+                self.control_data.settings.follow_renames = follow_renames;
+                if let Some(history) = &mut self.control_data.file_history {
+                    history.follow_renames = follow_renames;
+                    history.state = FileHistoryState::Loading;
+                }
+                self.load_file_history();
+            }
+            Message::UpdateFileHistory(file_name, follow_renames, state) => {
+                // What was asked for while the history before was still loading replaces it.
+                if let Some(history) = &mut self.control_data.file_history {
+                    if (&history.file_name, history.follow_renames) == (&file_name, follow_renames)
+                    {
+                        history.state = state;
+                    }
+                }
+            }
+            Message::CloseFileHistory => self.control_data.file_history = None,
+            Message::OpenExternalDiff(i) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let Some(diff) = app_data
+                    .diff_data(&self.control_data.diff_type)
+                    .diffs
+                    .get(i)
+                    .cloned()
+                else {
+                    return;
+                };
+                if app_data.pasted {
+                    return self.handle_message(Message::ShowError(
+                        "Pasted patches have no files to open!".to_string(),
+                    ));
+                }
+                let project_path = app_data.project_path.clone();
+                let compared_directory = app_data.compared_directory.clone();
+                let template = self.control_data.settings.external_diff_command.clone();
+                let s = self.sender.clone();
+                thread::spawn(move || {
+                    // Compared directories already have both sides on disk.
+                    let sides = match compared_directory {
+                        Some(old_dir) => Ok((
+                            Path::new(&old_dir).join(diff.file_name()),
+                            Path::new(&project_path).join(diff.file_name()),
+                        )),
+                        None => git::write_sides_to_temp(project_path, &diff),
+                    };
+                    let Ok((old, new)) = sides else {
+                        return s.send(Message::ShowError(
+                            "Error writing file contents!".to_string(),
+                        ));
+                    };
+                    match external::launch_diff_tool(&template, &old, &new) {
+                        Ok(()) => Ok(()),
+                        Err(err) => s.send(Message::ShowError(err.message())),
+                    }
+                });
+            }
+            Message::RevealInFileManager(i) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let Some(diff) = app_data
+                    .diff_data(&self.control_data.diff_type)
+                    .diffs
+                    .get(i)
+                else {
+                    return;
+                };
+                if app_data.pasted {
+                    return self.handle_message(Message::ShowError(
+                        "Pasted patches have no files to reveal!".to_string(),
+                    ));
+                }
+                let path = Path::new(&app_data.project_path).join(diff.worktree_file_name());
+                if let Err(err) = external::reveal_in_file_manager(&path) {
+                    self.sender
+                        .send(Message::ShowError(err.message()))
+                        .expect("Channel closed unexpectedly!");
+                }
+            }
+            Message::OpenInEditor(diff, line_no) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                if app_data.pasted {
+                    return self.handle_message(Message::ShowError(
+                        "Pasted patches have no files to open!".to_string(),
+                    ));
+                }
+                // The line is on the new side, so is the file it's in.
+                let path = Path::new(&app_data.project_path).join(diff.new_file_name());
+                if !path.exists() {
+                    return self.handle_message(Message::ShowError(format!(
+                        "\"{}\" doesn't exist anymore!",
+                        diff.new_file_name()
+                    )));
+                }
+                let template = &self.control_data.settings.editor_command;
+                if let Err(err) = external::open_in_editor(template, &path, line_no) {
+                    self.sender
+                        .send(Message::ShowError(err.message()))
+                        .expect("Channel closed unexpectedly!");
+                }
+            }
+            Message::OpenSecondaryPane(i) => self.control_data.secondary_diff_index = Some(i),
+            Message::CloseSecondaryPane => self.control_data.secondary_diff_index = None,
+            Message::ToggleSearch => self.control_data.show_search = !self.control_data.show_search,
+            Message::ToggleLog => self.control_data.show_log = !self.control_data.show_log,
+            Message::SearchAllFiles(query) => {
+                self.control_data.search_query = query;
+                self.start_search();
+            }
+            Message::AddSearchResult(id, file_matches) => {
+                // Results of a search that has since been replaced are dropped, even for the
+                // same query, as their indices are into the diffs that were shown then.
+                if id == self.control_data.search_count {
+                    self.control_data.search_results.push(file_matches);
+                }
+            }
+            Message::JumpToRow(diff_index, row) => {
+                let current = self.current_location();
+                self.control_data.history.push(current);
+                self.control_data.selected_diff_index = diff_index;
+                self.control_data.scroll_to_row = Some(row);
+            }
+            Message::StepSelectedDiff(forward) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                // In the order the file list shows them.
+                let order = (self.control_data)
+                    .listed_order(&app_data.diff_data(&self.control_data.diff_type).diffs);
+                let selected = self.control_data.selected_diff_index;
+                let Some(position) = order.iter().position(|&i| i == selected) else {
+                    return;
+                };
+                let next = match forward {
+                    true => order.get(position + 1),
+                    false => position
+                        .checked_sub(1)
+                        .and_then(|position| order.get(position)),
+                };
+                if let Some(&next) = next {
+                    self.handle_message(Message::ChangeSelectedDiffIndex(next));
+                }
+            }
+            // Like stepping through matches, stepping through hunks isn't kept in the history.
+            Message::StepHunk(forward) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let row = app_data
+                    .diff_data(&self.control_data.diff_type)
+                    .diffs
+                    .get(self.control_data.selected_diff_index)
+                    .and_then(|diff| diff.adjacent_hunk_row(ui::top_row(&self.ctx, 0), forward));
+                if let Some(row) = row {
+                    self.control_data.scroll_to_row = Some(row);
+                }
+            }
+            Message::StepChange(forward) => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                // Once the end of the file is in view, so are the changes below the top row,
+                // so the next one is the first.
+                let from = match forward && ui::shows_end(&self.ctx, 0) {
+                    true => usize::MAX,
+                    false => ui::top_row(&self.ctx, 0),
+                };
+                let row = app_data
+                    .diff_data(&self.control_data.diff_type)
+                    .diffs
+                    .get(self.control_data.selected_diff_index)
+                    .and_then(|diff| diff.adjacent_change_row(from, forward));
+                if let Some(row) = row {
+                    self.control_data.scroll_to_row = Some(row);
+                }
+            }
+            Message::ToggleFind => self.control_data.show_find = !self.control_data.show_find,
+            Message::UpdateFindQuery(query) => {
+                self.control_data.find_query = query;
+                self.control_data.find_match = 0;
+            }
+            // Stepping through matches isn't kept in the history, unlike search results.
+            Message::FindMatch(index, row) => {
+                self.control_data.find_match = index;
+                self.control_data.scroll_to_row = Some(row);
+            }
+            Message::NavigateBack => {
+                let current = self.current_location();
+                if let Some(location) = self.control_data.history.go_back(current) {
+                    self.go_to(location);
+                }
+            }
+            Message::NavigateForward => {
+                let current = self.current_location();
+                if let Some(location) = self.control_data.history.go_forward(current) {
+                    self.go_to(location);
+                }
+            }
+            Message::ToggleSettings => {
+                self.control_data.show_settings = !self.control_data.show_settings
+            }
+            Message::ToggleAbout => self.control_data.show_about = !self.control_data.show_about,
+            Message::TogglePastePatch => {
+                self.control_data.show_paste_patch = !self.control_data.show_paste_patch
+            }
+            Message::ViewPatch(text) => match AppData::from_patch(&text) {
+                Ok(app_data) => {
+                    self.control_data.show_paste_patch = false;
+                    let (id, _) = self.start_load("Reading patch…");
+                    self.handle_message(Message::UpdateAppData(id, Box::new(app_data)));
+                }
+                Err(err) => self.handle_message(Message::ShowError(err.message())),
             },
+            Message::ToggleDebugOverlay => {
+                self.control_data.show_debug_overlay = !self.control_data.show_debug_overlay
+            }
+            Message::ToggleFileList => {
+                self.control_data.files_collapsed = !self.control_data.files_collapsed
+            }
+            Message::ToggleViewed(file_name) => {
+                let key = (self.control_data.diff_type.clone(), file_name);
+                if !self.control_data.viewed.remove(&key) {
+                    self.control_data.viewed.insert(key);
+                }
+            }
+            Message::MarkViewedAndAdvance => {
+                let Some(app_data) = &self.app_data else {
+                    return;
+                };
+                let diff_type = self.control_data.diff_type.clone();
+                let names = app_data.diff_data(&diff_type).file_names();
+                let selected = self.control_data.selected_diff_index;
+                let Some(name) = names.get(selected) else {
+                    return;
+                };
+                self.control_data
+                    .viewed
+                    .insert((diff_type.clone(), name.clone()));
+
+                let order = (self.control_data).listed_order(&app_data.diff_data(&diff_type).diffs);
+                let position = order.iter().position(|&i| i == selected).unwrap_or(0);
+                let next = (1..order.len())
+                    .map(|offset| order[(position + offset) % order.len()])
+                    .find(|&i| {
+                        !self
+                            .control_data
+                            .viewed
+                            .contains(&(diff_type.clone(), names[i].clone()))
+                    });
+                if let Some(next) = next {
+                    self.handle_message(Message::ChangeSelectedDiffIndex(next));
+                }
+            }
+            Message::ToggleGallery => self.control_data.gallery = !self.control_data.gallery,
+            Message::ChangeViewMode(view_mode) => self.control_data.view_mode = view_mode,
+            Message::ToggleStatsOnly => {
+                self.control_data.stats_only = !self.control_data.stats_only
+            }
+            Message::UpdateSettings(settings) => {
+                let ignores_changed = self.app_data.as_ref().is_some_and(|app_data| {
+                    let path = &app_data.project_path;
+                    settings.for_repo(path).ignored_paths
+                        != self.control_data.settings.for_repo(path).ignored_paths
+                });
+                let diff_settings_changed =
+                    settings.diff_settings() != self.control_data.settings.diff_settings();
+                let moves_changed =
+                    settings.detect_moves != self.control_data.settings.detect_moves;
+                self.control_data.settings = *settings;
+                if moves_changed {
+                    if let Some(app_data) = &mut self.app_data {
+                        app_data.detect_moves(self.control_data.settings.detect_moves);
+                    }
+                }
+                if ignores_changed {
+                    self.apply_ignores();
+                }
+                if diff_settings_changed {
+                    self.handle_message(Message::Refresh);
+                }
+            }
+            Message::ToggleShowIgnored => {
+                self.control_data.show_ignored = !self.control_data.show_ignored;
+                self.apply_ignores();
+            }
+            Message::CloseError => {
+                self.control_data.error_information = "".to_string();
+                self.control_data.show_err_dialog = false;
+            }
+        }
+    }
+
+    /// Filters the listed files again after the ignored paths or their visibility changed,
+    /// keeping the selected files where possible.
+    fn apply_ignores(&mut self) {
+        let Some(app_data) = &mut self.app_data else {
+            return;
+        };
+
+        let snapshot = ViewSnapshot::take(app_data, &self.control_data, ui::top_row(&self.ctx, 0));
+        app_data.apply_ignores(&self.control_data.settings, self.control_data.show_ignored);
+        app_data.detect_moves(self.control_data.settings.detect_moves);
+        snapshot.restore(app_data, &mut self.control_data);
+        self.control_data.loading_diffs.clear();
+        self.start_search();
+    }
+
+    /// Starts tracking a new listing, cancelling the one still running.
+    fn start_load(&mut self, status: &'static str) -> (usize, Arc<AtomicBool>) {
+        if let Some(load) = self.control_data.load.take() {
+            load.cancel.store(true, Ordering::Relaxed);
+        }
+        self.control_data.load_progress = None;
+        self.control_data.load_count += 1;
+        self.control_data.recent_projects.opening = None;
+
+        let load = Load {
+            id: self.control_data.load_count,
+            cancel: Arc::new(AtomicBool::new(false)),
+            status,
+        };
+        let started = (load.id, load.cancel.clone());
+        self.control_data.load = Some(load);
+        started
+    }
+
+    /// Writes `diffs` as a patch `git apply` takes to `path`, or copies it if there is none.
+    /// The diffs that aren't loaded yet are loaded on a background thread.
+    fn export_patch(&self, diffs: Vec<git::Diff>, path: Option<PathBuf>) {
+        let Some(app_data) = &self.app_data else {
+            return;
+        };
+        let project_path = app_data.project_path.clone();
+        let s = self.sender.clone();
+        let ctx = self.ctx.clone();
+        thread::spawn(move || {
+            let mut patch = String::new();
+            for diff in diffs {
+                let diff = match diff.is_loaded() {
+                    true => diff,
+                    false => match git::load_diff(project_path.clone(), &diff) {
+                        Ok(diff) => diff,
+                        Err(err) => return s.send(Message::ShowError(err.message())),
+                    },
+                };
+                patch.push_str(&diff.to_unified_string());
+            }
+            let result = match path {
+                Some(path) => match fs::write(&path, patch) {
+                    Ok(()) => Ok(()),
+                    Err(err) => s.send(Message::ShowError(format!(
+                        "Couldn't save the patch to {}: {}",
+                        path.display(),
+                        err
+                    ))),
+                },
+                None => s.send(Message::CopyText(patch)),
+            };
+            ctx.request_repaint();
+            result
+        });
+    }
+
+    fn is_current_load(&self, id: usize) -> bool {
+        self.control_data.load.as_ref().map(|load| load.id) == Some(id)
+    }
+
+    /// Sends the progress of a listing to the UI, once it has taken long enough to be noticed,
+    /// and stops the listing when it is cancelled.
+    fn progress_reporter(
+        &self,
+        id: usize,
+        cancel: Arc<AtomicBool>,
+    ) -> impl FnMut(usize, usize) -> bool + Send {
+        let s = self.sender.clone();
+        let ctx = self.ctx.clone();
+        let start = Instant::now();
+        let mut last_report = start;
+        move |done, total| {
+            if cancel.load(Ordering::Relaxed) {
+                return false;
+            }
+            let now = Instant::now();
+            if now - start >= PROGRESS_DELAY && now - last_report >= PROGRESS_INTERVAL {
+                last_report = now;
+                let _ = s.send(Message::UpdateLoadProgress(id, done, total));
+                ctx.request_repaint();
+            }
+            true
+        }
+    }
+
+    /// Where the file at `i` among the diffs of the current type is among those of
+    /// `diff_type`, if it has changes of that type too.
+    fn diff_index_in(&self, i: usize, diff_type: &DiffType) -> Option<usize> {
+        let app_data = self.app_data.as_ref()?;
+        let file_name = app_data
+            .diff_data(&self.control_data.diff_type)
+            .diffs
+            .get(i)?
+            .file_name();
+        app_data
+            .diff_data(diff_type)
+            .diffs
+            .iter()
+            .position(|diff| diff.file_name() == file_name)
+    }
+
+    /// Keeps the file of the second pane open when switching to `diff_type`, or closes the
+    /// pane if the file has no changes of that type.
+    fn keep_secondary_diff(&mut self, diff_type: &DiffType) {
+        self.control_data.secondary_diff_index = self
+            .control_data
+            .secondary_diff_index
+            .and_then(|i| self.diff_index_in(i, diff_type));
+    }
+
+    fn current_location(&self) -> Location {
+        Location {
+            diff_type: self.control_data.diff_type.clone(),
+            diff_index: self.control_data.selected_diff_index,
+            row: ui::top_row(&self.ctx, 0),
         }
     }
+
+    fn go_to(&mut self, location: Location) {
+        if location.diff_type != self.control_data.diff_type {
+            self.keep_secondary_diff(&location.diff_type);
+            self.control_data.diff_type = location.diff_type;
+            self.start_search();
+        }
+        self.control_data.selected_diff_index = location.diff_index;
+        self.control_data.scroll_to_row = Some(location.row);
+    }
+
+    /// Computes the lines of the diffs shown in the file panes, if they haven't been yet.
+    fn load_visible_diffs(&mut self) {
+        let Some(app_data) = &self.app_data else {
+            return;
+        };
+
+        let diff_type = self.control_data.diff_type.clone();
+        let mut panes = vec![self.control_data.selected_diff_index];
+        panes.extend(self.control_data.secondary_diff_index);
+        if self.control_data.gallery {
+            panes.extend(ui::gallery_files(&self.ctx));
+        }
+        for i in panes {
+            let Some(diff) = app_data.diff_data(&diff_type).diffs.get(i) else {
+                continue;
+            };
+            if diff.is_loaded()
+                || diff.load_error().is_some()
+                || !self
+                    .control_data
+                    .loading_diffs
+                    .insert((diff_type.clone(), i))
+            {
+                continue;
+            }
+
+            let project_path = app_data.project_path.clone();
+            let diff = diff.clone();
+            let diff_type = diff_type.clone();
+            let s = self.sender.clone();
+            let ctx = self.ctx.clone();
+            thread::spawn(move || {
+                let result = match git::load_diff(project_path, &diff) {
+                    Ok(diff) => s.send(Message::UpdateDiff(diff_type, i, Box::new(diff))),
+                    Err(err) => s.send(Message::DiffLoadFailed(
+                        diff_type,
+                        i,
+                        diff.file_name(),
+                        err.message(),
+                    )),
+                };
+                ctx.request_repaint();
+                result
+            });
+        }
+    }
+
+    /// Computes the lines of every file of the current diff type one after the other, which
+    /// move detection needs to compare them.
+    fn load_all_diffs(&mut self) {
+        let Some(app_data) = &self.app_data else {
+            return;
+        };
+        if !self.control_data.settings.detect_moves {
+            return;
+        }
+
+        let diff_type = self.control_data.diff_type.clone();
+        let diffs = app_data
+            .diff_data(&diff_type)
+            .diffs
+            .iter()
+            .enumerate()
+            .filter(|(i, diff)| {
+                !diff.is_loaded()
+                    && diff.load_error().is_none()
+                    && !self
+                        .control_data
+                        .loading_diffs
+                        .contains(&(diff_type.clone(), *i))
+            })
+            .map(|(i, diff)| (i, diff.clone()))
+            .collect::<Vec<(usize, git::Diff)>>();
+        if diffs.is_empty() {
+            return;
+        }
+        for (i, _) in &diffs {
+            self.control_data
+                .loading_diffs
+                .insert((diff_type.clone(), *i));
+        }
+
+        let project_path = app_data.project_path.clone();
+        let s = self.sender.clone();
+        let ctx = self.ctx.clone();
+        thread::spawn(move || {
+            for (i, diff) in diffs {
+                let message = match git::load_diff(project_path.clone(), &diff) {
+                    Ok(diff) => Message::UpdateDiff(diff_type.clone(), i, Box::new(diff)),
+                    Err(err) => Message::DiffLoadFailed(
+                        diff_type.clone(),
+                        i,
+                        diff.file_name(),
+                        err.message(),
+                    ),
+                };
+                if s.send(message).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    /// Looks up when the changed files were last touched on a background thread.
+    fn load_file_times(&mut self) {
+        self.control_data.file_times = FileTimes::default();
+        let Some(app_data) = &self.app_data else {
+            return;
+        };
+        // The working tree's modification times say nothing about the files of a commit, a
+        // stash or other revisions.
+        if app_data.commit.is_some() || app_data.stash.is_some() || app_data.revisions.is_some() {
+            return;
+        }
+
+        let project_path = app_data.project_path.clone();
+        // Files only staged are listed with all changes too, and dated by modification there.
+        let mut modified_files = app_data.modified_diff_data.file_names();
+        modified_files.extend(app_data.combined_diff_data.file_names());
+        let staged_files = app_data.staged_diff_data.file_names();
+        let s = self.sender.clone();
+        let ctx = self.ctx.clone();
+        thread::spawn(move || {
+            let file_times = FileTimes {
+                committed: git::last_commit_times(project_path.clone(), staged_files)
+                    .unwrap_or_default(),
+                modified: git::modified_times(project_path, modified_files),
+            };
+            let result = s.send(Message::UpdateFileTimes(file_times));
+            ctx.request_repaint();
+            result
+        });
+    }
+
+    /// Lists the commits of the file whose history is shown on a background thread.
+    fn load_file_history(&self) {
+        let (Some(app_data), Some(history)) = (&self.app_data, &self.control_data.file_history)
+        else {
+            return;
+        };
+        let project_path = app_data.project_path.clone();
+        let file_name = history.file_name.clone();
+        let follow_renames = history.follow_renames;
+        let s = self.sender.clone();
+        let ctx = self.ctx.clone();
+        thread::spawn(move || {
+            let log = git::file_log(project_path, &file_name, LOG_LENGTH, follow_renames);
+            let state = match log {
+                Ok(log) => FileHistoryState::Commits(log),
+                Err(err) => FileHistoryState::Failed(err.message()),
+            };
+            let result = s.send(Message::UpdateFileHistory(file_name, follow_renames, state));
+            ctx.request_repaint();
+            result
+        });
+    }
+
+    /// Searches every file of the current diff type on a background thread,
+    /// streaming results back one file at a time.
+    fn start_search(&mut self) {
+        self.control_data.search_results.clear();
+        self.control_data.search_count += 1;
+        let id = self.control_data.search_count;
+        self.control_data
+            .search_cancel
+            .store(true, Ordering::Relaxed);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.control_data.search_cancel = cancel.clone();
+
+        self.control_data.search_error = None;
+
+        let query = self.control_data.search_query.clone();
+        let Some(app_data) = &self.app_data else {
+            return;
+        };
+        let matcher = match query.matcher() {
+            Ok(Some(matcher)) => matcher,
+            Ok(None) => return,
+            Err(err) => {
+                self.control_data.search_error = Some(err.to_string());
+                return;
+            }
+        };
+
+        let project_path = app_data.project_path.clone();
+        let diff_type = self.control_data.diff_type.clone();
+        let diffs = app_data.diff_data(&diff_type).diffs.clone();
+        let s = self.sender.clone();
+        let ctx = self.ctx.clone();
+        thread::spawn(move || {
+            for (i, diff) in diffs.into_iter().enumerate() {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                // Diffs loaded for searching are kept, so they don't have to be loaded again.
+                let diff = match diff.is_loaded() {
+                    true => diff,
+                    false => match git::load_diff(project_path.clone(), &diff) {
+                        Ok(diff) => {
+                            let update =
+                                Message::UpdateDiff(diff_type.clone(), i, Box::new(diff.clone()));
+                            if s.send(update).is_err() {
+                                return;
+                            }
+                            diff
+                        }
+                        Err(_) => continue,
+                    },
+                };
+
+                if let Some(file_matches) = search::search_diff(i, &diff, &matcher) {
+                    if s.send(Message::AddSearchResult(id, file_matches)).is_err() {
+                        return;
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        });
+    }
 }
 
 impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
         puffin::profile_function!();
         puffin::GlobalProfiler::lock().new_frame();
 
+        let visuals = self.control_data.settings.appearance.visuals();
+        if ctx.style().visuals.dark_mode != visuals.dark_mode {
+            ctx.set_visuals(visuals);
+        }
+
+        if ctx.input(|i| i.modifiers.command && i.modifiers.shift && i.key_pressed(Key::D)) {
+            self.sender
+                .send(Message::ToggleDebugOverlay)
+                .expect("Channel closed unexpectedly!");
+        }
+
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(Key::F)) {
+            self.sender
+                .send(Message::ToggleFind)
+                .expect("Channel closed unexpectedly!");
+        }
+
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(Key::B)) {
+            self.sender
+                .send(Message::ToggleFileList)
+                .expect("Channel closed unexpectedly!");
+        }
+
+        let zoom = ctx.input(|i| match i.modifiers.command {
+            true if i.key_pressed(Key::PlusEquals) => Some(1.0),
+            true if i.key_pressed(Key::Minus) => Some(-1.0),
+            true if i.key_pressed(Key::Num0) => {
+                Some(settings::DEFAULT_FONT_SIZE - self.control_data.settings.font_size)
+            }
+            _ => None,
+        });
+        if let Some(step) = zoom {
+            let settings = self.control_data.settings.zoomed(step);
+            self.sender
+                .send(Message::UpdateSettings(Box::new(settings)))
+                .expect("Channel closed unexpectedly!");
+        }
+
+        // Alt+arrows move the cursor by words in text fields.
+        let typing = ui::is_typing(ctx);
+        let back = ctx.input(|i| {
+            (!typing && i.modifiers.alt && i.key_pressed(Key::ArrowLeft))
+                || i.pointer.button_pressed(PointerButton::Extra1)
+        });
+        let forward = ctx.input(|i| {
+            (!typing && i.modifiers.alt && i.key_pressed(Key::ArrowRight))
+                || i.pointer.button_pressed(PointerButton::Extra2)
+        });
+        if back {
+            self.sender
+                .send(Message::NavigateBack)
+                .expect("Channel closed unexpectedly!");
+        }
+        if forward {
+            self.sender
+                .send(Message::NavigateForward)
+                .expect("Channel closed unexpectedly!");
+        }
+
+        if !typing {
+            let mut settings = self.control_data.settings.clone();
+            ctx.input(|i| {
+                if i.modifiers.is_none() && i.key_pressed(Key::L) {
+                    settings.show_line_numbers = !settings.show_line_numbers;
+                }
+                if i.modifiers.is_none() && i.key_pressed(Key::O) {
+                    settings.show_origins = !settings.show_origins;
+                }
+                if i.modifiers.is_none() && i.key_pressed(Key::W) {
+                    settings.wrap_lines = !settings.wrap_lines;
+                }
+            });
+            if ctx.input(|i| i.modifiers.is_none() && i.key_pressed(Key::X)) {
+                self.sender
+                    .send(Message::MarkViewedAndAdvance)
+                    .expect("Channel closed unexpectedly!");
+            }
+            let shortcuts: [(&[Key], Message); 4] = [
+                (&[Key::J, Key::ArrowDown], Message::StepSelectedDiff(true)),
+                (&[Key::K, Key::ArrowUp], Message::StepSelectedDiff(false)),
+                (&[Key::N], Message::StepHunk(true)),
+                (&[Key::P], Message::StepHunk(false)),
+            ];
+            for (keys, message) in shortcuts {
+                if ctx
+                    .input(|i| i.modifiers.is_none() && keys.iter().any(|&key| i.key_pressed(key)))
+                {
+                    self.sender
+                        .send(message)
+                        .expect("Channel closed unexpectedly!");
+                }
+            }
+            for (key, forward) in [(Key::N, true), (Key::P, false)] {
+                if ctx.input(|i| i.modifiers.shift_only() && i.key_pressed(key)) {
+                    self.sender
+                        .send(Message::StepChange(forward))
+                        .expect("Channel closed unexpectedly!");
+                }
+            }
+            if settings != self.control_data.settings {
+                self.sender
+                    .send(Message::UpdateSettings(Box::new(settings)))
+                    .expect("Channel closed unexpectedly!");
+            }
+        }
+
         self.handle_messages();
+        if let Some((diff, line_no)) = ui::take_blame_request(ctx) {
+            self.handle_message(Message::BlameLine(Box::new(diff), line_no));
+        }
+        if let Some((diff, line_no)) = ui::take_editor_request(ctx) {
+            self.handle_message(Message::OpenInEditor(Box::new(diff), line_no));
+        }
+        self.load_visible_diffs();
+        self.load_all_diffs();
+
+        ui::show(ctx, &self.app_data, &self.control_data, &self.sender);
+
+        let title = self.window_title();
+        if title != self.window_title {
+            frame.set_window_title(&title);
+            self.window_title = title;
+        }
+
+        // A refreshed diff only has rows to scroll to once its lines are loaded.
+        let waiting = self.app_data.as_ref().is_some_and(|app_data| {
+            app_data
+                .diff_data(&self.control_data.diff_type)
+                .diffs
+                .get(self.control_data.selected_diff_index)
+                .is_some_and(|diff| !diff.is_loaded())
+        });
+        if !waiting {
+            self.control_data.scroll_to_row = None;
+        }
+    }
 
-        ui::show(ctx, &self.app_data, &self.control_data, &self.sender)
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, settings::STORAGE_KEY, &self.control_data.settings);
+        let session = Session::new(self.app_data.as_ref(), &self.control_data);
+        eframe::set_value(storage, session::STORAGE_KEY, &session);
+        eframe::set_value(
+            storage,
+            session::RECENT_STORAGE_KEY,
+            &self.control_data.recent_projects,
+        );
     }
 }
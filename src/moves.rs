@@ -0,0 +1,159 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use crate::git::{Diff, LineKind};
+
+/// Runs with fewer non-blank lines than this are too common to tell a move apart from chance.
+const MIN_MOVED_LINES: usize = 3;
+
+/// A run of deleted or inserted lines whose content shows up on the other side of another file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MovedBlock {
+    /// Rows of the run in this file's rendered diff.
+    pub rows: RangeInclusive<usize>,
+    /// Whether the lines were removed here, and added in `file_name`.
+    pub removed: bool,
+    /// The file the lines moved to or from.
+    pub file_name: String,
+    /// First line of the run in the other file, on the side it's on there.
+    pub line_no: u32,
+}
+
+impl MovedBlock {
+    pub fn label_text(&self) -> String {
+        match self.removed {
+            true => format!("moved to {}:{}", self.file_name, self.line_no),
+            false => format!("moved from {}:{}", self.file_name, self.line_no),
+        }
+    }
+}
+
+/// A maximal run of lines of one kind in a diff.
+struct Run {
+    diff_index: usize,
+    rows: RangeInclusive<usize>,
+    line_no: u32,
+}
+
+/// Finds blocks of code removed from one file and added to another, by the content of
+/// their runs of deleted and inserted lines. Indentation and blank lines are ignored, so
+/// blocks that were moved into or out of a scope still match.
+///
+/// Returns the moved blocks of every diff, by its index. Only loaded diffs are considered.
+pub fn detect(diffs: &[Diff]) -> Vec<Vec<MovedBlock>> {
+    let mut insertions: HashMap<Vec<String>, Vec<Run>> = HashMap::new();
+    let mut deletions = Vec::new();
+    for (diff_index, diff) in diffs.iter().enumerate() {
+        for (kind, key, run) in runs(diff_index, diff) {
+            match kind {
+                LineKind::Insertion => insertions.entry(key).or_default().push(run),
+                _ => deletions.push((key, run)),
+            }
+        }
+    }
+
+    let mut moves = vec![Vec::new(); diffs.len()];
+    for (key, removed) in deletions {
+        let Some(candidates) = insertions.get_mut(&key) else {
+            continue;
+        };
+        // Each insertion is the other end of at most one move.
+        let Some(position) = candidates
+            .iter()
+            .position(|added| added.diff_index != removed.diff_index)
+        else {
+            continue;
+        };
+        let added = candidates.remove(position);
+
+        moves[removed.diff_index].push(MovedBlock {
+            rows: removed.rows.clone(),
+            removed: true,
+            file_name: diffs[added.diff_index].file_name(),
+            line_no: added.line_no,
+        });
+        moves[added.diff_index].push(MovedBlock {
+            rows: added.rows,
+            removed: false,
+            file_name: diffs[removed.diff_index].file_name(),
+            line_no: removed.line_no,
+        });
+    }
+    for blocks in &mut moves {
+        blocks.sort_by_key(|block| *block.rows.start());
+    }
+    moves
+}
+
+/// The runs of deleted and inserted lines of a diff that are long enough to count as moves,
+/// with their normalized content.
+fn runs(diff_index: usize, diff: &Diff) -> Vec<(LineKind, Vec<String>, Run)> {
+    let mut runs = Vec::new();
+    let mut current: Option<(LineKind, Vec<String>, Run)> = None;
+    for (line, &row) in diff.lines.iter().zip(&diff.line_rows) {
+        if line.is_eof_marker() {
+            continue;
+        }
+        let kind = line.kind();
+        if !matches!(kind, LineKind::Insertion | LineKind::Deletion) {
+            runs.extend(current.take());
+            continue;
+        }
+        if current
+            .as_ref()
+            .is_some_and(|(run_kind, ..)| *run_kind != kind)
+        {
+            runs.extend(current.take());
+        }
+
+        let (_, key, run) = current.get_or_insert_with(|| {
+            let run = Run {
+                diff_index,
+                rows: row..=row,
+                line_no: line.display_lineno(),
+            };
+            (kind, Vec::new(), run)
+        });
+        run.rows = *run.rows.start()..=row;
+        let content = line.content.trim();
+        if !content.is_empty() {
+            key.push(content.to_string());
+        }
+    }
+    runs.extend(current);
+
+    runs.retain(|(_, key, _)| key.len() >= MIN_MOVED_LINES);
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch;
+
+    #[test]
+    fn blocks_moved_between_files_are_linked() {
+        let (diffs, _) = patch::parse(
+            "--- a/a.rs\n+++ b/a.rs\n@@ -1,5 +1,1 @@\n keep\n-fn moved() {\n-    one();\n-\n-}\n\
+             --- a/b.rs\n+++ b/b.rs\n@@ -1,1 +1,4 @@\n other\n+    fn moved() {\n+        one();\n+    }\n\
+             --- a/c.rs\n+++ b/c.rs\n@@ -1,2 +1,2 @@\n-x\n-y\n+x\n+y\n",
+        )
+        .unwrap();
+
+        let moves = detect(&diffs);
+
+        assert_eq!(
+            moves[0],
+            vec![MovedBlock {
+                rows: 2..=5,
+                removed: true,
+                file_name: "b.rs".to_string(),
+                line_no: 2,
+            }]
+        );
+        assert_eq!(moves[0][0].label_text(), "moved to b.rs:2");
+        assert_eq!(moves[1].len(), 1);
+        assert_eq!(moves[1][0].label_text(), "moved from a.rs:2");
+        // Too short, and within the same file.
+        assert!(moves[2].is_empty());
+    }
+}
@@ -0,0 +1,323 @@
+use std::path::PathBuf;
+
+use git2::Delta;
+
+use crate::git::{Diff, Header, Line, Stats};
+
+#[derive(Debug)]
+pub struct PatchError {
+    /// 1-based line of the patch text.
+    line: usize,
+    reason: String,
+}
+
+impl PatchError {
+    fn new(line: usize, reason: &str) -> PatchError {
+        PatchError {
+            line,
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self.line {
+            0 => format!("Could not read the patch: {}", self.reason),
+            line => format!("Could not read the patch at line {}: {}", line, self.reason),
+        }
+    }
+}
+
+/// A file of the patch whose hunks are being read.
+struct FileBuilder {
+    old_path: Option<PathBuf>,
+    new_path: Option<PathBuf>,
+    status: Delta,
+    is_binary: bool,
+    headers: Vec<Header>,
+    lines: Vec<Line>,
+}
+
+impl FileBuilder {
+    fn new() -> FileBuilder {
+        FileBuilder {
+            old_path: None,
+            new_path: None,
+            status: Delta::Modified,
+            is_binary: false,
+            headers: Vec::new(),
+            lines: Vec::new(),
+        }
+    }
+
+    fn build(self) -> Diff {
+        let old_path = self.old_path.or(self.new_path.clone()).unwrap_or_default();
+        let new_path = self.new_path.unwrap_or(old_path.clone());
+        Diff::from_parts(
+            old_path,
+            new_path,
+            self.status,
+            self.is_binary,
+            self.headers,
+            self.lines,
+        )
+    }
+}
+
+/// Lines of the current hunk still expected on each side.
+struct Hunk {
+    old_remaining: u32,
+    new_remaining: u32,
+    old_lineno: u32,
+    new_lineno: u32,
+}
+
+impl Hunk {
+    fn is_done(&self) -> bool {
+        self.old_remaining == 0 && self.new_remaining == 0
+    }
+}
+
+/// Parses unified diff text, e.g. from `git diff` or `diff -u`, without a repository.
+///
+/// Text before the first file, like an email's headers and message, is skipped.
+pub fn parse(text: &str) -> Result<(Vec<Diff>, Stats), PatchError> {
+    let mut diffs = Vec::new();
+    let mut file: Option<FileBuilder> = None;
+    let mut hunk: Option<Hunk> = None;
+
+    for (index, raw) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = raw.strip_suffix('\r').unwrap_or(raw);
+
+        if let Some(current) = hunk.as_mut().filter(|hunk| !hunk.is_done()) {
+            let builder = file.as_mut().expect("Hunks belong to a file");
+            // Mail clients like to strip the space of empty context lines.
+            let (origin, content) = match line.chars().next() {
+                Some(origin) => (origin, &line[origin.len_utf8()..]),
+                None => (' ', ""),
+            };
+            let (old_lineno, new_lineno) = match origin {
+                ' ' if current.old_remaining > 0 && current.new_remaining > 0 => {
+                    current.old_remaining -= 1;
+                    current.new_remaining -= 1;
+                    (Some(current.old_lineno), Some(current.new_lineno))
+                }
+                '-' if current.old_remaining > 0 => {
+                    current.old_remaining -= 1;
+                    (Some(current.old_lineno), None)
+                }
+                '+' if current.new_remaining > 0 => {
+                    current.new_remaining -= 1;
+                    (None, Some(current.new_lineno))
+                }
+                '\\' => {
+                    push_eof_marker(builder, number)?;
+                    continue;
+                }
+                _ => {
+                    return Err(PatchError::new(
+                        number,
+                        "the hunk is shorter than its header says",
+                    ))
+                }
+            };
+            current.old_lineno += old_lineno.is_some() as u32;
+            current.new_lineno += new_lineno.is_some() as u32;
+            builder.lines.push(Line {
+                old_lineno,
+                new_lineno,
+                content: content.to_string(),
+                origin,
+            });
+            continue;
+        }
+
+        // The marker of a hunk's last line comes after the hunk is complete.
+        if line.starts_with('\\') {
+            if let (Some(builder), Some(_)) = (file.as_mut(), &hunk) {
+                push_eof_marker(builder, number)?;
+                continue;
+            }
+        }
+        hunk = None;
+
+        if let Some(paths) = line.strip_prefix("diff --git ") {
+            diffs.extend(file.take().map(FileBuilder::build));
+            let mut builder = FileBuilder::new();
+            // Only unambiguous for paths without " b/" in them; `---`/`+++` take precedence.
+            if let Some((old, new)) = paths.split_once(" b/") {
+                builder.old_path = Some(strip_side_prefix(old, "a/"));
+                builder.new_path = Some(PathBuf::from(new));
+            }
+            file = Some(builder);
+        } else if let Some(old) = line.strip_prefix("--- ") {
+            // Patches made with plain `diff -u` have no `diff --git` line.
+            let starts_file = !file.as_ref().is_some_and(|file| file.headers.is_empty());
+            if starts_file {
+                diffs.extend(file.take().map(FileBuilder::build));
+                file = Some(FileBuilder::new());
+            }
+            let builder = file.as_mut().expect("A file was just started");
+            match side_path(old, "a/") {
+                Some(path) => builder.old_path = Some(path),
+                None => builder.status = Delta::Added,
+            }
+        } else if let Some(new) = line.strip_prefix("+++ ") {
+            let Some(builder) = file.as_mut() else {
+                return Err(PatchError::new(number, "\"+++\" without a \"---\" line"));
+            };
+            match side_path(new, "b/") {
+                Some(path) => builder.new_path = Some(path),
+                None => builder.status = Delta::Deleted,
+            }
+        } else if line.starts_with("@@") {
+            let Some(builder) = file.as_mut() else {
+                return Err(PatchError::new(number, "hunk outside of a file"));
+            };
+            let (old_start, old_count, new_start, new_count) = parse_hunk_header(line)
+                .ok_or_else(|| PatchError::new(number, "malformed hunk header"))?;
+            builder.headers.push(Header {
+                content: line.to_string(),
+                line: new_start,
+                first_line: builder.lines.len(),
+            });
+            hunk = Some(Hunk {
+                old_remaining: old_count,
+                new_remaining: new_count,
+                old_lineno: old_start,
+                new_lineno: new_start,
+            });
+        } else if let Some(builder) = file.as_mut() {
+            if line.starts_with("new file mode") {
+                builder.status = Delta::Added;
+            } else if line.starts_with("deleted file mode") {
+                builder.status = Delta::Deleted;
+            } else if let Some(path) = line.strip_prefix("rename from ") {
+                builder.old_path = Some(PathBuf::from(path));
+                builder.status = Delta::Renamed;
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                builder.new_path = Some(PathBuf::from(path));
+                builder.status = Delta::Renamed;
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                builder.is_binary = true;
+            }
+        }
+    }
+
+    if hunk.as_ref().is_some_and(|hunk| !hunk.is_done()) {
+        return Err(PatchError::new(
+            text.lines().count(),
+            "the patch ends in the middle of a hunk",
+        ));
+    }
+    diffs.extend(file.map(FileBuilder::build));
+    if diffs.is_empty() {
+        return Err(PatchError::new(0, "no changed files found"));
+    }
+
+    let stats = Stats::of(&diffs);
+    Ok((diffs, stats))
+}
+
+/// Adds a "\ No newline at end of file" marker for the line before it.
+fn push_eof_marker(builder: &mut FileBuilder, number: usize) -> Result<(), PatchError> {
+    // git2 names the markers by the side that keeps its newline.
+    let origin = match builder.lines.last().map(|line| line.origin) {
+        Some(' ') => '=',
+        Some('-') => '>',
+        Some('+') => '<',
+        _ => return Err(PatchError::new(number, "end-of-file marker without a line")),
+    };
+    builder.lines.push(Line {
+        old_lineno: None,
+        new_lineno: None,
+        content: "\n\\ No newline at end of file".to_string(),
+        origin,
+    });
+
+    Ok(())
+}
+
+/// Path of a `---` or `+++` line, `None` for `/dev/null`.
+fn side_path(text: &str, prefix: &str) -> Option<PathBuf> {
+    // `diff -u` puts a timestamp after the path.
+    let path = text.split('\t').next().unwrap_or(text);
+    match path {
+        "/dev/null" => None,
+        path => Some(strip_side_prefix(path, prefix)),
+    }
+}
+
+fn strip_side_prefix(path: &str, prefix: &str) -> PathBuf {
+    PathBuf::from(path.strip_prefix(prefix).unwrap_or(path))
+}
+
+/// Start and length of both sides of `@@ -1,2 +1,3 @@`, where lengths of 1 may be left out.
+fn parse_hunk_header(line: &str) -> Option<(u32, u32, u32, u32)> {
+    let mut ranges = line.strip_prefix("@@ ")?.split(' ');
+    let side = |range: Option<&str>, sign: char| {
+        let range = range?.strip_prefix(sign)?;
+        let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+        Some((start.parse().ok()?, count.parse().ok()?))
+    };
+    let (old_start, old_count) = side(ranges.next(), '-')?;
+    let (new_start, new_count) = side(ranges.next(), '+')?;
+
+    Some((old_start, old_count, new_start, new_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+From: someone
+Subject: [PATCH] Change things
+
+diff --git a/src/a.txt b/src/a.txt
+index 1111111..2222222 100644
+--- a/src/a.txt
++++ b/src/a.txt
+@@ -1,3 +1,3 @@ fn main() {
+ one
+-two
++2
+ three
+\\ No newline at end of file
+diff --git a/new.txt b/new.txt
+new file mode 100644
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+";
+
+    #[test]
+    fn patches_are_parsed() {
+        let (diffs, stats) = parse(PATCH).unwrap();
+
+        assert_eq!(diffs.len(), 2);
+        assert_eq!((stats.insertions, stats.deletions), (2, 1));
+        assert_eq!(diffs[0].file_name(), "src/a.txt");
+        assert_eq!(diffs[0].headers[0].content, "@@ -1,3 +1,3 @@ fn main() {");
+        assert_eq!(diffs[0].lines.last().unwrap().origin, '=');
+        assert_eq!(diffs[0].row_count(), 5);
+        assert_eq!(
+            diffs[0].row_description(2).as_deref(),
+            Some("removed line 2: two")
+        );
+        assert_eq!(diffs[1].file_name(), "new.txt");
+        assert_eq!(diffs[1].lines[0].new_lineno, Some(1));
+    }
+
+    #[test]
+    fn malformed_patches_are_rejected() {
+        let truncated = "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n-one\n";
+        assert_eq!(
+            parse(truncated).unwrap_err().message(),
+            "Could not read the patch at line 4: the patch ends in the middle of a hunk"
+        );
+        assert!(parse("just some text").is_err());
+        assert!(parse("--- a/a.txt\n+++ b/a.txt\n@@ -x +1 @@\n").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::git::Diff;
+
+/// What the search panel looks for.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct SearchQuery {
+    pub text: String,
+    /// Treat `text` as a regular expression rather than a plain substring.
+    pub regex: bool,
+    /// Match case rather than ignoring it.
+    pub case_sensitive: bool,
+}
+
+impl SearchQuery {
+    /// Compiles the query, matching case-insensitively unless asked not to. Empty queries
+    /// match nothing.
+    pub fn matcher(&self) -> Result<Option<Regex>, regex::Error> {
+        if self.text.is_empty() {
+            return Ok(None);
+        }
+
+        let pattern = match self.regex {
+            true => self.text.clone(),
+            false => regex::escape(&self.text),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map(Some)
+    }
+}
+
+/// Compiles the query of the file list filter, which matches paths containing it, or the whole
+/// path as a glob if it has a `*` or `?`. `*` also crosses directories. Both ignore case, and
+/// empty queries match every file.
+pub fn file_filter(query: &str) -> Option<Regex> {
+    let query = query.trim();
+    if query.is_empty() {
+        return None;
+    }
+    if !query.contains(['*', '?']) {
+        return Some(file_filter_regex(&regex::escape(query)));
+    }
+
+    let mut pattern = "^".to_string();
+    for c in query.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+    Some(file_filter_regex(&pattern))
+}
+
+fn file_filter_regex(pattern: &str) -> Regex {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .expect("Escaped query should be a valid regex")
+}
+
+#[derive(Clone)]
+pub struct FileMatches {
+    pub diff_index: usize,
+    pub file_name: String,
+    pub matches: Vec<LineMatch>,
+}
+
+#[derive(Clone)]
+pub struct LineMatch {
+    /// Row of the match in the rendered diff, counting hunk headers.
+    pub row: usize,
+    pub line_no: u32,
+    pub origin: char,
+    pub content: String,
+}
+
+/// The row of every match in a file's rendered diff, once for each time it matches.
+pub fn match_rows(diff: &Diff, matcher: &Regex) -> Vec<usize> {
+    diff.lines
+        .iter()
+        .zip(&diff.line_rows)
+        .filter(|(line, _)| !line.is_eof_marker())
+        .flat_map(|(line, &row)| matcher.find_iter(&line.content).map(move |_| row))
+        .collect()
+}
+
+/// Searches the changed content of a single file.
+pub fn search_diff(diff_index: usize, diff: &Diff, matcher: &Regex) -> Option<FileMatches> {
+    let matches = diff
+        .lines
+        .iter()
+        .zip(&diff.line_rows)
+        .filter(|(line, _)| !line.is_eof_marker())
+        .filter(|(line, _)| matcher.is_match(&line.content))
+        .map(|(line, row)| LineMatch {
+            row: *row,
+            line_no: line.display_lineno(),
+            origin: line.origin,
+            content: line.content.clone(),
+        })
+        .collect::<Vec<LineMatch>>();
+
+    match matches.is_empty() {
+        true => None,
+        false => Some(FileMatches {
+            diff_index,
+            file_name: diff.file_name(),
+            matches,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_filtered_by_substring_or_glob() {
+        let matches = |query: &str, path: &str| file_filter(query).unwrap().is_match(path);
+        assert!(matches("ui/", "src/ui/mod.rs"));
+        assert!(matches("README", "docs/readme.md"));
+        assert!(!matches("ui/", "src/main.rs"));
+
+        assert!(matches("*.rs", "src/ui/mod.rs"));
+        assert!(matches("src/*/mod.rs", "src/ui/mod.rs"));
+        assert!(!matches("*.rs", "src/main.rs.orig"));
+        assert!(matches("?ain.rs", "main.rs"));
+        // Globs match the whole path, so a directory has to be covered by one.
+        assert!(!matches("mod.rs*", "src/ui/mod.rs"));
+
+        assert!(file_filter("  ").is_none());
+    }
+}
@@ -0,0 +1,188 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{AppData, ControlData, DiffType, Message};
+
+pub const STORAGE_KEY: &str = "session";
+pub const RECENT_STORAGE_KEY: &str = "recent projects";
+
+/// How many recently opened repositories are remembered.
+const MAX_RECENT_PROJECTS: usize = 10;
+
+/// Where the shown changes came from.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum View {
+    WorkingTree(PathBuf),
+    /// An old and a new directory.
+    Directories(PathBuf, PathBuf),
+    /// A commit of a repository, by its id.
+    Commit(PathBuf, String),
+    /// `stash@{index}` of a repository compared to its working tree.
+    Stash(PathBuf, usize),
+    /// Two revisions of a repository, as they were given, old one first, and whether they
+    /// are compared from their merge-base.
+    Revisions(PathBuf, String, String, bool),
+    /// Like [`View::Revisions`], with only the given files compared.
+    FileRevisions(PathBuf, String, String, bool, Vec<String>),
+}
+
+/// What was open when the app was last closed, so it can be opened again on the next launch.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// `None` when nothing was open, or only a pasted patch.
+    pub view: Option<View>,
+    /// Whether the modified, staged or all changes were shown.
+    pub diff_type: DiffType,
+    pub selected_file: Option<String>,
+}
+
+impl Session {
+    pub fn new(app_data: Option<&AppData>, control_data: &ControlData) -> Session {
+        let Some(app_data) = app_data.filter(|app_data| !app_data.pasted) else {
+            return Session::default();
+        };
+
+        let path = PathBuf::from(&app_data.project_path);
+        let view = match (
+            &app_data.compared_directory,
+            &app_data.commit,
+            &app_data.stash,
+            &app_data.revisions,
+        ) {
+            (Some(old_dir), _, _, _) => View::Directories(PathBuf::from(old_dir), path),
+            (None, Some(commit), _, _) => View::Commit(path, commit.id.clone()),
+            (None, None, Some(stash), _) => View::Stash(path, stash.index),
+            (None, None, None, Some(range)) if range.paths.is_empty() => {
+                View::Revisions(path, range.from.clone(), range.to.clone(), range.merge_base)
+            }
+            (None, None, None, Some(range)) => View::FileRevisions(
+                path,
+                range.from.clone(),
+                range.to.clone(),
+                range.merge_base,
+                range.paths.clone(),
+            ),
+            (None, None, None, None) => View::WorkingTree(path),
+        };
+        Session {
+            view: Some(view),
+            diff_type: control_data.diff_type.clone(),
+            selected_file: app_data
+                .diff_data(&control_data.diff_type)
+                .diffs
+                .get(control_data.selected_diff_index)
+                .map(|diff| diff.file_name()),
+        }
+    }
+
+    /// Paths the view needs that don't exist anymore, e.g. of a deleted repository.
+    pub fn missing_paths(&self) -> Vec<PathBuf> {
+        let paths = match &self.view {
+            None => Vec::new(),
+            Some(View::Directories(old_dir, new_dir)) => vec![old_dir, new_dir],
+            Some(
+                View::WorkingTree(path)
+                | View::Commit(path, _)
+                | View::Stash(path, _)
+                | View::Revisions(path, ..)
+                | View::FileRevisions(path, ..),
+            ) => vec![path],
+        };
+        paths
+            .into_iter()
+            .filter(|path| !path.exists())
+            .cloned()
+            .collect()
+    }
+
+    /// The message opening the repository or directories of the view.
+    pub fn open_message(&self) -> Option<Message> {
+        match self.view.clone()? {
+            View::Directories(old_dir, new_dir) => {
+                Some(Message::CompareDirectories(old_dir, new_dir))
+            }
+            View::WorkingTree(path)
+            | View::Commit(path, _)
+            | View::Stash(path, _)
+            | View::Revisions(path, ..)
+            | View::FileRevisions(path, ..) => Some(Message::LoadDiff(path)),
+        }
+    }
+
+    /// The message showing a commit, stash or revisions once its repository is open, as those are
+    /// looked up in the open repository.
+    pub fn follow_up_message(&self) -> Option<Message> {
+        match self.view.clone()? {
+            View::Commit(_, id) => Some(Message::ViewCommit(id)),
+            View::Stash(_, index) => Some(Message::CompareStash(index)),
+            View::Revisions(_, from, to, merge_base) => {
+                Some(Message::CompareRevisions(from, to, merge_base, Vec::new()))
+            }
+            View::FileRevisions(_, from, to, merge_base, paths) => {
+                Some(Message::CompareRevisions(from, to, merge_base, paths))
+            }
+            View::WorkingTree(_) | View::Directories(..) => None,
+        }
+    }
+}
+
+/// Repositories opened before, offered again next to the open button.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentProjects {
+    /// Most recently opened first.
+    pub paths: Vec<String>,
+    /// Paths that failed to open since the app started, offered for removal.
+    #[serde(skip)]
+    pub failed: HashSet<String>,
+    /// The path being opened, to tell which one failed.
+    #[serde(skip)]
+    pub opening: Option<String>,
+}
+
+impl RecentProjects {
+    /// Moves `path` to the front of the list, forgetting the oldest path if it gets too long.
+    pub fn add(&mut self, path: &str) {
+        self.failed.remove(path);
+        self.paths.retain(|recent| recent != path);
+        self.paths.insert(0, path.to_string());
+        self.paths.truncate(MAX_RECENT_PROJECTS);
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.failed.remove(path);
+        self.paths.retain(|recent| recent != path);
+    }
+
+    /// Remembers that the path being opened failed to, if it is in the list.
+    pub fn opening_failed(&mut self) {
+        if let Some(path) = self.opening.take().filter(|path| self.paths.contains(path)) {
+            self.failed.insert(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_projects_are_deduplicated() {
+        let mut recent = RecentProjects::default();
+        for i in 0..12 {
+            recent.add(&format!("/repo{}", i));
+        }
+        recent.add("/repo5");
+        assert_eq!(recent.paths.len(), MAX_RECENT_PROJECTS);
+        assert_eq!(recent.paths[..2], ["/repo5", "/repo11"]);
+        assert_eq!(recent.paths.last().unwrap(), "/repo2");
+
+        recent.opening = Some("/repo11".to_string());
+        recent.opening_failed();
+        assert!(recent.failed.contains("/repo11"));
+        recent.remove("/repo11");
+        assert!(recent.failed.is_empty() && !recent.paths.contains(&"/repo11".to_string()));
+    }
+}
@@ -0,0 +1,543 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use egui::{Color32, Visuals};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::git::{Diff, DiffAlgorithm, DiffSettings, Whitespace};
+
+pub const STORAGE_KEY: &str = "settings";
+
+/// Sizes the diff's font can be zoomed to, in points.
+pub const FONT_SIZES: RangeInclusive<f32> = 8.0..=32.0;
+pub const DEFAULT_FONT_SIZE: f32 = 12.0;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Command used to open the selected file in an external diff tool.
+    /// `{old}` and `{new}` are replaced with the paths of the two sides.
+    pub external_diff_command: String,
+    /// Command used to open a file at a line in an editor. `{path}` and `{line}` are
+    /// replaced with the file's path and the line number; when empty, `$EDITOR` is run.
+    pub editor_command: String,
+    pub density: Density,
+    pub appearance: Appearance,
+    /// Colors of the diff with the dark appearance.
+    pub dark_theme: Theme,
+    /// Colors of the diff with the light appearance.
+    pub light_theme: Theme,
+    /// Size of the font of the diff's columns, in points (zoomed with Ctrl+= and Ctrl+-).
+    pub font_size: f32,
+    /// How lines are matched up between both sides; changing it lists the changes again.
+    pub diff_algorithm: DiffAlgorithm,
+    /// Unchanged lines shown around each change, which also lists the changes again.
+    pub context_lines: u32,
+    /// Which whitespace differences are ignored, which also lists the changes again.
+    pub whitespace: Whitespace,
+    /// List untracked files among the working tree changes, which also lists the changes again.
+    pub include_untracked: bool,
+    /// List the commits from before a file was renamed in its history.
+    pub follow_renames: bool,
+    /// Fold runs of more unchanged lines than this into a band that can be expanded.
+    pub fold_unchanged: Option<usize>,
+    /// Strip the directory shared by all changed files from the file list.
+    pub trim_common_prefix: bool,
+    /// Group the file list by directory, with what changed below each.
+    pub file_tree: bool,
+    pub file_order: FileOrder,
+    /// Allow a second file pane next to the selected file on wide windows.
+    pub multi_pane: bool,
+    /// Whitespace-separated words highlighted in added lines.
+    pub highlight_keywords: String,
+    /// Flag added lines whose indentation doesn't use the expected style.
+    pub indent_check: IndentCheck,
+    /// Leave a gap with a line in it between consecutive hunks.
+    pub hunk_separators: bool,
+    /// Show the function of the hunk at the top of a file pane above its code.
+    pub context_breadcrumb: bool,
+    /// Point out blocks of code moved between files. Every file has to be loaded for it.
+    pub detect_moves: bool,
+    /// Column at which a vertical guide is drawn over the code, if any.
+    pub ruler_column: Option<usize>,
+    /// Show the column of line numbers next to the code (toggled with `L`).
+    pub show_line_numbers: bool,
+    /// Show the column of `+`/`-` origins next to the code (toggled with `O`).
+    pub show_origins: bool,
+    /// Wrap lines too long for the file pane instead of scrolling sideways (toggled with `W`).
+    pub wrap_lines: bool,
+    /// Mark spaces with dots and tabs with arrows in the code.
+    pub show_whitespace: bool,
+    /// Whitespace-separated glob patterns of files left out of the file list and stats,
+    /// e.g. generated lock files.
+    pub ignored_paths: String,
+    /// Name the open repository and branch in the window title, to tell windows apart.
+    pub repository_in_title: bool,
+    /// Overrides of the settings above, keyed by repository path.
+    pub repos: HashMap<String, RepoSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            external_diff_command: "code --diff {old} {new}".to_string(),
+            editor_command: "code -g {path}:{line}".to_string(),
+            density: Density::default(),
+            appearance: Appearance::default(),
+            dark_theme: Theme::dark(),
+            light_theme: Theme::light(),
+            font_size: DEFAULT_FONT_SIZE,
+            diff_algorithm: DiffAlgorithm::default(),
+            context_lines: 3,
+            whitespace: Whitespace::default(),
+            include_untracked: false,
+            follow_renames: true,
+            fold_unchanged: Some(10),
+            trim_common_prefix: false,
+            file_tree: false,
+            file_order: FileOrder::default(),
+            multi_pane: false,
+            highlight_keywords: "TODO FIXME XXX dbg! println!".to_string(),
+            indent_check: IndentCheck::default(),
+            hunk_separators: true,
+            context_breadcrumb: true,
+            detect_moves: false,
+            ruler_column: None,
+            show_line_numbers: true,
+            show_origins: true,
+            wrap_lines: false,
+            show_whitespace: false,
+            repository_in_title: true,
+            ignored_paths: "Cargo.lock package-lock.json yarn.lock pnpm-lock.yaml".to_string(),
+            repos: HashMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// How the changes are computed.
+    pub fn diff_settings(&self) -> DiffSettings {
+        DiffSettings {
+            algorithm: self.diff_algorithm,
+            context_lines: self.context_lines,
+            whitespace: self.whitespace,
+            include_untracked: self.include_untracked,
+        }
+    }
+
+    /// The colors of the diff with the chosen appearance.
+    pub fn theme(&self) -> Theme {
+        match self.appearance {
+            Appearance::Dark => self.dark_theme,
+            Appearance::Light => self.light_theme,
+        }
+    }
+
+    pub fn theme_mut(&mut self) -> &mut Theme {
+        match self.appearance {
+            Appearance::Dark => &mut self.dark_theme,
+            Appearance::Light => &mut self.light_theme,
+        }
+    }
+
+    /// These settings with the diff's font `step` points larger, within [`FONT_SIZES`].
+    pub fn zoomed(&self, step: f32) -> Settings {
+        Settings {
+            font_size: (self.font_size + step).clamp(*FONT_SIZES.start(), *FONT_SIZES.end()),
+            ..self.clone()
+        }
+    }
+
+    /// These settings with the overrides of the repository at `path` applied.
+    pub fn for_repo(&self, path: &str) -> Settings {
+        let mut settings = self.clone();
+        if let Some(overrides) = self.repos.get(path) {
+            overrides.apply(&mut settings);
+        }
+
+        settings
+    }
+
+    pub fn ignored_paths(&self) -> IgnoredPaths {
+        IgnoredPaths {
+            patterns: self
+                .ignored_paths
+                .split_whitespace()
+                .map(glob_regex)
+                .collect(),
+        }
+    }
+}
+
+/// Compiled patterns of [`Settings::ignored_paths`].
+pub struct IgnoredPaths {
+    patterns: Vec<Regex>,
+}
+
+impl IgnoredPaths {
+    /// Whether `path`, relative to the repository root, matches any of the patterns.
+    pub fn matches(&self, path: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.is_match(path))
+    }
+}
+
+/// Translates a glob to a regex the way `.gitignore` reads it: `*` and `?` stay within a
+/// directory, `**` crosses them, and a pattern without a `/` matches in any directory.
+fn glob_regex(glob: &str) -> Regex {
+    let anchored = glob.trim_end_matches('/').contains('/');
+    let mut pattern = match anchored {
+        true => "^".to_string(),
+        false => "^(?:.*/)?".to_string(),
+    };
+
+    let mut chars = glob.trim_start_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                match chars.peek() {
+                    Some('/') => {
+                        chars.next();
+                        pattern.push_str("(?:.*/)?");
+                    }
+                    _ => pattern.push_str(".*"),
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    // Directories match everything below them.
+    pattern.push_str("(?:/.*)?$");
+
+    Regex::new(&pattern).expect("Escaped glob should be a valid regex")
+}
+
+/// Settings that follow a project's conventions, so one repository can differ from the defaults.
+///
+/// Unset values fall back to the global settings.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepoSettings {
+    pub highlight_keywords: Option<String>,
+    pub indent_check: Option<IndentCheck>,
+    pub ruler_column: Option<Option<usize>>,
+    pub ignored_paths: Option<String>,
+}
+
+impl RepoSettings {
+    fn apply(&self, settings: &mut Settings) {
+        if let Some(keywords) = &self.highlight_keywords {
+            settings.highlight_keywords = keywords.clone();
+        }
+        if let Some(indent_check) = self.indent_check {
+            settings.indent_check = indent_check;
+        }
+        if let Some(ruler_column) = self.ruler_column {
+            settings.ruler_column = ruler_column;
+        }
+        if let Some(ignored_paths) = &self.ignored_paths {
+            settings.ignored_paths = ignored_paths.clone();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == RepoSettings::default()
+    }
+}
+
+/// Whether the app is light on dark or dark on light.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Appearance {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Appearance {
+    pub fn label_text(&self) -> String {
+        match self {
+            Appearance::Dark => "Dark".to_string(),
+            Appearance::Light => "Light".to_string(),
+        }
+    }
+
+    pub fn visuals(&self) -> Visuals {
+        match self {
+            Appearance::Dark => Visuals::dark(),
+            Appearance::Light => Visuals::light(),
+        }
+    }
+}
+
+/// Colors of the lines of a diff, which every view of it is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Theme {
+    pub insertion: Color32,
+    pub deletion: Color32,
+    /// Unchanged lines.
+    pub context: Color32,
+    /// Hunk headers.
+    pub header: Color32,
+    /// Whether the `+` and `-` origins are set off with a tint of their color, for when the
+    /// colors alone are hard to tell apart.
+    #[serde(default)]
+    pub emphasized_origins: bool,
+    /// Keywords and the function a hunk is in.
+    #[serde(default = "Theme::default_highlight")]
+    pub highlight: Color32,
+}
+
+impl Theme {
+    pub fn dark() -> Theme {
+        Theme {
+            insertion: Color32::GREEN,
+            deletion: Color32::RED,
+            context: Color32::WHITE,
+            header: Color32::from_rgb(7, 138, 171),
+            emphasized_origins: false,
+            highlight: Color32::YELLOW,
+        }
+    }
+
+    pub fn light() -> Theme {
+        Theme {
+            insertion: Color32::from_rgb(0, 130, 40),
+            deletion: Color32::from_rgb(190, 20, 20),
+            context: Color32::from_gray(30),
+            header: Color32::from_rgb(0, 100, 150),
+            emphasized_origins: false,
+            highlight: Color32::from_rgb(150, 100, 0),
+        }
+    }
+
+    /// A gold that reads on either background, for themes saved before `highlight` was added.
+    fn default_highlight() -> Color32 {
+        Color32::from_rgb(200, 140, 0)
+    }
+
+    /// Background of the words that changed within an added line, a tint of its color.
+    pub fn inserted_words_background(&self) -> Color32 {
+        self.insertion.linear_multiply(0.3)
+    }
+
+    /// Background of the words that changed within a removed line.
+    pub fn deleted_words_background(&self) -> Color32 {
+        self.deletion.linear_multiply(0.35)
+    }
+
+    /// Background of the function a hunk is in, set off from the header before it.
+    pub fn hunk_context_background(&self) -> Color32 {
+        self.header.linear_multiply(0.12)
+    }
+
+    /// Background of an indent that doesn't match the rest of the file.
+    pub fn indent_warning_background(&self) -> Color32 {
+        self.highlight.linear_multiply(0.3)
+    }
+
+    /// Background of the part of an added line past the column guide.
+    pub fn overflow_background(&self) -> Color32 {
+        self.deletion.linear_multiply(0.2)
+    }
+
+    /// Background of the matches of the search query.
+    pub fn search_match_background(&self) -> Color32 {
+        self.header.linear_multiply(0.4)
+    }
+
+    /// Background of the side of a split row without a line, e.g. next to an added line.
+    pub fn placeholder_background(&self) -> Color32 {
+        self.context.linear_multiply(0.04)
+    }
+}
+
+/// The built-in sets of diff colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Palette {
+    Standard,
+    /// Blue and orange instead of green and red, which look alike with red-green color
+    /// blindness.
+    Deuteranopia,
+}
+
+impl Palette {
+    pub fn label_text(&self) -> String {
+        match self {
+            Palette::Standard => "Standard".to_string(),
+            Palette::Deuteranopia => "Deuteranopia".to_string(),
+        }
+    }
+
+    pub fn theme(&self, appearance: Appearance) -> Theme {
+        let standard = match appearance {
+            Appearance::Dark => Theme::dark(),
+            Appearance::Light => Theme::light(),
+        };
+        match (self, appearance) {
+            (Palette::Standard, _) => standard,
+            (Palette::Deuteranopia, Appearance::Dark) => Theme {
+                insertion: Color32::from_rgb(90, 170, 255),
+                deletion: Color32::from_rgb(255, 150, 40),
+                emphasized_origins: true,
+                ..standard
+            },
+            (Palette::Deuteranopia, Appearance::Light) => Theme {
+                insertion: Color32::from_rgb(0, 90, 200),
+                deletion: Color32::from_rgb(190, 95, 0),
+                emphasized_origins: true,
+                ..standard
+            },
+        }
+    }
+
+    /// The palette `theme` is for `appearance`, unless its colors were changed.
+    pub fn of(theme: Theme, appearance: Appearance) -> Option<Palette> {
+        [Palette::Standard, Palette::Deuteranopia]
+            .into_iter()
+            .find(|palette| palette.theme(appearance) == theme)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Density {
+    Compact,
+    #[default]
+    Comfortable,
+}
+
+impl Density {
+    pub fn label_text(&self) -> String {
+        match self {
+            Density::Compact => "Compact".to_string(),
+            Density::Comfortable => "Comfortable".to_string(),
+        }
+    }
+}
+
+/// How the file list is ordered. Only the list is, the files keep their indices.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum FileOrder {
+    /// As git lists them.
+    #[default]
+    Path,
+    /// By the name of the file, whatever directory it is in.
+    Name,
+    /// Files with the most added and removed lines first.
+    Changes,
+}
+
+impl FileOrder {
+    pub fn label_text(&self) -> String {
+        match self {
+            FileOrder::Path => "Path".to_string(),
+            FileOrder::Name => "Name A–Z".to_string(),
+            FileOrder::Changes => "Most changes".to_string(),
+        }
+    }
+
+    /// Indices of `diffs` in this order. Files that compare equal stay in the order git
+    /// lists them.
+    pub fn indices(&self, diffs: &[Diff]) -> Vec<usize> {
+        let mut indices = (0..diffs.len()).collect::<Vec<_>>();
+        match self {
+            FileOrder::Path => {}
+            FileOrder::Name => indices.sort_by_cached_key(|&i| {
+                let path = diffs[i].file_name();
+                let name = path.rsplit('/').next().unwrap_or_default();
+                name.to_lowercase()
+            }),
+            FileOrder::Changes => indices
+                .sort_by_key(|&i| std::cmp::Reverse(diffs[i].insertions() + diffs[i].deletions())),
+        }
+        indices
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum IndentCheck {
+    #[default]
+    Off,
+    Tabs,
+    Spaces,
+}
+
+impl IndentCheck {
+    pub fn label_text(&self) -> String {
+        match self {
+            IndentCheck::Off => "Off".to_string(),
+            IndentCheck::Tabs => "Tabs".to_string(),
+            IndentCheck::Spaces => "Spaces".to_string(),
+        }
+    }
+
+    /// Length of `line`'s indentation if it contains the wrong kind of whitespace.
+    pub fn mismatched_indent(&self, line: &str) -> Option<usize> {
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        let mismatched = match self {
+            IndentCheck::Off => false,
+            IndentCheck::Tabs => indent.contains(' '),
+            IndentCheck::Spaces => indent.contains('\t'),
+        };
+
+        mismatched.then_some(indent.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch;
+
+    #[test]
+    fn files_are_ordered_without_moving_them() {
+        let (diffs, _) = patch::parse(
+            "--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,1 +1,1 @@\n-a\n+b\n\
+             --- a/src/Cargo.toml\n+++ b/src/Cargo.toml\n@@ -1,1 +1,3 @@\n a\n+b\n+c\n\
+             --- a/README.md\n+++ b/README.md\n@@ -1,1 +1,4 @@\n a\n+b\n+c\n+d\n",
+        )
+        .unwrap();
+
+        assert_eq!(FileOrder::Path.indices(&diffs), [0, 1, 2]);
+        assert_eq!(FileOrder::Name.indices(&diffs), [1, 0, 2]);
+        // Both files in src change two lines, so they stay in the order they were listed.
+        assert_eq!(FileOrder::Changes.indices(&diffs), [2, 0, 1]);
+    }
+
+    #[test]
+    fn globs_match_like_gitignore() {
+        let settings = Settings {
+            ignored_paths: "Cargo.lock *.min.js docs/**/*.svg /vendor".to_string(),
+            ..Settings::default()
+        };
+        let ignored = settings.ignored_paths();
+
+        assert!(ignored.matches("Cargo.lock"));
+        assert!(ignored.matches("crates/core/Cargo.lock"));
+        assert!(ignored.matches("web/app.min.js"));
+        assert!(ignored.matches("docs/logo.svg"));
+        assert!(ignored.matches("docs/img/logo.svg"));
+        assert!(ignored.matches("vendor/lib/a.c"));
+        assert!(!ignored.matches("src/vendor/a.c"));
+        assert!(!ignored.matches("Cargo.lock.bak"));
+        assert!(!ignored.matches("img/logo.svg"));
+    }
+
+    #[test]
+    fn colors_are_kept_per_appearance() {
+        let mut settings = Settings::default();
+        settings.theme_mut().insertion = Color32::BLUE;
+        settings.appearance = Appearance::Light;
+        assert_eq!(settings.theme(), Theme::light());
+        settings.appearance = Appearance::Dark;
+        assert_eq!(settings.theme().insertion, Color32::BLUE);
+        assert_eq!(settings.theme().deletion, Theme::dark().deletion);
+        assert_eq!(Palette::of(settings.theme(), Appearance::Dark), None);
+
+        *settings.theme_mut() = Palette::Deuteranopia.theme(Appearance::Dark);
+        let palette = Palette::of(settings.theme(), Appearance::Dark);
+        assert_eq!(palette, Some(Palette::Deuteranopia));
+        assert!(settings.theme().emphasized_origins);
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::mpsc::Sender;
+
+use egui::{Context, RichText, Window};
+
+use crate::data::Message;
+
+pub struct AboutWindow {
+    sender: Sender<Message>,
+    last_error: Option<String>,
+}
+
+impl AboutWindow {
+    pub fn new(sender: Sender<Message>, last_error: Option<String>) -> AboutWindow {
+        AboutWindow { sender, last_error }
+    }
+}
+
+impl AboutWindow {
+    pub fn show(&self, ctx: &Context) {
+        puffin::profile_function!("AboutWindow");
+        let mut open = true;
+
+        Window::new("About")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading(RichText::new("Contrast").strong());
+                ui.label(env!("CARGO_PKG_DESCRIPTION"));
+                ui.separator();
+                for line in version_info() {
+                    ui.label(line);
+                }
+
+                ui.separator();
+                if ui.button("Copy diagnostics").clicked() {
+                    ui.output_mut(|o| o.copied_text = self.diagnostics());
+                }
+            });
+
+        if !open {
+            self.sender
+                .send(Message::ToggleAbout)
+                .expect("Channel closed unexpectedly!");
+        }
+    }
+
+    /// Version info plus the last error, for pasting into bug reports.
+    fn diagnostics(&self) -> String {
+        let mut lines = version_info();
+        lines.push(format!(
+            "OS: {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ));
+        lines.push(format!(
+            "Last error: {}",
+            self.last_error.as_deref().unwrap_or("none")
+        ));
+        lines.join("\n")
+    }
+}
+
+fn version_info() -> Vec<String> {
+    let libgit2 = git2::Version::get();
+    let (major, minor, rev) = libgit2.libgit2_version();
+
+    vec![
+        format!("Version: {}", env!("CARGO_PKG_VERSION")),
+        format!("Built: {}", env!("CONTRAST_BUILD_DATE")),
+        format!("git2: {}", libgit2.crate_version()),
+        format!(
+            "libgit2: {}.{}.{}{}",
+            major,
+            minor,
+            rev,
+            match libgit2.vendored() {
+                true => " (vendored)",
+                false => "",
+            }
+        ),
+    ]
+}
@@ -1,166 +1,533 @@
-use std::ops::Range;
+use std::{
+    hash::{Hash, Hasher},
+    ops::{Range, RangeInclusive},
+};
 
 use egui::{
-    text::LayoutJob,
+    text::{LayoutJob, TAB_SIZE},
     util::cache::{ComputerMut, FrameCache},
-    Color32, Context, FontFamily, FontId, Layout, Response, TextEdit, TextFormat, Ui, Widget,
+    Color32, Context, Event, FontId, Layout, Response, Stroke, TextEdit, TextFormat, Ui, Widget,
 };
 
-use crate::git::Diff;
+use crate::{
+    git::{self, Diff, LineKind, Permalink},
+    search::SearchQuery,
+    settings::{IndentCheck, Settings, Theme},
+    ui::metrics::DiffMetrics,
+    wrap::WrappedRows,
+};
 
 pub struct CodeWidget {
     diff: Diff,
     range: Range<usize>,
+    metrics: DiffMetrics,
+    settings: Settings,
+    search: SearchQuery,
+    permalink: Option<Permalink>,
+    /// How the rows are wrapped and the gaps left between them, which the code is laid out
+    /// to match.
+    wrapped: WrappedRows,
 }
 
 impl CodeWidget {
-    pub fn new(diff: Diff, range: Range<usize>) -> CodeWidget {
-        CodeWidget { diff, range }
+    pub fn new(
+        diff: Diff,
+        range: Range<usize>,
+        metrics: DiffMetrics,
+        settings: Settings,
+        search: SearchQuery,
+        permalink: Option<Permalink>,
+        wrapped: WrappedRows,
+    ) -> CodeWidget {
+        CodeWidget {
+            diff,
+            range,
+            metrics,
+            settings,
+            search,
+            permalink,
+            wrapped,
+        }
     }
 }
+/// Where a line to open in the editor is put; see [`crate::ui::take_editor_request`].
+pub fn editor_request_id() -> egui::Id {
+    egui::Id::new("editor request")
+}
+
+/// Where a line to blame is put for the app to pick up; see [`crate::ui::take_blame_request`].
+pub fn blame_request_id() -> egui::Id {
+    egui::Id::new("blame request")
+}
+
 impl Widget for CodeWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         puffin::profile_function!("CodeWidget");
 
+        let font_id = self.metrics.font_id();
+        let glyph_width = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+
+        // Each gap between the rows shown is a blank line of the text.
+        let lines = self.diff.content.lines().collect::<Vec<&str>>();
+        let Range { start, end } = self.range;
+        let end = std::cmp::min(end, lines.len());
+        let mut shown = Vec::new();
+        let mut gap_lines = Vec::new();
+        for (row, &line) in lines.iter().enumerate().take(end).skip(start) {
+            if row > start && self.wrapped.gaps().binary_search(&row).is_ok() {
+                gap_lines.push(shown.len());
+                shown.push("");
+            }
+            shown.push(line);
+        }
+        let content = &shown.join("\n");
+        let row_of = |paragraph: usize| {
+            start + paragraph - gap_lines.partition_point(|&line| line < paragraph)
+        };
+
         let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
-            let layout_job: egui::text::LayoutJob = highlight(
+            let mut layout_job: egui::text::LayoutJob = highlight(
                 ui.ctx(),
-                string,
-                self.range.start,
-                &self.diff.header_indices,
-                &self.diff.insertion_indices,
-                &self.diff.deletion_indices,
-                &self.diff.neutral_indices,
+                HighlightParams {
+                    text: string,
+                    offset: self.range.start,
+                    gap_lines: &gap_lines,
+                    header_indices: &self.diff.header_indices,
+                    insertion_indices: &self.diff.insertion_indices,
+                    deletion_indices: &self.diff.deletion_indices,
+                    neutral_indices: &self.diff.neutral_indices,
+                    changed_spans: &self.diff.changed_spans,
+                    keywords: &self.settings.highlight_keywords,
+                    indent_check: self.settings.indent_check,
+                    ruler_column: self.settings.ruler_column,
+                    search: &self.search,
+                    font_id: &font_id,
+                    theme: self.settings.theme(),
+                    whitespace_marks: (self.settings.show_whitespace)
+                        .then_some(WhitespaceMarks { glyph_width }),
+                },
             );
+            // Breaking anywhere after a whole number of glyphs makes the rows line up with
+            // the wrapped rows the other columns are padded to.
+            if let Some(columns) = self.wrapped.columns() {
+                layout_job.wrap.max_width = columns as f32 * glyph_width + glyph_width / 2.0;
+                layout_job.wrap.break_anywhere = true;
+            }
             ui.fonts(|f| f.layout_job(layout_job))
         };
 
-        let lines = self.diff.content.lines().collect::<Vec<&str>>();
-        let Range { start, end } = self.range;
-        let end = std::cmp::min(end, lines.len());
-        let content = &lines[start..end].join("\n");
-
         ui.with_layout(Layout::left_to_right(egui::Align::Min), |ui| {
             puffin::profile_function!("ui.with_layout");
-            ui.add(
-                TextEdit::multiline(&mut content.as_str())
-                    .desired_width(f32::INFINITY)
-                    .frame(false)
-                    .code_editor()
-                    .layouter(&mut layouter),
-            );
+            let output = TextEdit::multiline(&mut content.as_str())
+                .desired_width(f32::INFINITY)
+                .frame(false)
+                .code_editor()
+                .margin(self.metrics.margin)
+                .layouter(&mut layouter)
+                .show(ui);
+            let response = output.response;
+            let selection = output
+                .cursor_range
+                .filter(|range| !range.is_empty())
+                .map(|range| {
+                    let [first, last] = range.sorted_cursors();
+                    row_of(first.pcursor.paragraph)..=row_of(last.pcursor.paragraph)
+                });
+
+            // Copying the selection copies the lines it touches, like the menu does, rather
+            // than the hunk headers and the padding shown with them.
+            if let Some(rows) = selection.clone() {
+                if response.has_focus() && ui.input(|i| i.events.contains(&Event::Copy)) {
+                    let text = self.diff.copied_text(rows, false);
+                    ui.output_mut(|o| o.copied_text = text);
+                }
+            }
+
+            // The rows a reference is copied for are picked when the menu is opened:
+            // the selected ones, or otherwise the one that was right-clicked.
+            let reference_rows = response.id.with("reference rows");
+            if response.secondary_clicked() {
+                let clicked = response.interact_pointer_pos().map(|pos| {
+                    let paragraph = output
+                        .galley
+                        .cursor_from_pos(pos - output.text_draw_pos)
+                        .pcursor
+                        .paragraph;
+                    row_of(paragraph)..=row_of(paragraph)
+                });
+                if let Some(rows) = selection.or(clicked) {
+                    ui.data_mut(|d| d.insert_temp(reference_rows, rows));
+                }
+            }
+            response.clone().context_menu(|ui| {
+                let rows = ui
+                    .data(|d| d.get_temp::<RangeInclusive<usize>>(reference_rows))
+                    .unwrap_or(start..=start);
+                let path = self.diff.file_name();
+                let shown_lines = self.diff.lines_in(rows.clone());
+                let copy_label = match shown_lines.len() {
+                    1 => "Copy line",
+                    _ => "Copy selected lines",
+                };
+                if ui
+                    .button(copy_label)
+                    .on_hover_text("Without the + and - in front of them")
+                    .clicked()
+                {
+                    let text = self.diff.copied_text(rows.clone(), false);
+                    ui.output_mut(|o| o.copied_text = text);
+                    ui.close_menu();
+                }
+                if ui
+                    .button("Copy as patch")
+                    .on_hover_text("With the + and - in front of the lines")
+                    .clicked()
+                {
+                    let text = self.diff.copied_text(rows.clone(), true);
+                    ui.output_mut(|o| o.copied_text = text);
+                    ui.close_menu();
+                }
+                if ui.button("Copy with line numbers").clicked() {
+                    let text = shown_lines
+                        .iter()
+                        .map(|line| format!("{}: {}", line.display_lineno(), line.content))
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    ui.output_mut(|o| o.copied_text = text);
+                    ui.close_menu();
+                }
+                // Unchanged lines are in the history as they are; the others are this change.
+                if let [line] = shown_lines.as_slice() {
+                    if let (LineKind::Context, Some(line_no)) = (line.kind(), line.old_lineno) {
+                        if ui
+                            .button("Blame this line")
+                            .on_hover_text("Show the commit that last changed this line")
+                            .clicked()
+                        {
+                            ui.data_mut(|d| {
+                                d.insert_temp(blame_request_id(), (self.diff.clone(), line_no))
+                            });
+                            ui.close_menu();
+                        }
+                    }
+                }
+                if let Some(line_no) = self.diff.new_line_near(*rows.start()) {
+                    if ui
+                        .button("Open in editor")
+                        .on_hover_text("Open the file at this line in the editor set in Settings")
+                        .clicked()
+                    {
+                        ui.data_mut(|d| {
+                            d.insert_temp(editor_request_id(), (self.diff.clone(), line_no))
+                        });
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+
+                let lines = self.diff.line_numbers(rows);
+
+                if ui.button("Copy reference").clicked() {
+                    let reference = match &lines {
+                        Some(lines) if lines.start() == lines.end() => {
+                            format!("{}:{}", path, lines.start())
+                        }
+                        Some(lines) => format!("{}:{}-{}", path, lines.start(), lines.end()),
+                        None => path.clone(),
+                    };
+                    ui.output_mut(|o| o.copied_text = reference);
+                    ui.close_menu();
+                }
+                if let (Some(permalink), Some(lines)) = (&self.permalink, lines) {
+                    if ui
+                        .button("Copy link")
+                        .on_hover_text("Link to these lines as of the commit shown")
+                        .clicked()
+                    {
+                        ui.output_mut(|o| o.copied_text = permalink.url(&path, lines));
+                        ui.close_menu();
+                    }
+                }
+            });
+
+            if let Some(column) = self.settings.ruler_column {
+                let x = response.rect.left() + self.metrics.margin.x + column as f32 * glyph_width;
+                ui.painter().vline(
+                    x,
+                    response.rect.y_range(),
+                    ui.visuals().widgets.noninteractive.bg_stroke,
+                );
+            }
         })
         .response
     }
 }
 
+/// Color of the dots and arrows that mark spaces and tabs, faint enough not to stand out.
+const WHITESPACE_MARK_COLOR: Color32 = Color32::from_gray(85);
+
 type HighlightCache = FrameCache<LayoutJob, LayoutHandler>;
 
-fn highlight(
-    ctx: &Context,
-    text: &str,
+#[derive(Clone, Copy, Hash)]
+struct HighlightParams<'a> {
+    text: &'a str,
     offset: usize,
-    header_indices: &Vec<usize>,
-    insertion_indices: &Vec<usize>,
-    deletion_indices: &Vec<usize>,
-    neutral_indices: &Vec<usize>,
-) -> LayoutJob {
-    impl
-        ComputerMut<
-            (
-                &str,
-                usize,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
-            ),
-            LayoutJob,
-        > for LayoutHandler
-    {
-        fn compute(
-            &mut self,
-            (text, offset, header_indices, insertion_indices, deletion_indices, neutral_indices): (
-                &str,
-                usize,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
-                &Vec<usize>,
-            ),
-        ) -> LayoutJob {
-            puffin::profile_function!();
-            LayoutHandler::layout_job(
-                text,
-                offset,
-                header_indices,
-                insertion_indices,
-                deletion_indices,
-                neutral_indices,
-            )
-        }
+    /// Lines of `text` that are blank gaps rather than rows.
+    gap_lines: &'a [usize],
+    header_indices: &'a [usize],
+    insertion_indices: &'a [usize],
+    deletion_indices: &'a [usize],
+    neutral_indices: &'a [usize],
+    changed_spans: &'a [(usize, Vec<Range<usize>>)],
+    keywords: &'a str,
+    indent_check: IndentCheck,
+    ruler_column: Option<usize>,
+    search: &'a SearchQuery,
+    font_id: &'a FontId,
+    theme: Theme,
+    whitespace_marks: Option<WhitespaceMarks>,
+}
+
+/// Spaces are shown as `·` and tabs as `→`, followed by a gap for the rest of the tab's width,
+/// so that neither moves the text after them.
+#[derive(Clone, Copy)]
+pub(super) struct WhitespaceMarks {
+    glyph_width: f32,
+}
+
+impl Hash for WhitespaceMarks {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.glyph_width.to_bits().hash(state);
     }
+}
 
-    ctx.memory_mut(|mem| {
-        mem.caches.cache::<HighlightCache>().get((
-            text,
-            offset,
-            header_indices,
-            insertion_indices,
-            deletion_indices,
-            neutral_indices,
-        ))
-    })
+fn highlight(ctx: &Context, params: HighlightParams) -> LayoutJob {
+    ctx.memory_mut(|mem| mem.caches.cache::<HighlightCache>().get(params))
+}
+
+impl ComputerMut<HighlightParams<'_>, LayoutJob> for LayoutHandler {
+    fn compute(&mut self, params: HighlightParams) -> LayoutJob {
+        puffin::profile_function!();
+        LayoutHandler::layout_job(params)
+    }
 }
 
 #[derive(Debug, Default)]
 struct LayoutHandler {}
 
 impl LayoutHandler {
-    fn layout_job(
-        text: &str,
-        offset: usize,
-        header_indices: &[usize],
-        insertion_indices: &[usize],
-        deletion_indices: &[usize],
-        neutral_indices: &[usize],
-    ) -> LayoutJob {
+    fn layout_job(params: HighlightParams) -> LayoutJob {
         puffin::profile_function!();
 
+        let HighlightParams {
+            text,
+            offset,
+            gap_lines,
+            header_indices,
+            insertion_indices,
+            deletion_indices,
+            neutral_indices,
+            changed_spans,
+            keywords,
+            indent_check,
+            ruler_column,
+            search,
+            font_id,
+            theme,
+            whitespace_marks,
+        } = params;
+
         let mut job = LayoutJob::default();
         job.wrap.max_width = f32::INFINITY;
 
-        let header_format = TextFormat::simple(
-            FontId::new(12.0, FontFamily::Monospace),
-            Color32::from_rgb(7, 138, 171),
-        );
-        let insertion_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::GREEN);
-        let deletion_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::RED);
-        let neutral_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::WHITE);
+        let header_format = TextFormat::simple(font_id.clone(), theme.header);
+        // The function a hunk is in is what the eye looks for when scanning the headers.
+        let context_format = TextFormat {
+            background: theme.hunk_context_background(),
+            ..TextFormat::simple(font_id.clone(), theme.highlight)
+        };
+        let insertion_format = TextFormat::simple(font_id.clone(), theme.insertion);
+        let keyword_format = TextFormat {
+            underline: Stroke::new(1.0, theme.highlight),
+            ..TextFormat::simple(font_id.clone(), theme.highlight)
+        };
+        let indent_warning_format = TextFormat {
+            background: theme.indent_warning_background(),
+            ..insertion_format.clone()
+        };
+        let deletion_format = TextFormat::simple(font_id.clone(), theme.deletion);
+        let neutral_format = TextFormat::simple(font_id.clone(), theme.context);
+
+        let keywords = keywords.split_whitespace().collect::<Vec<&str>>();
+        let search_matcher = search.matcher().ok().flatten();
 
+        let mut gaps = 0;
         for (i, line) in text.lines().enumerate() {
-            if header_indices.contains(&(i + offset)) {
-                let green_part = line.split(' ').take(4).collect::<Vec<&str>>().join(" ");
-                let white_part = line.split(' ').skip(4).collect::<Vec<&str>>().join(" ");
-                job.append(&green_part, 0.0, header_format.clone());
-                job.append(" ", 0.0, neutral_format.clone());
-                job.append(&white_part, 0.0, neutral_format.clone());
+            if gap_lines.contains(&i) {
+                job.append("\n", 0.0, neutral_format.clone());
+                gaps += 1;
+                continue;
+            }
+            let row = i - gaps + offset;
+            if header_indices.contains(&row) {
+                let (ranges, context) = git::split_hunk_header(line);
+                job.append(ranges, 0.0, header_format.clone());
+                if !context.is_empty() {
+                    job.append(" ", 0.0, neutral_format.clone());
+                    job.append(context, 0.0, context_format.clone());
+                }
                 job.append("\n", 0.0, neutral_format.clone());
             }
-            if insertion_indices.contains(&(i + offset)) {
-                job.append(format!("{line}\n").as_str(), 0.0, insertion_format.clone());
+            let changed = match changed_spans.binary_search_by_key(&row, |(row, _)| *row) {
+                Ok(k) => changed_spans[k].1.as_slice(),
+                Err(_) => &[],
+            };
+            let backgrounds = search_matcher
+                .iter()
+                .flat_map(|matcher| matcher.find_iter(line))
+                .map(|found| (found.range(), theme.search_match_background()))
+                .collect::<Vec<(Range<usize>, Color32)>>();
+
+            if insertion_indices.contains(&row) {
+                let mut segments = Vec::new();
+                let mut position = 0;
+                if let Some(indent) = indent_check.mismatched_indent(line) {
+                    segments.push((0..indent, indent_warning_format.clone()));
+                    position = indent;
+                }
+                for range in keyword_ranges(line, &keywords) {
+                    segments.push((position..range.start, insertion_format.clone()));
+                    segments.push((range.clone(), keyword_format.clone()));
+                    position = range.end;
+                }
+                segments.push((position..line.len(), insertion_format.clone()));
+
+                let overflow = ruler_column
+                    .and_then(|column| line.char_indices().nth(column))
+                    .map(|(overflow, _)| (overflow..line.len(), theme.overflow_background()));
+                let changed = changed
+                    .iter()
+                    .map(|range| (range.clone(), theme.inserted_words_background()));
+                let backgrounds = changed
+                    .chain(overflow)
+                    .chain(backgrounds.iter().cloned())
+                    .collect::<Vec<(Range<usize>, Color32)>>();
+
+                append_line(&mut job, line, &segments, &backgrounds, whitespace_marks);
+                job.append("\n", 0.0, insertion_format.clone());
             }
-            if deletion_indices.contains(&(i + offset)) {
-                job.append(format!("{line}\n").as_str(), 0.0, deletion_format.clone());
+            if deletion_indices.contains(&row) {
+                let segments = [(0..line.len(), deletion_format.clone())];
+                let backgrounds = changed
+                    .iter()
+                    .map(|range| (range.clone(), theme.deleted_words_background()))
+                    .chain(backgrounds.iter().cloned())
+                    .collect::<Vec<(Range<usize>, Color32)>>();
+                append_line(&mut job, line, &segments, &backgrounds, whitespace_marks);
+                job.append("\n", 0.0, deletion_format.clone());
             }
-            if neutral_indices.contains(&(i + offset)) {
-                job.append(format!("{line}\n").as_str(), 0.0, neutral_format.clone());
+            if neutral_indices.contains(&row) {
+                let segments = [(0..line.len(), neutral_format.clone())];
+                append_line(&mut job, line, &segments, &backgrounds, whitespace_marks);
+                job.append("\n", 0.0, neutral_format.clone());
             }
         }
 
         job
     }
 }
+
+/// Appends `line` split into formatted `segments`, with `backgrounds` painted over them.
+///
+/// The segments must cover the whole line. Later backgrounds win where they overlap.
+pub(super) fn append_line(
+    job: &mut LayoutJob,
+    line: &str,
+    segments: &[(Range<usize>, TextFormat)],
+    backgrounds: &[(Range<usize>, Color32)],
+    whitespace_marks: Option<WhitespaceMarks>,
+) {
+    let mut bounds = segments
+        .iter()
+        .map(|(range, _)| range)
+        .chain(backgrounds.iter().map(|(range, _)| range))
+        .flat_map(|range| [range.start, range.end])
+        .collect::<Vec<usize>>();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    for pair in bounds.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let contains = |range: &Range<usize>| range.start <= start && start < range.end;
+        let Some((_, format)) = segments.iter().find(|(range, _)| contains(range)) else {
+            continue;
+        };
+
+        let mut format = format.clone();
+        if let Some((_, color)) = backgrounds.iter().rev().find(|(range, _)| contains(range)) {
+            format.background = *color;
+        }
+        match whitespace_marks {
+            Some(marks) => append_marked(job, &line[start..end], format, marks),
+            None => job.append(&line[start..end], 0.0, format),
+        }
+    }
+}
+
+/// Appends `text` with its spaces and tabs replaced by their marks. The marks keep the number
+/// of characters, so the cursor still lines up with the text.
+fn append_marked(job: &mut LayoutJob, text: &str, format: TextFormat, marks: WhitespaceMarks) {
+    let mark_format = TextFormat {
+        color: WHITESPACE_MARK_COLOR,
+        ..format.clone()
+    };
+    // The gap after a tab's arrow is left before the text that follows it.
+    let tab_gap = (TAB_SIZE - 1) as f32 * marks.glyph_width;
+    let mut leading_space = 0.0;
+    let mut run_start = 0;
+    for (i, c) in text.char_indices() {
+        let mark = match c {
+            ' ' => "·",
+            '\t' => "→",
+            _ => continue,
+        };
+        if run_start < i {
+            job.append(&text[run_start..i], leading_space, format.clone());
+            leading_space = 0.0;
+        }
+        job.append(mark, leading_space, mark_format.clone());
+        leading_space = match c {
+            '\t' => tab_gap,
+            _ => 0.0,
+        };
+        run_start = i + c.len_utf8();
+    }
+    if run_start < text.len() {
+        job.append(&text[run_start..], leading_space, format);
+    } else if leading_space > 0.0 {
+        // Keeps the gap when the next segment of the line follows.
+        job.append("", leading_space, format);
+    }
+}
+
+/// Sorted, non-overlapping byte ranges of every keyword occurrence in `line`.
+fn keyword_ranges(line: &str, keywords: &[&str]) -> Vec<Range<usize>> {
+    let mut ranges = keywords
+        .iter()
+        .flat_map(|keyword| {
+            line.match_indices(keyword)
+                .map(|(start, keyword)| start..start + keyword.len())
+        })
+        .collect::<Vec<Range<usize>>>();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
@@ -0,0 +1,55 @@
+use egui::{Align2, Area, Context, Frame, RichText};
+
+use crate::data::AppData;
+
+pub struct DebugOverlay {
+    app_data: Option<AppData>,
+}
+
+impl DebugOverlay {
+    pub fn new(app_data: Option<AppData>) -> DebugOverlay {
+        DebugOverlay { app_data }
+    }
+}
+
+impl DebugOverlay {
+    pub fn show(&self, ctx: &Context) {
+        puffin::profile_function!("DebugOverlay");
+        let frame_time = ctx.input(|i| i.unstable_dt);
+
+        Area::new("debug overlay")
+            .anchor(Align2::RIGHT_BOTTOM, [-10.0, -10.0])
+            .interactable(false)
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    let mut lines = vec![format!("Frame time: {:.1} ms", frame_time * 1000.0)];
+
+                    match &self.app_data {
+                        Some(app_data) => {
+                            lines.push(format!(
+                                "Last load: {:.1} ms",
+                                app_data.load_duration.as_secs_f64() * 1000.0
+                            ));
+                            for (label, diff_data) in [
+                                ("Modified", &app_data.modified_diff_data),
+                                ("Staged", &app_data.staged_diff_data),
+                                ("All", &app_data.combined_diff_data),
+                            ] {
+                                lines.push(format!(
+                                    "{}: {} files, {} lines",
+                                    label,
+                                    diff_data.diffs.len(),
+                                    diff_data.line_count()
+                                ));
+                            }
+                        }
+                        None => lines.push("No project loaded".to_string()),
+                    }
+
+                    for line in lines {
+                        ui.label(RichText::new(line).monospace().strong());
+                    }
+                });
+            });
+    }
+}
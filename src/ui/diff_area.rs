@@ -1,41 +1,664 @@
-use egui::{Color32, Response, RichText, ScrollArea, Ui, Widget};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+use egui::{
+    pos2, Align, Align2, Color32, Id, Rect, Response, RichText, ScrollArea, Sense, Ui, Widget,
+    WidgetInfo, WidgetType,
+};
 
 use crate::{
-    git::Diff,
-    ui::{code::CodeWidget, line_numbers::LineNumbersWidget, origins::OriginsWidget},
+    annotations::{Annotation, Severity},
+    git::{Diff, Encoding, FoldedDiff, Permalink},
+    search::SearchQuery,
+    settings::Settings,
+    ui::{
+        code::CodeWidget,
+        line_numbers::LineNumbersWidget,
+        metrics::DiffMetrics,
+        minimap::{MinimapWidget, MINIMAP_WIDTH},
+        origins::OriginsWidget,
+    },
+    wrap::WrappedRows,
 };
 
 pub struct DiffAreaWidget {
     diff: Diff,
+    pane: usize,
+    settings: Settings,
+    metrics: DiffMetrics,
+    scroll_to_row: Option<usize>,
+    /// Matches of this query are highlighted in the code.
+    search: SearchQuery,
+    permalink: Option<Permalink>,
+    /// Annotations of this file by new line number.
+    annotations: BTreeMap<u32, Vec<Annotation>>,
+}
+
+impl DiffAreaWidget {
+    pub fn new(
+        diff: Diff,
+        pane: usize,
+        settings: Settings,
+        scroll_to_row: Option<usize>,
+        search: SearchQuery,
+        permalink: Option<Permalink>,
+        annotations: BTreeMap<u32, Vec<Annotation>>,
+    ) -> DiffAreaWidget {
+        DiffAreaWidget {
+            diff,
+            pane,
+            metrics: DiffMetrics::new(settings.density, settings.font_size),
+            settings,
+            scroll_to_row,
+            search,
+            permalink,
+            annotations,
+        }
+    }
+}
+
+impl DiffAreaWidget {
+    /// Writes the annotations of the visible lines after the end of their code, in `code`'s rows.
+    fn paint_annotations(
+        &self,
+        ui: &Ui,
+        code: &Response,
+        row_range: Range<usize>,
+        wrapped: &WrappedRows,
+    ) {
+        let font_id = self.metrics.font_id();
+        let glyph_width = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+        let row_height = self.metrics.row_height(ui);
+
+        for (line, &row) in self.diff.lines.iter().zip(&self.diff.line_rows) {
+            let Some(annotations) = line.new_lineno.and_then(|n| self.annotations.get(&n)) else {
+                continue;
+            };
+            if !row_range.contains(&row) || line.is_eof_marker() {
+                continue;
+            }
+
+            // After the last screen row of the line, when it's wrapped.
+            let columns = wrapped.end_column(line.content.trim_end()) + 4;
+            let screen_row = wrapped.last(row) - wrapped.start(row_range.start);
+            let position = code.rect.left_top()
+                + self.metrics.margin
+                + egui::vec2(columns as f32 * glyph_width, screen_row as f32 * row_height);
+            let severity = annotations
+                .iter()
+                .map(|annotation| annotation.severity)
+                .min()
+                .unwrap_or(Severity::Note);
+            let text = annotations
+                .iter()
+                .map(|annotation| {
+                    format!(
+                        "● {}: {}",
+                        annotation.severity.label_text(),
+                        annotation.message
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("  ");
+            ui.painter().text(
+                position,
+                Align2::LEFT_TOP,
+                text,
+                font_id.clone(),
+                severity_color(severity),
+            );
+        }
+    }
 }
 
 impl DiffAreaWidget {
-    pub fn new(diff: Diff) -> DiffAreaWidget {
-        DiffAreaWidget { diff }
+    /// Marks the blocks moved to or from other files with a bar along their rows, and names
+    /// the other file after the end of the block's first visible line.
+    fn paint_moves(
+        &self,
+        ui: &Ui,
+        code: &Response,
+        row_range: Range<usize>,
+        wrapped: &WrappedRows,
+    ) {
+        let font_id = self.metrics.font_id();
+        let glyph_width = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+        let row_height = self.metrics.row_height(ui);
+        let color = Color32::from_rgb(180, 140, 240);
+
+        for block in &self.diff.moves {
+            let first = (*block.rows.start()).max(row_range.start);
+            let end = (*block.rows.end() + 1).min(row_range.end);
+            if first >= end {
+                continue;
+            }
+
+            let top = code.rect.top() + self.metrics.margin.y;
+            let y = |row: usize| {
+                top + (wrapped.start(row) - wrapped.start(row_range.start)) as f32 * row_height
+            };
+            // Down to the last screen row of the block, not into a gap after it.
+            let bottom = y(end - 1)
+                + (wrapped.last(end - 1) + 1 - wrapped.start(end - 1)) as f32 * row_height;
+            let y_range = y(first)..=bottom;
+            let bar = Rect::from_x_y_ranges(code.rect.left()..=code.rect.left() + 2.0, y_range);
+            ui.painter().rect_filled(bar, 0.0, color);
+
+            let columns = self
+                .diff
+                .lines
+                .iter()
+                .zip(&self.diff.line_rows)
+                .find(|(line, &row)| row == first && !line.is_eof_marker())
+                .map_or(0, |(line, _)| wrapped.end_column(line.content.trim_end()))
+                + 4;
+            ui.painter().text(
+                pos2(
+                    code.rect.left() + self.metrics.margin.x + columns as f32 * glyph_width,
+                    y(first) + (wrapped.last(first) - wrapped.start(first)) as f32 * row_height,
+                ),
+                Align2::LEFT_TOP,
+                block.label_text(),
+                font_id.clone(),
+                color,
+            );
+        }
+    }
+}
+
+impl DiffAreaWidget {
+    /// The diff with its long runs of unchanged lines folded, except the ones expanded
+    /// before, and its collapsed hunks hidden. It's kept between frames, as folding rebuilds
+    /// the whole diff.
+    fn folded(&self, ui: &Ui) -> FoldedDiff {
+        let threshold = self.settings.fold_unchanged;
+        let expanded = ui
+            .data(|d| d.get_temp::<HashSet<usize>>(expanded_folds_id(&self.diff)))
+            .unwrap_or_default();
+        let collapsed = ui
+            .data(|d| d.get_temp::<HashSet<usize>>(collapsed_hunks_id(&self.diff)))
+            .unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        let sorted = |set: &HashSet<usize>| {
+            let mut items = set.iter().copied().collect::<Vec<usize>>();
+            items.sort();
+            items
+        };
+        (
+            self.diff.file_name(),
+            self.diff.lines.len(),
+            self.diff.moves.len(),
+            threshold,
+            sorted(&expanded),
+            sorted(&collapsed),
+        )
+            .hash(&mut hasher);
+        let key = hasher.finish();
+
+        let cache = Id::new(("folded diff", self.pane));
+        if let Some((cached_key, folded)) = ui.data(|d| d.get_temp::<(u64, FoldedDiff)>(cache)) {
+            if cached_key == key {
+                return folded;
+            }
+        }
+        let folded = self.diff.folded(threshold, &expanded, &collapsed);
+        ui.data_mut(|d| d.insert_temp(cache, (key, folded.clone())));
+        folded
+    }
+
+    /// Lets the bands of folded lines be clicked to show the lines again.
+    fn add_fold_bands(
+        &self,
+        ui: &mut Ui,
+        folded: &FoldedDiff,
+        columns: Rect,
+        rows: Range<usize>,
+        wrapped: &WrappedRows,
+    ) {
+        for fold in &folded.folds {
+            if !rows.contains(&fold.row) {
+                continue;
+            }
+            let rect = self.row_rect(ui, columns, rows.start, fold.row, wrapped);
+            let response = ui
+                .interact(
+                    rect,
+                    Id::new(("fold band", self.pane, fold.first_line)),
+                    Sense::click(),
+                )
+                .on_hover_text("Show the hidden lines")
+                .on_hover_cursor(egui::CursorIcon::PointingHand);
+            if response.hovered() {
+                ui.painter()
+                    .rect_stroke(rect, 0.0, ui.visuals().widgets.hovered.bg_stroke);
+            }
+            if response.clicked() {
+                let id = expanded_folds_id(&folded.diff);
+                ui.data_mut(|d| {
+                    d.get_temp_mut_or_default::<HashSet<usize>>(id)
+                        .insert(fold.first_line)
+                });
+            }
+        }
+    }
+
+    /// Lets hunk headers be clicked to collapse their hunk, or expand it again.
+    fn add_hunk_toggles(
+        &self,
+        ui: &mut Ui,
+        folded: &FoldedDiff,
+        columns: Rect,
+        rows: Range<usize>,
+        wrapped: &WrappedRows,
+    ) {
+        for header in &folded.hunks {
+            if !rows.contains(&header.row) {
+                continue;
+            }
+            let rect = self.row_rect(ui, columns, rows.start, header.row, wrapped);
+            let hover_text = match header.collapsed {
+                true => format!("Show the hunk at line {}", header.line),
+                false => format!("Hide the hunk at line {}", header.line),
+            };
+            let response = ui
+                .interact(
+                    rect,
+                    Id::new(("hunk toggle", self.pane, header.hunk)),
+                    Sense::click(),
+                )
+                .on_hover_text(hover_text)
+                .on_hover_cursor(egui::CursorIcon::PointingHand);
+            if response.hovered() {
+                ui.painter()
+                    .rect_stroke(rect, 0.0, ui.visuals().widgets.hovered.bg_stroke);
+            }
+            if response.clicked() {
+                let id = collapsed_hunks_id(&self.diff);
+                ui.data_mut(|d| {
+                    let collapsed = d.get_temp_mut_or_default::<HashSet<usize>>(id);
+                    if !collapsed.remove(&header.hunk) {
+                        collapsed.insert(header.hunk);
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl DiffAreaWidget {
+    /// Characters of code that fit in `width` next to the other columns, to wrap lines after.
+    fn wrap_columns(&self, ui: &Ui, width: f32) -> usize {
+        let glyph_width = ui.fonts(|f| f.glyph_width(&self.metrics.font_id(), ' '));
+        let spacing = ui.spacing();
+        let column_width = |glyphs: usize| {
+            glyphs as f32 * glyph_width + 2.0 * self.metrics.margin.x + self.metrics.column_spacing
+        };
+        let mut code_width = width
+            - spacing.scroll_bar_width
+            - spacing.scroll_bar_inner_margin
+            - spacing.scroll_bar_outer_margin
+            - 2.0 * self.metrics.margin.x;
+        if self.settings.show_line_numbers {
+            let lines = self.diff.lines_content.lines();
+            let glyphs = lines.map(|line| line.chars().count()).max().unwrap_or(0);
+            code_width -= column_width(glyphs);
+        }
+        if self.settings.show_origins {
+            code_width -= column_width(2);
+        }
+        (code_width / glyph_width).max(0.0) as usize
+    }
+
+    /// The screen rows of the folded diff, wrapped after `columns` characters if given and
+    /// with a gap above each of `gaps`. Like the folded diff, they are kept between frames.
+    fn wrapped(&self, ui: &Ui, columns: Option<usize>, gaps: &[usize]) -> WrappedRows {
+        let Some(columns) = columns else {
+            return WrappedRows::unwrapped(self.diff.row_count()).with_gaps(gaps);
+        };
+        let mut hasher = DefaultHasher::new();
+        (
+            self.diff.file_name(),
+            self.diff.content.len(),
+            self.diff.row_count(),
+            columns,
+            self.settings.show_whitespace,
+            gaps,
+        )
+            .hash(&mut hasher);
+        let key = hasher.finish();
+
+        let cache = Id::new(("wrapped rows", self.pane));
+        if let Some((cached_key, wrapped)) = ui.data(|d| d.get_temp::<(u64, WrappedRows)>(cache)) {
+            if cached_key == key {
+                return wrapped;
+            }
+        }
+        let wrapped = WrappedRows::new(&self.diff.content, columns, self.settings.show_whitespace)
+            .with_gaps(gaps);
+        ui.data_mut(|d| d.insert_temp(cache, (key, wrapped.clone())));
+        wrapped
+    }
+
+    /// Where `row` is shown in `columns`, the first shown row of which is `first`.
+    fn row_rect(
+        &self,
+        ui: &Ui,
+        columns: Rect,
+        first: usize,
+        row: usize,
+        wrapped: &WrappedRows,
+    ) -> Rect {
+        let row_height = self.metrics.row_height(ui);
+        let top = columns.top()
+            + self.metrics.margin.y
+            + (wrapped.start(row) - wrapped.start(first)) as f32 * row_height;
+        let bottom = top + (wrapped.start(row + 1) - wrapped.start(row)) as f32 * row_height;
+        Rect::from_x_y_ranges(columns.x_range(), top..=bottom)
+    }
+}
+
+impl DiffAreaWidget {
+    /// Makes every visible row a focusable widget describing its line, so that screen
+    /// readers and keyboard users can step through the diff with Tab.
+    fn add_row_labels(
+        &self,
+        ui: &mut Ui,
+        columns: Rect,
+        row_range: Range<usize>,
+        wrapped: &WrappedRows,
+    ) {
+        for row in row_range.clone() {
+            let Some(description) = self.diff.row_description(row) else {
+                continue;
+            };
+            let rect = self.row_rect(ui, columns, row_range.start, row, wrapped);
+            let response = ui.interact(
+                rect,
+                Id::new(("diff row", self.pane, row)),
+                Sense::focusable_noninteractive(),
+            );
+            response.widget_info(|| WidgetInfo::labeled(WidgetType::Label, &description));
+
+            if response.gained_focus() {
+                // Keep the rows after it laid out, so Tab can move on to them.
+                response.scroll_to_me(Some(Align::Center));
+            }
+            if response.has_focus() {
+                ui.painter()
+                    .rect_stroke(rect, 0.0, ui.visuals().selection.stroke);
+            }
+        }
     }
 }
 
 impl Widget for DiffAreaWidget {
-    fn ui(self, ui: &mut Ui) -> Response {
+    fn ui(mut self, ui: &mut Ui) -> Response {
         puffin::profile_function!("DiffAreaWidget");
+        if !self.diff.is_loaded() {
+            return ui
+                .horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(RichText::new("Loading diff…").color(Color32::GRAY));
+                })
+                .response;
+        }
+        if let Some((old, new)) = self.diff.line_ending_change {
+            let show_anyway = Id::new(("show line endings", self.diff.file_name()));
+            if !ui.data(|d| d.get_temp::<bool>(show_anyway).unwrap_or(false)) {
+                return ui
+                    .horizontal(|ui| {
+                        ui.label(
+                            RichText::new(format!(
+                                "Line endings changed {} → {}",
+                                old.label_text(),
+                                new.label_text()
+                            ))
+                            .color(Color32::GRAY),
+                        );
+                        if ui.button("Show anyway").clicked() {
+                            ui.data_mut(|d| d.insert_temp(show_anyway, true));
+                        }
+                    })
+                    .response;
+            }
+        }
         if self.diff.lines.is_empty() {
-            return ui.label(RichText::new("No content").color(Color32::GRAY));
+            return ui.label(RichText::new(self.diff.empty_reason()).color(Color32::GRAY));
         }
 
-        let total_rows = self.diff.lines.len() + self.diff.headers.len();
+        // From here on, rows are those of the folded diff.
+        let folded = self.folded(ui);
+        self.scroll_to_row = self.scroll_to_row.map(|row| folded.row(row));
+        self.diff = folded.diff.clone();
+        let metrics = self.metrics;
+        let row_height = metrics.row_height(ui);
 
         ui.vertical(|ui| {
-            ScrollArea::both()
-                .id_source("diff area")
-                .auto_shrink([false, false])
-                .show_rows(ui, 10.0, total_rows, |ui, row_range| {
-                    ui.horizontal(|ui| {
-                        ui.add(LineNumbersWidget::new(self.diff.clone(), row_range.clone()));
-                        ui.add(OriginsWidget::new(self.diff.clone(), row_range.clone()));
-                        ui.add(CodeWidget::new(self.diff.clone(), row_range.clone()));
-                    });
-                });
+            if let Some((old, new)) = self.diff.encoding_change {
+                ui.label(
+                    RichText::new(format!(
+                        "Encoding changed {} → {}",
+                        old.label_text(),
+                        new.label_text()
+                    ))
+                    .color(Color32::GRAY),
+                );
+            } else if self.diff.encoding != Encoding::Utf8 {
+                ui.label(RichText::new(self.diff.encoding.label_text()).color(Color32::GRAY));
+            }
+            if let Some((old, new)) = self.diff.mode_change() {
+                ui.label(
+                    RichText::new(format!("Mode changed {:o} → {:o}", old, new))
+                        .color(Color32::GRAY),
+                );
+            }
+            let has_context = self
+                .diff
+                .headers
+                .iter()
+                .any(|header| !header.context().is_empty());
+            if self.settings.context_breadcrumb && has_context {
+                // The top row is only known from the previous frame's scroll offset. The label
+                // stays even without a context, so the code doesn't jump while scrolling.
+                let top_row = ui.data(|d| d.get_temp(top_row_id(self.pane))).unwrap_or(0);
+                let top_row = folded.row(top_row);
+                let text = match self.diff.hunk_context_at(top_row) {
+                    Some(context) => format!("in {}", context.trim_end_matches('{').trim_end()),
+                    None => String::new(),
+                };
+                ui.label(
+                    RichText::new(text)
+                        .monospace()
+                        .color(self.settings.theme().highlight),
+                );
+            }
+
+            let available = ui.available_rect_before_wrap();
+            let minimap_left = available.right() - MINIMAP_WIDTH;
+            let diff_rect = Rect::from_min_max(
+                available.min,
+                pos2(minimap_left - metrics.column_spacing, available.bottom()),
+            );
+            let wrap_columns =
+                (self.settings.wrap_lines).then(|| self.wrap_columns(ui, diff_rect.width()));
+            // Every hunk but the first is set off from the one above it.
+            let hunk_gaps = match self.settings.hunk_separators {
+                true => folded.hunks.iter().map(|hunk| hunk.row).collect(),
+                false => Vec::new(),
+            };
+            let wrapped = self.wrapped(ui, wrap_columns, &hunk_gaps);
+
+            // Rows are laid out inside the column text, so any item spacing would
+            // make the shown rows disagree with the actual row positions.
+            ui.spacing_mut().item_spacing = egui::vec2(metrics.column_spacing, 0.0);
+            let scroll_area = match wrap_columns {
+                Some(_) => ScrollArea::vertical(),
+                None => ScrollArea::both(),
+            };
+            let mut scroll_area = scroll_area
+                .id_source(("diff area", self.pane))
+                .auto_shrink([false, false]);
+            if let Some(row) = self.scroll_to_row {
+                let offset = wrapped.start(row) as f32 * row_height;
+                scroll_area = scroll_area.vertical_scroll_offset(offset);
+            }
+            let minimap_jump = Id::new(("minimap jump", self.pane));
+            if let Some(offset) = ui.data(|d| d.get_temp::<f32>(minimap_jump)) {
+                ui.data_mut(|d| d.remove::<f32>(minimap_jump));
+                scroll_area = scroll_area.vertical_scroll_offset(offset);
+            }
+            let output = ui
+                .allocate_ui_at_rect(diff_rect, |ui| {
+                    show_screen_rows(scroll_area, ui, row_height, &wrapped, |ui, row_range| {
+                        let continuations = wrapped.continuations(row_range.clone());
+                        let columns = ui.horizontal(|ui| {
+                            if self.settings.show_line_numbers {
+                                ui.add(LineNumbersWidget::new(
+                                    self.diff.clone(),
+                                    row_range.clone(),
+                                    metrics,
+                                    continuations.clone(),
+                                ));
+                            }
+                            if self.settings.show_origins {
+                                ui.add(OriginsWidget::new(
+                                    self.diff.clone(),
+                                    row_range.clone(),
+                                    metrics,
+                                    continuations,
+                                    self.settings.theme(),
+                                ));
+                            }
+                            ui.add(CodeWidget::new(
+                                self.diff.clone(),
+                                row_range.clone(),
+                                metrics,
+                                self.settings.clone(),
+                                self.search.clone(),
+                                self.permalink.clone(),
+                                wrapped.clone(),
+                            ))
+                        });
+
+                        let rect = columns.response.rect;
+                        self.add_row_labels(ui, rect, row_range.clone(), &wrapped);
+                        self.add_fold_bands(ui, &folded, rect, row_range.clone(), &wrapped);
+                        self.add_hunk_toggles(ui, &folded, rect, row_range.clone(), &wrapped);
+
+                        if !self.annotations.is_empty() {
+                            self.paint_annotations(ui, &columns.inner, row_range.clone(), &wrapped);
+                        }
+                        if !self.diff.moves.is_empty() {
+                            self.paint_moves(ui, &columns.inner, row_range.clone(), &wrapped);
+                        }
+
+                        // A line across the middle of the gap above each hunk.
+                        let rect = columns.response.rect;
+                        let stroke = ui.visuals().widgets.noninteractive.bg_stroke;
+                        for &row in wrapped.gaps() {
+                            if row <= row_range.start || !row_range.contains(&row) {
+                                continue;
+                            }
+                            let y = self
+                                .row_rect(ui, rect, row_range.start, row, &wrapped)
+                                .top()
+                                - row_height / 2.0;
+                            ui.painter().hline(rect.x_range(), y, stroke);
+                        }
+                    })
+                })
+                .inner;
+
+            // Kept as a row of the unfolded diff, which the navigation history goes by.
+            let top_row = wrapped.row_at((output.state.offset.y / row_height) as usize);
+            let top_row = folded.unfolded_row(top_row);
+            ui.data_mut(|d| d.insert_temp(top_row_id(self.pane), top_row));
+            let bottom = output.state.offset.y + output.inner_rect.height();
+            let shows_end = bottom >= output.content_size.y - row_height;
+            ui.data_mut(|d| d.insert_temp(shows_end_id(self.pane), shows_end));
+
+            let content_height = output.content_size.y.max(1.0);
+            let viewport = output.state.offset.y / content_height
+                ..(output.state.offset.y + output.inner_rect.height()) / content_height;
+            let minimap_rect = Rect::from_x_y_ranges(
+                minimap_left..=available.right(),
+                output.inner_rect.y_range(),
+            );
+            let minimap = ui.add(MinimapWidget::new(
+                self.diff.clone(),
+                wrapped,
+                minimap_rect,
+                viewport.start.min(1.0)..viewport.end.min(1.0),
+                self.settings.theme(),
+            ));
+            if let Some(fraction) = MinimapWidget::fraction_at(&minimap) {
+                let offset = fraction * content_height - output.inner_rect.height() / 2.0;
+                ui.data_mut(|d| d.insert_temp(minimap_jump, offset.max(0.0)));
+                ui.ctx().request_repaint();
+            }
         })
         .response
     }
 }
+
+/// Like [`ScrollArea::show_rows`], but with rows taking up as many screen rows as `wrapped`
+/// wraps them onto. `add_contents` is given the rows to show.
+fn show_screen_rows<R>(
+    scroll_area: ScrollArea,
+    ui: &mut Ui,
+    row_height: f32,
+    wrapped: &WrappedRows,
+    add_contents: impl FnOnce(&mut Ui, Range<usize>) -> R,
+) -> egui::scroll_area::ScrollAreaOutput<R> {
+    scroll_area.show_viewport(ui, |ui, viewport| {
+        ui.set_height(wrapped.screen_rows() as f32 * row_height);
+
+        let first = wrapped.row_at((viewport.min.y / row_height).floor() as usize);
+        let last = wrapped.row_at((viewport.max.y / row_height).ceil() as usize);
+        let rows = first..(last + 2).min(wrapped.row_count());
+        let top = ui.max_rect().top() + wrapped.start(rows.start) as f32 * row_height;
+        let bottom = ui.max_rect().top() + wrapped.start(rows.end) as f32 * row_height;
+        let rect = Rect::from_x_y_ranges(ui.max_rect().x_range(), top..=bottom);
+
+        ui.allocate_ui_at_rect(rect, |ui| {
+            // Keeps the ids of the widgets in the rows the same, however far down they are.
+            ui.skip_ahead_auto_ids(rows.start);
+            add_contents(ui, rows)
+        })
+        .inner
+    })
+}
+
+/// Where the folds of a file that were expanded are kept, by their first hidden line. They
+/// are kept for each version of the change, so a refresh that changes it expands none.
+fn expanded_folds_id(diff: &Diff) -> Id {
+    Id::new(("expanded folds", diff.file_name(), diff.content_key()))
+}
+
+/// Where the hunks of a file that were collapsed are kept, by their index. Like the expanded
+/// folds, they are kept for each version of the change, as its hunks may be others.
+fn collapsed_hunks_id(diff: &Diff) -> Id {
+    Id::new(("collapsed hunks", diff.file_name(), diff.content_key()))
+}
+
+/// Where the first visible row of a file pane is kept, for the navigation history.
+pub fn top_row_id(pane: usize) -> Id {
+    Id::new(("diff area top row", pane))
+}
+
+/// Where whether the end of a file pane is in view is kept, to wrap around stepping through
+/// its changes.
+pub fn shows_end_id(pane: usize) -> Id {
+    Id::new(("diff area shows end", pane))
+}
+
+fn severity_color(severity: Severity) -> Color32 {
+    match severity {
+        Severity::Error => Color32::from_rgb(255, 110, 110),
+        Severity::Warning => Color32::from_rgb(230, 190, 60),
+        Severity::Note => Color32::from_rgb(120, 170, 230),
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::mpsc::Sender;
+
+use egui::{Color32, Response, RichText, Ui, Widget};
+
+use crate::data::Message;
+
+pub struct DiffToolbarWidget {
+    diff_index: usize,
+    file_name: String,
+    /// Old and new size of the file, when known.
+    sizes: Option<String>,
+    closable: bool,
+    sender: Sender<Message>,
+}
+
+impl DiffToolbarWidget {
+    pub fn new(
+        diff_index: usize,
+        file_name: String,
+        sizes: Option<String>,
+        closable: bool,
+        sender: Sender<Message>,
+    ) -> DiffToolbarWidget {
+        DiffToolbarWidget {
+            diff_index,
+            file_name,
+            sizes,
+            closable,
+            sender,
+        }
+    }
+}
+
+impl Widget for DiffToolbarWidget {
+    fn ui(self, ui: &mut Ui) -> Response {
+        puffin::profile_function!("DiffToolbarWidget");
+        ui.horizontal(|ui| {
+            let breadcrumb = self.file_name.split('/').collect::<Vec<&str>>().join(" › ");
+            ui.label(RichText::new(breadcrumb).strong());
+            if let Some(sizes) = self.sizes {
+                ui.label(RichText::new(sizes).color(Color32::GRAY));
+            }
+            ui.separator();
+
+            // Stepping goes through the selected file, which is the one in the first pane.
+            if !self.closable {
+                let steps = [
+                    ("⬆ Change", "Scroll to the previous change (Shift+P)", false),
+                    ("⬇ Change", "Scroll to the next change (Shift+N)", true),
+                ];
+                for (text, hover_text, forward) in steps {
+                    if ui
+                        .button(RichText::new(text).strong())
+                        .on_hover_text(hover_text)
+                        .clicked()
+                    {
+                        self.sender
+                            .send(Message::StepChange(forward))
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+            }
+            if ui
+                .button(RichText::new("Open in diff tool").strong())
+                .clicked()
+            {
+                self.sender
+                    .send(Message::OpenExternalDiff(self.diff_index))
+                    .expect("Channel closed unexpectedly!");
+            }
+            if ui
+                .button(RichText::new("Copy patch").strong())
+                .on_hover_text("Copy the changes to this file as a patch git apply takes")
+                .clicked()
+            {
+                self.sender
+                    .send(Message::CopyPatch(self.diff_index))
+                    .expect("Channel closed unexpectedly!");
+            }
+
+            if self.closable && ui.button(RichText::new("Close pane").strong()).clicked() {
+                self.sender
+                    .send(Message::CloseSecondaryPane)
+                    .expect("Channel closed unexpectedly!");
+            }
+        })
+        .response
+    }
+}
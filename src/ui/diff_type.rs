@@ -22,24 +22,24 @@ impl DiffTypeSelection {
     pub fn ui(&mut self, ui: &mut Ui) {
         puffin::profile_function!("DiffTypeSelectionArea");
         ui.horizontal(|ui| {
-            if ui
-                .selectable_value(
-                    &mut self.selected_diff_type,
-                    DiffType::Modified,
-                    DiffType::Modified.label_text(),
-                )
-                .clicked()
-                || ui
-                    .selectable_value(
-                        &mut self.selected_diff_type,
-                        DiffType::Staged,
-                        DiffType::Staged.label_text(),
-                    )
+            for (diff_type, hover) in [
+                (DiffType::Modified, "Changes not staged yet"),
+                (DiffType::Staged, "Changes staged for the next commit"),
+                (
+                    DiffType::All,
+                    "Staged and not staged changes, compared to HEAD",
+                ),
+            ] {
+                let label = diff_type.label_text();
+                if ui
+                    .selectable_value(&mut self.selected_diff_type, diff_type, label)
+                    .on_hover_text(hover)
                     .clicked()
-            {
-                self.sender
-                    .send(Message::ChangeDiffType(self.selected_diff_type.clone()))
-                    .expect("Channel closed unexpectedly!");
+                {
+                    self.sender
+                        .send(Message::ChangeDiffType(self.selected_diff_type.clone()))
+                        .expect("Channel closed unexpectedly!");
+                }
             }
         });
     }
@@ -1,24 +1,55 @@
-use std::sync::mpsc::Sender;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::mpsc::Sender,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use egui::{ScrollArea, Ui};
+use egui::{
+    pos2, Align, CollapsingHeader, Color32, Id, Layout, Rect, Response, RichText, ScrollArea,
+    Stroke, TextEdit, Ui,
+};
 
-use crate::data::{DiffData, Message};
+use crate::{
+    data::{ControlData, DiffData, DiffType, Message},
+    file_tree::{self, Directory},
+    settings::{FileOrder, Settings},
+    ui::stats::UNTRACKED_COLOR,
+};
 
 pub struct FilesArea {
     diff_data: DiffData,
     selected_diff_index: usize,
+    settings: Settings,
+    file_filter: String,
+    /// The files listed, in order.
+    order: Vec<usize>,
+    file_times: HashMap<String, i64>,
+    /// Names of the files marked as viewed.
+    viewed: HashSet<String>,
+    /// The other diff type, and the index of each file that has changes there too, unless
+    /// all changes are shown.
+    other_changes: Option<(DiffType, HashMap<String, usize>)>,
     sender: Sender<Message>,
 }
 
 impl FilesArea {
     pub fn new(
         diff_data: DiffData,
-        selected_diff_index: usize,
+        control_data: &ControlData,
+        other_changes: Option<(DiffType, HashMap<String, usize>)>,
         sender: Sender<Message>,
     ) -> FilesArea {
+        let diff_type = &control_data.diff_type;
         FilesArea {
+            order: control_data.listed_order(&diff_data.diffs),
             diff_data,
-            selected_diff_index,
+            selected_diff_index: control_data.selected_diff_index,
+            settings: control_data.settings.clone(),
+            file_filter: control_data.file_filter.clone(),
+            file_times: control_data.file_times.for_diff_type(diff_type).clone(),
+            viewed: control_data.viewed_files(diff_type),
+            other_changes,
             sender,
         }
     }
@@ -27,21 +58,270 @@ impl FilesArea {
 impl FilesArea {
     pub fn ui(&mut self, ui: &mut Ui) {
         puffin::profile_function!("FilesAreaWidget");
+        let prefix = file_tree::prefix(&self.diff_data.diffs, &self.settings);
+
         ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button(RichText::new("◀").strong())
+                    .on_hover_text("Collapse file list (Ctrl+B)")
+                    .clicked()
+                {
+                    self.sender
+                        .send(Message::ToggleFileList)
+                        .expect("Channel closed unexpectedly!");
+                }
+
+                if !prefix.as_os_str().is_empty() {
+                    ui.label(RichText::new(format!("{}/", prefix.display())).color(Color32::GRAY));
+                }
+
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.menu_button(RichText::new("⇅").strong(), |ui| {
+                        for file_order in [FileOrder::Path, FileOrder::Name, FileOrder::Changes] {
+                            if ui
+                                .radio(
+                                    self.settings.file_order == file_order,
+                                    file_order.label_text(),
+                                )
+                                .clicked()
+                            {
+                                let settings = Settings {
+                                    file_order,
+                                    ..self.settings.clone()
+                                };
+                                self.sender
+                                    .send(Message::UpdateSettings(Box::new(settings)))
+                                    .expect("Channel closed unexpectedly!");
+                                ui.close_menu();
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text(format!(
+                        "Sorted by {}",
+                        self.settings.file_order.label_text()
+                    ));
+                });
+            });
+
+            // Like the sort order, the filter only changes which files are listed.
+            let filter_changed = ui
+                .horizontal(|ui| {
+                    let entry = ui.add(
+                        TextEdit::singleline(&mut self.file_filter)
+                            .hint_text("Filter files, e.g. *.rs")
+                            .desired_width(200.0),
+                    );
+                    super::note_text_field(ui, &entry);
+                    let mut changed = entry.changed();
+                    if !self.file_filter.is_empty()
+                        && ui.small_button("✖").on_hover_text("Clear").clicked()
+                    {
+                        self.file_filter.clear();
+                        changed = true;
+                    }
+                    changed
+                })
+                .inner;
+            if filter_changed {
+                self.sender
+                    .send(Message::ChangeFileFilter(self.file_filter.clone()))
+                    .expect("Channel closed unexpectedly!");
+            }
+            if self.order.is_empty() {
+                ui.label(RichText::new("No files match the filter").color(Color32::GRAY));
+            }
+
             ScrollArea::vertical()
                 .id_source("file scroll area")
                 .show(ui, |ui| {
-                    for (i, diff) in self.diff_data.diffs.iter().enumerate() {
-                        if ui
-                            .selectable_value(&mut self.selected_diff_index, i, diff.file_name())
-                            .clicked()
-                        {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs() as i64);
+
+                    // Follow the selection when it moves, e.g. after marking a file as viewed.
+                    let scrolled_to = Id::new("files area scrolled to");
+                    let follow = ui.data(|d| d.get_temp::<usize>(scrolled_to))
+                        != Some(self.selected_diff_index);
+                    ui.data_mut(|d| d.insert_temp(scrolled_to, self.selected_diff_index));
+
+                    if self.settings.file_tree {
+                        let root = file_tree::build(&self.diff_data.diffs, &self.order, &prefix);
+                        self.directory_contents(ui, &root, &prefix, now, follow);
+                    } else {
+                        for &i in &self.order.clone() {
+                            let name = self.diff_data.diffs[i].display_name(&prefix);
+                            self.file_row(ui, i, name, now, follow);
+                        }
+                    }
+                });
+        });
+    }
+
+    /// The subdirectories of `dir`, each collapsible and with what changed below it, then
+    /// the files directly in it.
+    fn directory_contents(
+        &mut self,
+        ui: &mut Ui,
+        dir: &Directory,
+        prefix: &Path,
+        now: i64,
+        follow: bool,
+    ) {
+        for subdirectory in &dir.directories {
+            // Folders open up when the selection moves to a file in them.
+            let open = (follow && subdirectory.contains(self.selected_diff_index)).then_some(true);
+            CollapsingHeader::new(subdirectory.label_text())
+                .id_source(("file tree", &subdirectory.path))
+                .default_open(true)
+                .open(open)
+                .show(ui, |ui| {
+                    self.directory_contents(ui, subdirectory, prefix, now, follow)
+                });
+        }
+        for &i in &dir.files {
+            let name = self.diff_data.diffs[i].display_name(&prefix.join(&dir.path));
+            self.file_row(ui, i, name, now, follow);
+        }
+    }
+
+    /// A file of the list, with whether it was viewed and where else it has changes.
+    fn file_row(&mut self, ui: &mut Ui, i: usize, name: String, now: i64, follow: bool) {
+        let file_name = self.diff_data.diffs[i].file_name();
+        let is_binary = self.diff_data.diffs[i].is_binary;
+        let response = ui
+            .horizontal(|ui| {
+                let mut viewed = self.viewed.contains(&file_name);
+                if ui
+                    .checkbox(&mut viewed, "")
+                    .on_hover_text("Viewed (X marks the selected file and moves on)")
+                    .changed()
+                {
+                    self.sender
+                        .send(Message::ToggleViewed(file_name.clone()))
+                        .expect("Channel closed unexpectedly!");
+                }
+                let mut name = RichText::new(name);
+                if viewed {
+                    name = name.color(Color32::GRAY);
+                }
+                let response = ui.selectable_value(&mut self.selected_diff_index, i, name);
+                if i == self.selected_diff_index {
+                    mark_selected(ui, &response);
+                    if follow {
+                        response.scroll_to_me(None);
+                    }
+                }
+                if self.diff_data.diffs[i].is_untracked() {
+                    ui.label(RichText::new("U").small().color(UNTRACKED_COLOR))
+                        .on_hover_text("Untracked file, not added to git yet");
+                }
+                if is_binary {
+                    ui.label(RichText::new("BIN").small().color(Color32::GRAY))
+                        .on_hover_text("Binary file");
+                } else {
+                    let diff = &self.diff_data.diffs[i];
+                    let theme = self.settings.theme();
+                    ui.label(
+                        RichText::new(format!("+{}", diff.insertions()))
+                            .small()
+                            .color(theme.insertion),
+                    );
+                    ui.label(
+                        RichText::new(format!("−{}", diff.deletions()))
+                            .small()
+                            .color(theme.deletion),
+                    );
+                }
+                let other_change = self
+                    .other_changes
+                    .as_ref()
+                    .and_then(|(other_type, indices)| {
+                        Some((other_type, *indices.get(&file_name)?))
+                    });
+                if let Some((other_type, other)) = other_change {
+                    // Files changed in both areas are tagged with both;
+                    // the other tag switches to that part of the changes.
+                    for diff_type in [DiffType::Staged, DiffType::Modified] {
+                        let index = (diff_type == *other_type).then_some(other);
+                        let tag = ui
+                            .selectable_label(
+                                index.is_none(),
+                                RichText::new(diff_type.area_text()).small(),
+                            )
+                            .on_hover_text(format!(
+                                "Has {} changes",
+                                diff_type.label_text().to_lowercase()
+                            ));
+                        if let (Some(index), true) = (index, tag.clicked()) {
                             self.sender
-                                .send(Message::ChangeSelectedDiffIndex(i))
+                                .send(Message::ChangeSelectedDiff(diff_type, index))
                                 .expect("Channel closed unexpectedly!");
                         }
                     }
-                });
+                }
+                if let Some(time) = self.file_times.get(&file_name) {
+                    ui.label(RichText::new(relative_time(now - time)).color(Color32::GRAY));
+                }
+                response
+            })
+            .inner;
+        if response.clicked() {
+            self.sender
+                .send(Message::ChangeSelectedDiffIndex(i))
+                .expect("Channel closed unexpectedly!");
+        }
+
+        response.context_menu(|ui| {
+            if self.settings.multi_pane && ui.button("Open in second pane").clicked() {
+                self.sender
+                    .send(Message::OpenSecondaryPane(i))
+                    .expect("Channel closed unexpectedly!");
+                ui.close_menu();
+            }
+            if ui
+                .button("Compare across revisions…")
+                .on_hover_text("Pick two commits that changed this file to compare")
+                .clicked()
+            {
+                self.sender
+                    .send(Message::ShowFileHistory(i))
+                    .expect("Channel closed unexpectedly!");
+                ui.close_menu();
+            }
+            if ui.button("Reveal in file manager").clicked() {
+                self.sender
+                    .send(Message::RevealInFileManager(i))
+                    .expect("Channel closed unexpectedly!");
+                ui.close_menu();
+            }
         });
     }
 }
+
+/// Outlines the selected entry and draws an accent bar on its left edge.
+fn mark_selected(ui: &Ui, response: &Response) {
+    let color = ui.visuals().selection.stroke.color;
+    let rect = response.rect;
+    let painter = ui.painter();
+
+    painter.rect_stroke(rect, 2.0, Stroke::new(1.0, color));
+    painter.rect_filled(
+        Rect::from_min_max(rect.left_top(), pos2(rect.left() + 3.0, rect.bottom())),
+        0.0,
+        color,
+    );
+}
+
+/// Formats an age in seconds as e.g. "2h ago".
+fn relative_time(seconds: i64) -> String {
+    match seconds {
+        s if s < 60 => "just now".to_string(),
+        s if s < 60 * 60 => format!("{}m ago", s / 60),
+        s if s < 60 * 60 * 24 => format!("{}h ago", s / (60 * 60)),
+        s if s < 60 * 60 * 24 * 30 => format!("{}d ago", s / (60 * 60 * 24)),
+        s if s < 60 * 60 * 24 * 365 => format!("{}mo ago", s / (60 * 60 * 24 * 30)),
+        s => format!("{}y ago", s / (60 * 60 * 24 * 365)),
+    }
+}
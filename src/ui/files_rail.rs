@@ -0,0 +1,37 @@
+use std::sync::mpsc::Sender;
+
+use egui::{Color32, Response, RichText, Ui, Widget};
+
+use crate::data::Message;
+
+/// Stand-in for the file list while it is collapsed.
+pub struct FilesRailWidget {
+    file_count: usize,
+    sender: Sender<Message>,
+}
+
+impl FilesRailWidget {
+    pub fn new(file_count: usize, sender: Sender<Message>) -> FilesRailWidget {
+        FilesRailWidget { file_count, sender }
+    }
+}
+
+impl Widget for FilesRailWidget {
+    fn ui(self, ui: &mut Ui) -> Response {
+        puffin::profile_function!("FilesRailWidget");
+        ui.vertical(|ui| {
+            if ui
+                .button(RichText::new("▶").strong())
+                .on_hover_text("Expand file list (Ctrl+B)")
+                .clicked()
+            {
+                self.sender
+                    .send(Message::ToggleFileList)
+                    .expect("Channel closed unexpectedly!");
+            }
+            ui.label(RichText::new(self.file_count.to_string()).color(Color32::GRAY))
+                .on_hover_text("Changed files");
+        })
+        .response
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::mpsc::Sender;
+
+use egui::{Color32, Id, Key, Response, RichText, TextEdit, Ui, Widget};
+
+use crate::{
+    data::Message,
+    git::Diff,
+    search::{self, SearchQuery},
+};
+
+/// Finds text in the shown file, and steps through the matches with Enter and Shift+Enter.
+pub struct FindBarWidget {
+    diff: Diff,
+    query: SearchQuery,
+    /// Index of the match that was last jumped to.
+    current: usize,
+    sender: Sender<Message>,
+}
+
+impl FindBarWidget {
+    pub fn new(
+        diff: Diff,
+        query: SearchQuery,
+        current: usize,
+        sender: Sender<Message>,
+    ) -> FindBarWidget {
+        FindBarWidget {
+            diff,
+            query,
+            current,
+            sender,
+        }
+    }
+}
+
+/// Whether the text field of the bar has been focused since the bar was opened.
+fn focused_id() -> Id {
+    Id::new("find bar focused")
+}
+
+impl FindBarWidget {
+    /// Lets the text field take the focus the next time the bar is opened.
+    pub fn closed(ui: &Ui) {
+        ui.data_mut(|d| d.remove::<bool>(focused_id()));
+    }
+
+    fn jump_to(&self, index: usize, rows: &[usize]) {
+        if let Some(&row) = rows.get(index) {
+            self.sender
+                .send(Message::FindMatch(index, row))
+                .expect("Channel closed unexpectedly!");
+        }
+    }
+}
+
+impl Widget for FindBarWidget {
+    fn ui(mut self, ui: &mut Ui) -> Response {
+        puffin::profile_function!("FindBarWidget");
+        ui.horizontal(|ui| {
+            let text = ui.add(
+                TextEdit::singleline(&mut self.query.text)
+                    .hint_text("Find in file")
+                    .desired_width(200.0),
+            );
+            super::note_text_field(ui, &text);
+            if !ui.data(|d| d.get_temp::<bool>(focused_id()).unwrap_or(false)) {
+                text.request_focus();
+                ui.data_mut(|d| d.insert_temp(focused_id(), true));
+            }
+            let case = ui
+                .checkbox(&mut self.query.case_sensitive, "Aa")
+                .on_hover_text("Match case");
+            let regex = ui
+                .checkbox(&mut self.query.regex, ".*")
+                .on_hover_text("Regular expression");
+
+            let rows = match self.query.matcher() {
+                Ok(matcher) => matcher.map_or(Vec::new(), |m| search::match_rows(&self.diff, &m)),
+                Err(_) => {
+                    ui.label(RichText::new("Invalid regular expression").color(Color32::RED));
+                    Vec::new()
+                }
+            };
+            if text.changed() || case.changed() || regex.changed() {
+                self.sender
+                    .send(Message::UpdateFindQuery(self.query.clone()))
+                    .expect("Channel closed unexpectedly!");
+                self.current = 0;
+                self.jump_to(0, &rows);
+            }
+
+            let step = |forward: bool| match forward {
+                true => (self.current + 1) % rows.len(),
+                false => (self.current + rows.len() - 1) % rows.len(),
+            };
+            if text.lost_focus() && ui.input(|i| i.key_pressed(Key::Escape)) {
+                self.sender
+                    .send(Message::ToggleFind)
+                    .expect("Channel closed unexpectedly!");
+            }
+            if text.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                text.request_focus();
+                if !rows.is_empty() {
+                    self.jump_to(step(!ui.input(|i| i.modifiers.shift)), &rows);
+                }
+            }
+
+            ui.add_enabled_ui(!rows.is_empty(), |ui| {
+                if ui
+                    .small_button("⬆")
+                    .on_hover_text("Previous match")
+                    .clicked()
+                {
+                    self.jump_to(step(false), &rows);
+                }
+                if ui.small_button("⬇").on_hover_text("Next match").clicked() {
+                    self.jump_to(step(true), &rows);
+                }
+            });
+
+            let count = match (rows.len(), self.query.text.is_empty()) {
+                (_, true) => String::new(),
+                (0, false) => "No matches".to_string(),
+                (count, false) => format!("{} of {}", self.current.min(count - 1) + 1, count),
+            };
+            ui.label(RichText::new(count).color(Color32::GRAY));
+        })
+        .response
+    }
+}
@@ -0,0 +1,211 @@
+use std::{path::Path, sync::mpsc::Sender};
+
+use egui::{pos2, vec2, Color32, Id, Rect, Response, RichText, ScrollArea, Sense, Ui, Widget};
+
+use crate::{
+    data::{DiffData, Message},
+    git::{Diff, Permalink},
+    search::SearchQuery,
+    settings::Settings,
+    ui::{
+        code::CodeWidget, diff_toolbar::DiffToolbarWidget, line_numbers::LineNumbersWidget,
+        metrics::DiffMetrics, origins::OriginsWidget,
+    },
+    wrap::WrappedRows,
+};
+
+/// Space between the end of one file and the header of the next.
+const FILE_SPACING: f32 = 16.0;
+
+/// Every changed file's diff in one scroll area, one below the other.
+///
+/// Only the files and rows in view are laid out, so it stays cheap with many files.
+/// Selecting a file in the file list scrolls to it.
+pub struct GalleryWidget {
+    diff_data: DiffData,
+    selected_diff_index: usize,
+    settings: Settings,
+    scroll_to_row: Option<usize>,
+    search: SearchQuery,
+    permalink: Option<Permalink>,
+    sender: Sender<Message>,
+}
+
+impl GalleryWidget {
+    pub fn new(
+        diff_data: DiffData,
+        selected_diff_index: usize,
+        settings: Settings,
+        scroll_to_row: Option<usize>,
+        search: SearchQuery,
+        permalink: Option<Permalink>,
+        sender: Sender<Message>,
+    ) -> GalleryWidget {
+        GalleryWidget {
+            diff_data,
+            selected_diff_index,
+            settings,
+            scroll_to_row,
+            search,
+            permalink,
+            sender,
+        }
+    }
+}
+
+impl GalleryWidget {
+    /// Rows a file takes up below its header. Files that aren't loaded yet are estimated
+    /// from their changed lines, and files without lines take one row for the reason.
+    fn row_count(diff: &Diff) -> usize {
+        match (diff.is_loaded(), diff.lines.is_empty()) {
+            (false, _) => (diff.insertions() + diff.deletions()).max(1),
+            (true, true) => 1,
+            (true, false) => diff.row_count(),
+        }
+    }
+
+    /// Shows the rows `first..end` of a loaded file with its lines, at `rect`'s top.
+    fn add_rows(&self, ui: &mut Ui, diff: &Diff, rect: Rect, rows: (usize, usize)) {
+        let metrics = DiffMetrics::new(self.settings.density, self.settings.font_size);
+        let (first, end) = rows;
+        ui.allocate_ui_at_rect(rect, |ui| {
+            ui.spacing_mut().item_spacing = vec2(metrics.column_spacing, 0.0);
+            ui.horizontal(|ui| {
+                if self.settings.show_line_numbers {
+                    ui.add(LineNumbersWidget::new(
+                        diff.clone(),
+                        first..end,
+                        metrics,
+                        Vec::new(),
+                    ));
+                }
+                if self.settings.show_origins {
+                    ui.add(OriginsWidget::new(
+                        diff.clone(),
+                        first..end,
+                        metrics,
+                        Vec::new(),
+                        self.settings.theme(),
+                    ));
+                }
+                ui.add(CodeWidget::new(
+                    diff.clone(),
+                    first..end,
+                    metrics,
+                    self.settings.clone(),
+                    self.search.clone(),
+                    self.permalink.clone(),
+                    WrappedRows::unwrapped(diff.row_count()),
+                ));
+            });
+        });
+    }
+}
+
+impl Widget for GalleryWidget {
+    fn ui(self, ui: &mut Ui) -> Response {
+        puffin::profile_function!("GalleryWidget");
+        let metrics = DiffMetrics::new(self.settings.density, self.settings.font_size);
+        let row_height = metrics.row_height(ui);
+        let header_height = ui.spacing().interact_size.y + ui.spacing().item_spacing.y * 2.0;
+
+        let diffs = &self.diff_data.diffs;
+        let mut tops = Vec::with_capacity(diffs.len());
+        let mut total_height = 0.0;
+        for diff in diffs {
+            tops.push(total_height);
+            total_height += header_height
+                + Self::row_count(diff) as f32 * row_height
+                + metrics.margin.y * 2.0
+                + FILE_SPACING;
+        }
+
+        let mut scroll_area = ScrollArea::both()
+            .id_source("gallery")
+            .auto_shrink([false, false]);
+        let jumped_to = Id::new("gallery jumped to");
+        let selected_top = tops.get(self.selected_diff_index).copied();
+        if ui.data(|d| d.get_temp::<usize>(jumped_to)) != Some(self.selected_diff_index) {
+            ui.data_mut(|d| d.insert_temp(jumped_to, self.selected_diff_index));
+            if let Some(top) = selected_top {
+                scroll_area = scroll_area.vertical_scroll_offset(top);
+            }
+        }
+        if let (Some(row), Some(top)) = (self.scroll_to_row, selected_top) {
+            scroll_area =
+                scroll_area.vertical_scroll_offset(top + header_height + row as f32 * row_height);
+        }
+
+        let mut visible = Vec::new();
+        let output = scroll_area.show_viewport(ui, |ui, viewport| {
+            ui.set_min_height(total_height);
+            let origin = ui.max_rect().min;
+            let width = ui.available_width();
+
+            for (i, diff) in diffs.iter().enumerate() {
+                let top = tops[i];
+                let bottom = tops.get(i + 1).copied().unwrap_or(total_height);
+                if bottom < viewport.min.y || top > viewport.max.y {
+                    continue;
+                }
+                visible.push(i);
+
+                let header =
+                    Rect::from_min_size(origin + vec2(0.0, top), vec2(width, header_height));
+                ui.allocate_ui_at_rect(header, |ui| {
+                    ui.add(DiffToolbarWidget::new(
+                        i,
+                        diff.display_name(Path::new("")),
+                        diff.sizes_text(),
+                        false,
+                        self.sender.clone(),
+                    ));
+                });
+
+                let body_top = top + header_height;
+                let body = Rect::from_min_max(
+                    origin + vec2(0.0, body_top),
+                    pos2(origin.x + width, origin.y + bottom - FILE_SPACING),
+                );
+                if let Some(error) = diff.load_error() {
+                    ui.allocate_ui_at_rect(body, |ui| {
+                        super::load_failed(ui, error, i, &self.sender);
+                    });
+                    continue;
+                }
+                if !diff.is_loaded() || diff.lines.is_empty() {
+                    let text = match diff.is_loaded() {
+                        true => diff.empty_reason(),
+                        false => "Loading diff…".to_string(),
+                    };
+                    ui.allocate_ui_at_rect(body, |ui| {
+                        ui.label(RichText::new(text).color(Color32::GRAY));
+                    });
+                    continue;
+                }
+
+                // Only the rows inside the viewport, shifted to where they belong.
+                let rows_top = body_top + metrics.margin.y;
+                let first = ((viewport.min.y - rows_top) / row_height).floor().max(0.0) as usize;
+                let end = (((viewport.max.y - rows_top) / row_height).ceil().max(0.0) as usize)
+                    .min(diff.row_count());
+                if first >= end {
+                    continue;
+                }
+                let rows = Rect::from_min_max(
+                    origin + vec2(0.0, body_top + first as f32 * row_height),
+                    body.max,
+                );
+                self.add_rows(ui, diff, rows, (first, end));
+            }
+        });
+        ui.data_mut(|d| d.insert_temp(visible_files_id(), visible));
+
+        ui.interact(output.inner_rect, output.id, Sense::hover())
+    }
+}
+
+/// Where the indices of the files in view are kept, so their lines can be loaded.
+pub fn visible_files_id() -> Id {
+    Id::new("gallery visible files")
+}
@@ -1,17 +1,30 @@
 use std::ops::Range;
 
-use egui::{Color32, Response, RichText, Ui, Widget};
+use egui::{text::LayoutJob, Color32, Response, TextEdit, Ui, Widget};
 
-use crate::git::Diff;
+use crate::{git::Diff, ui::metrics::DiffMetrics, wrap};
 
 pub struct LineNumbersWidget {
     diff: Diff,
     range: Range<usize>,
+    metrics: DiffMetrics,
+    /// Screen rows each row of the code next to it wraps onto after its first.
+    continuations: Vec<usize>,
 }
 
 impl LineNumbersWidget {
-    pub fn new(diff: Diff, range: Range<usize>) -> LineNumbersWidget {
-        LineNumbersWidget { diff, range }
+    pub fn new(
+        diff: Diff,
+        range: Range<usize>,
+        metrics: DiffMetrics,
+        continuations: Vec<usize>,
+    ) -> LineNumbersWidget {
+        LineNumbersWidget {
+            diff,
+            range,
+            metrics,
+            continuations,
+        }
     }
 }
 
@@ -19,15 +32,29 @@ impl Widget for LineNumbersWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         puffin::profile_function!("LineNumbersWidget");
 
+        let font_id = self.metrics.font_id();
+        let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
+            let layout_job = LayoutJob::simple(
+                string.to_owned(),
+                font_id.clone(),
+                Color32::GRAY,
+                f32::INFINITY,
+            );
+            ui.fonts(|f| f.layout_job(layout_job))
+        };
+
         let lines = self.diff.lines_content.lines().collect::<Vec<&str>>();
         let Range { start, end } = self.range;
         let end = std::cmp::min(end, lines.len());
 
-        let content = &lines[start..end].join("\n");
-        ui.vertical(|ui| {
-            ui.add_space(3.0);
-            ui.label(RichText::new(content).monospace().color(Color32::GRAY))
-        })
-        .response
+        let mut content = wrap::pad_continuations(&lines[start..end], &self.continuations);
+        ui.add(
+            TextEdit::multiline(&mut content)
+                .desired_width(0.0)
+                .frame(false)
+                .interactive(false)
+                .margin(self.metrics.margin)
+                .layouter(&mut layouter),
+        )
     }
 }
@@ -0,0 +1,63 @@
+use std::sync::mpsc::Sender;
+
+use egui::{Color32, Context, RichText, ScrollArea, SidePanel};
+
+use crate::{data::Message, git::CommitInfo};
+
+/// Lists the most recent commits, showing the changes of the one clicked.
+pub struct LogPanel {
+    sender: Sender<Message>,
+    log: Vec<CommitInfo>,
+    /// Id of the commit that is shown, if any.
+    shown: Option<String>,
+}
+
+impl LogPanel {
+    pub fn new(sender: Sender<Message>, log: Vec<CommitInfo>, shown: Option<String>) -> LogPanel {
+        LogPanel { sender, log, shown }
+    }
+}
+
+impl LogPanel {
+    pub fn show(&mut self, ctx: &Context) {
+        puffin::profile_function!("LogPanel");
+        SidePanel::right("log panel")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.heading(RichText::new("Log").strong());
+                if self.log.is_empty() {
+                    ui.label(RichText::new("No commits yet").color(Color32::GRAY));
+                    return;
+                }
+                ui.separator();
+
+                ScrollArea::vertical()
+                    .id_source("log")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for commit in &self.log {
+                            let shown = self.shown.as_ref() == Some(&commit.id);
+                            let mut text = format!("{} {}", &commit.id[..7], commit.summary);
+                            let mut hover = commit.id.clone();
+                            if commit.parent_count > 1 {
+                                text.push_str(" (merge)");
+                                hover.push_str("\nMerge commit, compared to its first parent");
+                            }
+                            let response = ui
+                                .selectable_label(shown, RichText::new(text).monospace())
+                                .on_hover_text(hover);
+                            if response.clicked() && !shown {
+                                self.sender
+                                    .send(Message::ViewCommit(commit.id.clone()))
+                                    .expect("Channel closed unexpectedly!");
+                            }
+                            ui.label(
+                                RichText::new(format!("{}, {}", commit.author, commit.date_text()))
+                                    .small()
+                                    .color(Color32::GRAY),
+                            );
+                        }
+                    });
+            });
+    }
+}
@@ -0,0 +1,39 @@
+use egui::{vec2, FontFamily, FontId, Ui, Vec2};
+
+use crate::settings::Density;
+
+/// Spacing shared by the line number, origin and code columns.
+///
+/// All three columns are laid out from the same values so their rows stay
+/// aligned, whichever density is selected.
+#[derive(Clone, Copy)]
+pub struct DiffMetrics {
+    pub margin: Vec2,
+    pub column_spacing: f32,
+    pub font_size: f32,
+}
+
+impl DiffMetrics {
+    pub fn new(density: Density, font_size: f32) -> DiffMetrics {
+        match density {
+            Density::Compact => DiffMetrics {
+                margin: vec2(2.0, 0.0),
+                column_spacing: 2.0,
+                font_size,
+            },
+            Density::Comfortable => DiffMetrics {
+                margin: vec2(4.0, 2.0),
+                column_spacing: 8.0,
+                font_size,
+            },
+        }
+    }
+
+    pub fn font_id(&self) -> FontId {
+        FontId::new(self.font_size, FontFamily::Monospace)
+    }
+
+    pub fn row_height(&self, ui: &Ui) -> f32 {
+        ui.fonts(|f| f.row_height(&self.font_id()))
+    }
+}
@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+use egui::{Color32, Rect, Response, Sense, Stroke, Ui, Widget, WidgetInfo, WidgetType};
+
+use crate::{git::Diff, settings::Theme, wrap::WrappedRows};
+
+/// Width of the strip drawn next to the diff.
+pub const MINIMAP_WIDTH: f32 = 12.0;
+/// Color of the unchanged lines, dim next to the changes.
+const CONTEXT_COLOR: Color32 = Color32::from_gray(70);
+
+/// Overview of where a file's insertions and deletions are, with the visible rows marked.
+///
+/// Clicking or dragging on it is left to the caller, see [`MinimapWidget::fraction_at`].
+pub struct MinimapWidget {
+    diff: Diff,
+    /// The screen rows of the diff's rows, so that wrapped lines take up as much of the
+    /// minimap as of the diff.
+    wrapped: WrappedRows,
+    rect: Rect,
+    /// Visible part of the file, as fractions of its height.
+    viewport: Range<f32>,
+    theme: Theme,
+}
+
+impl MinimapWidget {
+    pub fn new(
+        diff: Diff,
+        wrapped: WrappedRows,
+        rect: Rect,
+        viewport: Range<f32>,
+        theme: Theme,
+    ) -> MinimapWidget {
+        MinimapWidget {
+            diff,
+            wrapped,
+            rect,
+            viewport,
+            theme,
+        }
+    }
+
+    /// How far down the file the pointer is, if it is interacting with the minimap.
+    pub fn fraction_at(response: &Response) -> Option<f32> {
+        let pos = response.interact_pointer_pos()?;
+        Some(((pos.y - response.rect.top()) / response.rect.height()).clamp(0.0, 1.0))
+    }
+}
+
+impl Widget for MinimapWidget {
+    fn ui(self, ui: &mut Ui) -> Response {
+        puffin::profile_function!("MinimapWidget");
+        let response = ui.allocate_rect(self.rect, Sense::click_and_drag());
+        response.widget_info(|| WidgetInfo::labeled(WidgetType::Other, "Minimap of the changes"));
+        let rect = self.rect;
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+        // Rows are painted in strips a point high, so that long files take no longer to
+        // paint than short ones. A strip with both kinds of changes is split between them.
+        let strips = (rect.height().ceil() as usize).max(1);
+        let mut kinds = vec![Kinds::default(); strips];
+        let screen_rows = self.wrapped.screen_rows().max(1) as f32;
+        let mut mark = |rows: &[usize], set: fn(&mut Kinds)| {
+            for &row in rows {
+                let top = self.wrapped.start(row) as f32 / screen_rows * strips as f32;
+                let bottom = self.wrapped.start(row + 1) as f32 / screen_rows * strips as f32;
+                let last = (bottom.ceil() as usize).clamp(top as usize + 1, strips);
+                kinds[(top as usize).min(strips - 1)..last]
+                    .iter_mut()
+                    .for_each(set);
+            }
+        };
+        mark(&self.diff.neutral_indices, |kinds| kinds.context = true);
+        mark(&self.diff.insertion_indices, |kinds| kinds.insertion = true);
+        mark(&self.diff.deletion_indices, |kinds| kinds.deletion = true);
+
+        let strip_height = rect.height() / strips as f32;
+        for (i, kinds) in kinds.iter().enumerate() {
+            let top = rect.top() + i as f32 * strip_height;
+            let y_range = top..=top + strip_height;
+            let (insertion, deletion) = (self.theme.insertion, self.theme.deletion);
+            let (left, right) = match (kinds.insertion, kinds.deletion) {
+                (true, true) => (insertion, deletion),
+                (true, false) => (insertion, insertion),
+                (false, true) => (deletion, deletion),
+                (false, false) if kinds.context => (CONTEXT_COLOR, CONTEXT_COLOR),
+                (false, false) => continue,
+            };
+            let middle = rect.center().x;
+            let left_half = Rect::from_x_y_ranges(rect.left()..=middle, y_range.clone());
+            let right_half = Rect::from_x_y_ranges(middle..=rect.right(), y_range);
+            painter.rect_filled(left_half, 0.0, left);
+            painter.rect_filled(right_half, 0.0, right);
+        }
+
+        let viewport = Rect::from_x_y_ranges(
+            rect.x_range(),
+            rect.top() + self.viewport.start * rect.height()
+                ..=rect.top() + self.viewport.end * rect.height(),
+        );
+        painter.rect(
+            viewport,
+            0.0,
+            Color32::from_white_alpha(20),
+            Stroke::new(1.0, Color32::GRAY),
+        );
+
+        response.on_hover_text("Click to jump")
+    }
+}
+
+/// The kinds of lines in a strip of the minimap.
+#[derive(Clone, Copy, Default)]
+struct Kinds {
+    context: bool,
+    insertion: bool,
+    deletion: bool,
+}
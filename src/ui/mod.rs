@@ -1,24 +1,104 @@
-use std::{env, sync::mpsc::Sender};
+use std::{env, path::Path, sync::mpsc::Sender};
 
-use egui::{Align, Color32, Context, Layout, RichText, Window};
+use egui::{Align, Color32, Context, Grid, Id, Layout, RichText, ScrollArea, Ui, Window};
 
 use crate::{
-    data::{DiffType, Message},
+    annotations::Annotations,
+    data::{BlameState, FileHistory, FileHistoryState, LineBlame, Load, Message, ViewMode},
+    git::{self, CommitInfo, Diff},
+    search::SearchQuery,
     ui::{
-        diff_area::DiffAreaWidget, diff_type::DiffTypeSelection, files_area::FilesArea,
-        selection_area::SelectionAreaWidget, stats::StatsWidget,
+        about::AboutWindow, debug_overlay::DebugOverlay, diff_area::DiffAreaWidget,
+        diff_toolbar::DiffToolbarWidget, diff_type::DiffTypeSelection, files_area::FilesArea,
+        files_rail::FilesRailWidget, find_bar::FindBarWidget, gallery::GalleryWidget,
+        log_panel::LogPanel, paste_patch::PastePatchWindow, search_panel::SearchPanel,
+        selection_area::SelectionAreaWidget, settings::SettingsWindow, split_diff::SplitDiffWidget,
+        stats::StatsWidget, stats_overview::StatsOverviewWidget,
     },
     AppData, ControlData,
 };
 
+mod about;
 mod code;
+mod debug_overlay;
 mod diff_area;
+mod diff_toolbar;
 mod diff_type;
 mod files_area;
+mod files_rail;
+mod find_bar;
+mod gallery;
 mod line_numbers;
+mod log_panel;
+mod metrics;
+mod minimap;
 mod origins;
+mod paste_patch;
+mod search_panel;
 mod selection_area;
+mod settings;
+mod split_diff;
 mod stats;
+mod stats_overview;
+
+/// Narrowest window width at which a second file pane is shown.
+const MIN_MULTI_PANE_WIDTH: f32 = 1400.0;
+
+/// Row at the top of the file pane `pane`, as of the last frame.
+pub fn top_row(ctx: &Context, pane: usize) -> usize {
+    ctx.data(|d| d.get_temp(diff_area::top_row_id(pane)))
+        .unwrap_or(0)
+}
+
+/// Whether the end of the file pane `pane` is in view, as of the last frame.
+pub fn shows_end(ctx: &Context, pane: usize) -> bool {
+    ctx.data(|d| d.get_temp(diff_area::shows_end_id(pane)))
+        .unwrap_or(false)
+}
+
+/// Files in view in the gallery, as of the last frame.
+pub fn gallery_files(ctx: &Context) -> Vec<usize> {
+    ctx.data(|d| d.get_temp(gallery::visible_files_id()))
+        .unwrap_or_default()
+}
+
+/// Whether a text field that can be typed in has focus, as of the last frame, which turns
+/// the single-key shortcuts off. The code panes are text fields too, but only for selecting.
+pub fn is_typing(ctx: &Context) -> bool {
+    let focused = ctx.memory(|m| m.focus());
+    focused.is_some() && ctx.data(|d| d.get_temp::<Id>(text_field_id())) == focused
+}
+
+/// Notes that `response`, a text field that can be typed in, has focus if it does; see
+/// [`is_typing`].
+pub fn note_text_field(ui: &Ui, response: &egui::Response) {
+    if response.has_focus() {
+        ui.data_mut(|d| d.insert_temp(text_field_id(), response.id));
+    }
+}
+
+/// Where the text field that had focus last is kept.
+fn text_field_id() -> Id {
+    Id::new("focused text field")
+}
+
+/// A line a code pane's context menu asked to blame, since the panes can't send messages.
+pub fn take_blame_request(ctx: &Context) -> Option<(Diff, u32)> {
+    let request = ctx.data(|d| d.get_temp(code::blame_request_id()));
+    if request.is_some() {
+        ctx.data_mut(|d| d.remove::<(Diff, u32)>(code::blame_request_id()));
+    }
+    request
+}
+
+/// A line a code pane's context menu asked to open in the editor.
+pub fn take_editor_request(ctx: &Context) -> Option<(Diff, u32)> {
+    let request = ctx.data(|d| d.get_temp(code::editor_request_id()));
+    if request.is_some() {
+        ctx.data_mut(|d| d.remove::<(Diff, u32)>(code::editor_request_id()));
+    }
+    request
+}
 
 pub fn show(
     ctx: &Context,
@@ -26,6 +106,24 @@ pub fn show(
     control_data: &ControlData,
     sender: &Sender<Message>,
 ) {
+    if let Some(app_data) = app_data.as_ref().filter(|_| control_data.show_log) {
+        LogPanel::new(
+            sender.clone(),
+            app_data.log.clone(),
+            app_data.commit.as_ref().map(|commit| commit.id.clone()),
+        )
+        .show(ctx);
+    }
+    if control_data.show_search {
+        SearchPanel::new(
+            sender.clone(),
+            control_data.search_query.clone(),
+            control_data.search_results.clone(),
+            control_data.search_error.clone(),
+        )
+        .show(ctx);
+    }
+
     egui::CentralPanel::default().show(ctx, |ui| {
         puffin::profile_function!();
 
@@ -33,52 +131,637 @@ pub fn show(
             error_dialog(ctx, control_data, sender);
         }
 
+        if let Some(blame) = &control_data.blame {
+            blame_popup(ctx, blame, sender);
+        }
+
+        if let Some(history) = &control_data.file_history {
+            file_history_window(ctx, history, sender);
+        }
+
+        if control_data.show_settings {
+            SettingsWindow::new(
+                sender.clone(),
+                control_data.settings.clone(),
+                app_data
+                    .as_ref()
+                    .map(|app_data| app_data.project_path.clone()),
+            )
+            .show(ctx);
+        }
+
+        if control_data.show_paste_patch {
+            PastePatchWindow::new(sender.clone()).show(ctx);
+        }
+
+        if control_data.show_about {
+            AboutWindow::new(sender.clone(), control_data.last_error.clone()).show(ctx);
+        }
+
+        if control_data.show_debug_overlay {
+            DebugOverlay::new(app_data.clone()).show(ctx);
+        }
+
         if env::var("PROFILING").is_ok() {
             puffin_egui::profiler_window(ctx);
         }
 
-        ui.add(SelectionAreaWidget::new(app_data.clone(), sender.clone()));
+        let opening = control_data
+            .load
+            .as_ref()
+            .is_some_and(|load| load.status == Load::OPENING);
+        ui.add(SelectionAreaWidget::new(
+            app_data.clone(),
+            opening,
+            control_data.recent_projects.clone(),
+            control_data.settings.clone(),
+            control_data.view_mode,
+            sender.clone(),
+        ));
+
+        if let Some(load) = &control_data.load {
+            ui.horizontal(|ui| {
+                if ui.button("Cancel").clicked() {
+                    sender
+                        .send(Message::CancelLoad)
+                        .expect("Channel closed unexpectedly!");
+                }
+                // Until the files are counted, e.g. while the repository is opened.
+                match control_data.load_progress {
+                    Some((done, total)) => {
+                        ui.add(
+                            egui::ProgressBar::new(done as f32 / total.max(1) as f32)
+                                .text(format!("Listing changes: {} of {} files", done, total)),
+                        );
+                    }
+                    None => {
+                        ui.spinner();
+                        ui.label(RichText::new(load.status).color(Color32::GRAY));
+                    }
+                }
+            });
+        }
 
         if let Some(app_data) = app_data {
-            let diff_data = match control_data.diff_type {
-                DiffType::Modified => &app_data.modified_diff_data,
-                DiffType::Staged => &app_data.staged_diff_data,
-            };
+            let diff_data = app_data.diff_data(&control_data.diff_type);
 
             ui.separator();
-            ui.heading(RichText::new(app_data.project_path.clone()).color(Color32::WHITE));
+            ui.horizontal(|ui| {
+                let history = &control_data.history;
+                if ui
+                    .add_enabled(history.can_go_back(), egui::Button::new("◀"))
+                    .on_hover_text("Back (Alt+Left)")
+                    .clicked()
+                {
+                    sender
+                        .send(Message::NavigateBack)
+                        .expect("Channel closed unexpectedly!");
+                }
+                if ui
+                    .add_enabled(history.can_go_forward(), egui::Button::new("▶"))
+                    .on_hover_text("Forward (Alt+Right)")
+                    .clicked()
+                {
+                    sender
+                        .send(Message::NavigateForward)
+                        .expect("Channel closed unexpectedly!");
+                }
+                if let Some(old_dir) = &app_data.compared_directory {
+                    ui.heading(RichText::new(format!("{} →", old_dir)).strong());
+                }
+                ui.heading(RichText::new(app_data.project_path.clone()).strong());
+                if let Some(commit) = &app_data.commit {
+                    let mut hover = commit.id.clone();
+                    if commit.parent_count > 1 {
+                        hover.push_str("\nMerge commit, compared to its first parent");
+                    }
+                    ui.label(
+                        RichText::new(format!("{} {}", &commit.id[..7], commit.summary))
+                            .strong(),
+                    )
+                    .on_hover_text(hover);
+                    ui.label(RichText::new(format!("by {}", commit.author)).color(Color32::GRAY));
+                    // What a merge brought in from a branch is its diff against that parent.
+                    for parent in 2..=commit.parent_count {
+                        if ui
+                            .small_button(format!("Compare to parent {}", parent))
+                            .on_hover_text("Show the merge's changes relative to this parent")
+                            .clicked()
+                        {
+                            sender
+                                .send(Message::CompareRevisions(
+                                    format!("{}^{}", commit.id, parent),
+                                    commit.id.clone(),
+                                    false,
+                                    Vec::new(),
+                                ))
+                                .expect("Channel closed unexpectedly!");
+                        }
+                    }
+                    if ui.small_button("Back to working tree").clicked() {
+                        sender
+                            .send(Message::LoadDiff(app_data.project_path.clone().into()))
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+                if let Some(stash) = &app_data.stash {
+                    ui.label(
+                        RichText::new(format!(
+                            "Popping stash@{{{}}}: {}",
+                            stash.index, stash.message
+                        ))
+                        .strong(),
+                    )
+                    .on_hover_text("Compared to the working tree, without applying the stash");
+                    if ui.small_button("Back to working tree").clicked() {
+                        sender
+                            .send(Message::LoadDiff(app_data.project_path.clone().into()))
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+                if let Some(range) = &app_data.revisions {
+                    let short = |commit: &CommitInfo| commit.id[..7].to_string();
+                    let since = match range.merge_base {
+                        true => " (since the merge-base)",
+                        false => "",
+                    };
+                    ui.label(
+                        RichText::new(format!("{} → {}{}", range.from, range.to, since))
+                            .strong(),
+                    )
+                    .on_hover_text(format!(
+                        "{} {}\n{} {}",
+                        short(&range.from_commit),
+                        range.from_commit.summary,
+                        short(&range.to_commit),
+                        range.to_commit.summary
+                    ));
+                    if !range.paths.is_empty() {
+                        ui.label(
+                            RichText::new(format!("only {}", range.paths.join(", ")))
+                                .color(Color32::GRAY),
+                        );
+                    }
+                    if ui.small_button("Back to working tree").clicked() {
+                        sender
+                            .send(Message::LoadDiff(app_data.project_path.clone().into()))
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+                if let Some(worktree) = &app_data.worktree {
+                    ui.label(RichText::new(format!("({})", worktree)).color(Color32::GRAY))
+                        .on_hover_text("Active worktree");
+                }
+            });
             ui.separator();
 
-            let mut diff_type_selection =
-                DiffTypeSelection::new(sender.clone(), control_data.diff_type.clone());
-            diff_type_selection.ui(ui);
+            // Only a working tree has an index, so otherwise there is nothing staged to select.
+            if app_data.shows_working_tree() {
+                let mut diff_type_selection =
+                    DiffTypeSelection::new(sender.clone(), control_data.diff_type.clone());
+                diff_type_selection.ui(ui);
+            }
+
+            ui.horizontal(|ui| {
+                ui.add(StatsWidget::new(
+                    diff_data.stats.clone(),
+                    control_data.settings.theme(),
+                ));
+                let viewed = control_data.viewed_files(&control_data.diff_type);
+                let viewed_count = diff_data
+                    .diffs
+                    .iter()
+                    .filter(|diff| viewed.contains(&diff.file_name()))
+                    .count();
+                if viewed_count > 0 && viewed_count == diff_data.diffs.len() {
+                    ui.label(RichText::new("All files reviewed ✔").color(Color32::GREEN));
+                } else if viewed_count > 0 {
+                    ui.label(
+                        RichText::new(format!(
+                            "{}/{} viewed",
+                            viewed_count,
+                            diff_data.diffs.len()
+                        ))
+                        .color(Color32::GRAY),
+                    );
+                }
+                if !diff_data.ignored.is_empty()
+                    && ui
+                        .selectable_label(
+                            control_data.show_ignored,
+                            RichText::new(format!("Show ignored ({})", diff_data.ignored.len()))
+                                .strong(),
+                        )
+                        .on_hover_text("Files matching the ignored paths in the settings")
+                        .clicked()
+                {
+                    sender
+                        .send(Message::ToggleShowIgnored)
+                        .expect("Channel closed unexpectedly!");
+                }
+                if diff_data.diffs.is_empty() {
+                    return;
+                }
+                if ui
+                    .button(RichText::new("Copy all as unified diff").strong())
+                    .clicked()
+                {
+                    sender
+                        .send(Message::CopyUnifiedDiff)
+                        .expect("Channel closed unexpectedly!");
+                }
+                if ui
+                    .button(RichText::new("Save patch…").strong())
+                    .on_hover_text("Save the changes to all files as a patch git apply takes")
+                    .clicked()
+                {
+                    let path = rfd::FileDialog::new()
+                        .add_filter("Patch", &["patch", "diff"])
+                        .set_file_name("changes.patch")
+                        .save_file();
+                    if let Some(path) = path {
+                        sender
+                            .send(Message::SavePatch(path))
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+                if ui
+                    .button(RichText::new("Copy stat summary").strong())
+                    .on_hover_text("A line per file with its changed lines, like git diff --stat")
+                    .clicked()
+                {
+                    sender
+                        .send(Message::CopyText(git::stat_summary(&diff_data.diffs)))
+                        .expect("Channel closed unexpectedly!");
+                }
+                if ui
+                    .selectable_label(
+                        control_data.gallery,
+                        RichText::new("All files").strong(),
+                    )
+                    .on_hover_text("Show every file's diff in one scrolling list")
+                    .clicked()
+                {
+                    sender
+                        .send(Message::ToggleGallery)
+                        .expect("Channel closed unexpectedly!");
+                }
+                if ui
+                    .selectable_label(
+                        control_data.stats_only,
+                        RichText::new("Stats only").strong(),
+                    )
+                    .on_hover_text("Summarize every file without showing diffs")
+                    .clicked()
+                {
+                    sender
+                        .send(Message::ToggleStatsOnly)
+                        .expect("Channel closed unexpectedly!");
+                }
+
+                ui.separator();
+                if ui
+                    .button(RichText::new("Load annotations…").strong())
+                    .on_hover_text("Show lint or test results next to the changed lines,\none per line as `path:line: severity: message`")
+                    .clicked()
+                {
+                    if let Some(path) = rfd::FileDialog::new().pick_file() {
+                        sender
+                            .send(Message::LoadAnnotations(path))
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+                if !control_data.annotations.is_empty() {
+                    ui.label(
+                        RichText::new(format!("{} annotations", control_data.annotations.len()))
+                            .color(Color32::GRAY),
+                    );
+                    if ui.small_button("Clear").clicked() {
+                        sender
+                            .send(Message::UpdateAnnotations(Annotations::default()))
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+            });
+
+            if app_data.shows_working_tree() {
+                ui.horizontal(|ui| {
+                    if let Some(branch_status) = &app_data.branch_status {
+                        ui.label(RichText::new(branch_status.label_text()).strong());
+                        ui.separator();
+                    }
+                    ui.label(RichText::new("HEAD").color(Color32::GRAY))
+                        .on_hover_text("The commit the changes are compared with");
+                    let Some(head) = &app_data.head else {
+                        ui.label(RichText::new("No commits yet").color(Color32::GRAY));
+                        return;
+                    };
+                    ui.label(RichText::new(&head.id[..7]).monospace().strong())
+                        .on_hover_text(&head.id);
+                    ui.label(RichText::new(&head.summary).strong());
+                    ui.label(
+                        RichText::new(format!("by {}, {}", head.author, head.date_text()))
+                            .color(Color32::GRAY),
+                    );
+                });
+            }
 
-            ui.add(StatsWidget::new(diff_data.stats.clone()));
+            if !diff_data.skipped.is_empty() {
+                let skipped = diff_data
+                    .skipped
+                    .iter()
+                    .map(|file| format!("{}: {}", file.path, file.reason))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+                ui.label(
+                    RichText::new(format!(
+                        "⚠ {} files couldn't be read and were skipped",
+                        diff_data.skipped.len()
+                    ))
+                    .color(Color32::YELLOW),
+                )
+                .on_hover_text(skipped);
+            }
 
             if diff_data.diffs.is_empty() {
+                ui.separator();
+                ui.label(
+                    RichText::new(format!(
+                        "No {} changes",
+                        control_data.diff_type.label_text().to_lowercase()
+                    ))
+                    .color(Color32::GRAY),
+                );
                 return;
             }
 
             ui.separator();
 
-            ui.with_layout(Layout::left_to_right(Align::LEFT), |ui| {
-                let mut files_area = FilesArea::new(
+            if control_data.stats_only {
+                ui.add(StatsOverviewWidget::new(
                     diff_data.clone(),
-                    control_data.selected_diff_index,
+                    control_data.settings.theme(),
                     sender.clone(),
-                );
+                ));
+                return;
+            }
+
+            ui.with_layout(Layout::left_to_right(Align::LEFT), |ui| {
+                if control_data.files_collapsed {
+                    ui.add(FilesRailWidget::new(diff_data.diffs.len(), sender.clone()));
+                } else {
+                    let other_changes = control_data.diff_type.other().map(|other_type| {
+                        let other_indices = app_data
+                            .diff_data(&other_type)
+                            .diffs
+                            .iter()
+                            .enumerate()
+                            .map(|(i, diff)| (diff.file_name(), i))
+                            .collect();
+                        (other_type, other_indices)
+                    });
+                    let mut files_area = FilesArea::new(
+                        diff_data.clone(),
+                        control_data,
+                        other_changes,
+                        sender.clone(),
+                    );
 
-                files_area.ui(ui);
+                    files_area.ui(ui);
+                }
                 ui.separator();
 
-                if let Some(diff) = diff_data.diffs.get(control_data.selected_diff_index) {
-                    ui.add(DiffAreaWidget::new(diff.clone()));
+                if control_data.gallery {
+                    ui.add(GalleryWidget::new(
+                        diff_data.clone(),
+                        control_data.selected_diff_index,
+                        control_data.settings.for_repo(&app_data.project_path),
+                        control_data.scroll_to_row,
+                        highlighted_query(control_data),
+                        app_data.permalink.clone(),
+                        sender.clone(),
+                    ));
+                    return;
+                }
+
+                let mut panes = vec![control_data.selected_diff_index];
+                if let Some(i) = control_data.secondary_diff_index {
+                    if control_data.settings.multi_pane
+                        && ui.available_width() >= MIN_MULTI_PANE_WIDTH
+                    {
+                        panes.push(i);
+                    }
                 }
+
+                ui.columns(panes.len(), |columns| {
+                    for (pane, (ui, diff_index)) in columns.iter_mut().zip(panes).enumerate() {
+                        if let Some(diff) = diff_data.diffs.get(diff_index) {
+                            ui.add(DiffToolbarWidget::new(
+                                diff_index,
+                                diff.display_name(Path::new("")),
+                                diff.sizes_text(),
+                                pane > 0,
+                                sender.clone(),
+                            ));
+                            if pane == 0 && control_data.show_find {
+                                ui.add(FindBarWidget::new(
+                                    diff.clone(),
+                                    control_data.find_query.clone(),
+                                    control_data.find_match,
+                                    sender.clone(),
+                                ));
+                            } else if pane == 0 {
+                                FindBarWidget::closed(ui);
+                            }
+                            if let Some(error) = diff.load_error() {
+                                load_failed(ui, error, diff_index, sender);
+                                continue;
+                            }
+                            if control_data.view_mode == ViewMode::Split {
+                                ui.add(SplitDiffWidget::new(
+                                    diff.clone(),
+                                    pane,
+                                    control_data.settings.for_repo(&app_data.project_path),
+                                    control_data.scroll_to_row.filter(|_| pane == 0),
+                                    highlighted_query(control_data),
+                                ));
+                                continue;
+                            }
+                            ui.add(DiffAreaWidget::new(
+                                diff.clone(),
+                                pane,
+                                control_data.settings.for_repo(&app_data.project_path),
+                                control_data.scroll_to_row.filter(|_| pane == 0),
+                                highlighted_query(control_data),
+                                app_data.permalink.clone(),
+                                control_data.annotations.for_file(&diff.file_name()),
+                            ));
+                        }
+                    }
+                });
             });
         }
     });
 }
 
+/// The query whose matches are highlighted in the code: the one found in the file while
+/// the find bar is open, or else the one searched for in all files.
+fn highlighted_query(control_data: &ControlData) -> SearchQuery {
+    if control_data.show_find && !control_data.find_query.text.is_empty() {
+        return control_data.find_query.clone();
+    }
+    match control_data.show_search {
+        true => control_data.search_query.clone(),
+        false => SearchQuery::default(),
+    }
+}
+
+/// Says why a diff couldn't be loaded instead of showing it, with a way to try again.
+fn load_failed(ui: &mut Ui, error: &str, diff_index: usize, sender: &Sender<Message>) {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(error).color(Color32::LIGHT_RED));
+        if ui.button("Retry").clicked() {
+            sender
+                .send(Message::RetryDiff(diff_index))
+                .expect("Channel closed unexpectedly!");
+        }
+    });
+}
+
+fn blame_popup(ctx: &Context, blame: &LineBlame, sender: &Sender<Message>) {
+    Window::new(format!("Blame {}:{}", blame.file_name, blame.line_no))
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            match &blame.state {
+                BlameState::Loading => {
+                    ui.spinner();
+                }
+                BlameState::Uncommitted => {
+                    ui.label("Not committed yet");
+                }
+                BlameState::Commit(commit) => {
+                    ui.label(RichText::new(&commit.summary).strong());
+                    ui.label(format!("{} by {}", &commit.id[..7], commit.author));
+                    if ui.button("Show commit").clicked() {
+                        sender
+                            .send(Message::ViewCommit(commit.id.clone()))
+                            .expect("Channel closed unexpectedly!");
+                        sender
+                            .send(Message::CloseBlame)
+                            .expect("Channel closed unexpectedly!");
+                    }
+                }
+                BlameState::Failed(error) => {
+                    ui.label(RichText::new(error).color(Color32::LIGHT_RED));
+                }
+            }
+            if ui.button("Close").clicked() {
+                sender
+                    .send(Message::CloseBlame)
+                    .expect("Channel closed unexpectedly!");
+            }
+        });
+}
+
+/// Lists the commits that changed a file, to compare the file as of two of them.
+fn file_history_window(ctx: &Context, history: &FileHistory, sender: &Sender<Message>) {
+    Window::new(format!("History of {}", history.file_name))
+        .collapsible(false)
+        .default_height(400.0)
+        .show(ctx, |ui| {
+            let mut follow_renames = history.follow_renames;
+            if ui
+                .checkbox(&mut follow_renames, "Follow renames")
+                .on_hover_text("List the commits from before the file was moved too")
+                .changed()
+            {
+                sender
+                    .send(Message::FollowRenames(follow_renames))
+                    .expect("Channel closed unexpectedly!");
+            }
+            match &history.state {
+                FileHistoryState::Loading => {
+                    ui.spinner();
+                }
+                FileHistoryState::Failed(error) => {
+                    ui.label(RichText::new(error).color(Color32::LIGHT_RED));
+                }
+                FileHistoryState::Commits(log) if log.commits.is_empty() => {
+                    ui.label(RichText::new("No commits changed this file").color(Color32::GRAY));
+                }
+                FileHistoryState::Commits(log) => {
+                    let commits = &log.commits;
+                    // The first path is the file's own.
+                    let old_paths = &log.paths[1..];
+                    if !old_paths.is_empty() {
+                        ui.label(
+                            RichText::new(format!("Previously {}", old_paths.join(", ")))
+                                .color(Color32::GRAY),
+                        );
+                    }
+                    // The newest commit and the one before it, until others are picked.
+                    let picked_id = Id::new("file history").with(&history.file_name);
+                    let (mut from, mut to) = ui
+                        .data(|d| d.get_temp::<(usize, usize)>(picked_id))
+                        .unwrap_or((1.min(commits.len() - 1), 0));
+                    ui.label(
+                        RichText::new("The file as of the \"From\" commit, and as of \"To\"")
+                            .color(Color32::GRAY),
+                    );
+                    ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        Grid::new("file history").striped(true).show(ui, |ui| {
+                            ui.label(RichText::new("From").strong());
+                            ui.label(RichText::new("To").strong());
+                            ui.label("");
+                            ui.end_row();
+                            for (i, commit) in commits.iter().enumerate() {
+                                ui.radio_value(&mut from, i, "");
+                                ui.radio_value(&mut to, i, "");
+                                ui.label(
+                                    RichText::new(format!(
+                                        "{} {}",
+                                        &commit.id[..7],
+                                        commit.summary
+                                    ))
+                                    .monospace(),
+                                )
+                                .on_hover_text(format!(
+                                    "{}, {}",
+                                    commit.author,
+                                    commit.date_text()
+                                ));
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if ui
+                        .add_enabled(from != to, egui::Button::new("Compare"))
+                        .clicked()
+                    {
+                        sender
+                            .send(Message::CompareRevisions(
+                                commits[from].id.clone(),
+                                commits[to].id.clone(),
+                                false,
+                                log.paths.clone(),
+                            ))
+                            .expect("Channel closed unexpectedly!");
+                        sender
+                            .send(Message::CloseFileHistory)
+                            .expect("Channel closed unexpectedly!");
+                    }
+                    ui.data_mut(|d| d.insert_temp(picked_id, (from, to)));
+                }
+            }
+            if ui.button("Close").clicked() {
+                sender
+                    .send(Message::CloseFileHistory)
+                    .expect("Channel closed unexpectedly!");
+            }
+        });
+}
+
 pub fn error_dialog(ctx: &Context, control_data: &ControlData, sender: &Sender<Message>) {
     Window::new("Error")
         .collapsible(false)
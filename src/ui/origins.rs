@@ -3,19 +3,40 @@ use std::ops::Range;
 use egui::{
     text::LayoutJob,
     util::cache::{ComputerMut, FrameCache},
-    Color32, Context, FontFamily, FontId, Response, TextEdit, TextFormat, Ui, Widget,
+    Color32, Context, FontId, Response, TextEdit, TextFormat, Ui, Widget,
 };
 
-use crate::git::Diff;
+use crate::{
+    git::{Diff, LineKind},
+    settings::Theme,
+    ui::metrics::DiffMetrics,
+    wrap,
+};
 
 pub struct OriginsWidget {
     diff: Diff,
     range: Range<usize>,
+    metrics: DiffMetrics,
+    /// Screen rows each row of the code next to it wraps onto after its first.
+    continuations: Vec<usize>,
+    theme: Theme,
 }
 
 impl OriginsWidget {
-    pub fn new(diff: Diff, range: Range<usize>) -> OriginsWidget {
-        OriginsWidget { diff, range }
+    pub fn new(
+        diff: Diff,
+        range: Range<usize>,
+        metrics: DiffMetrics,
+        continuations: Vec<usize>,
+        theme: Theme,
+    ) -> OriginsWidget {
+        OriginsWidget {
+            diff,
+            range,
+            metrics,
+            continuations,
+            theme,
+        }
     }
 }
 
@@ -23,8 +44,10 @@ impl Widget for OriginsWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         puffin::profile_function!("OriginsWidget");
 
+        let font_id = self.metrics.font_id();
         let mut layouter = |ui: &egui::Ui, string: &str, _wrap_width: f32| {
-            let layout_job: egui::text::LayoutJob = origins_highlight(ui.ctx(), string);
+            let layout_job: egui::text::LayoutJob =
+                origins_highlight(ui.ctx(), string, &font_id, self.theme);
             ui.fonts(|f| f.layout_job(layout_job))
         };
 
@@ -32,12 +55,13 @@ impl Widget for OriginsWidget {
         let Range { start, end } = self.range;
         let end = std::cmp::min(end, lines.len());
 
-        let mut content = lines[start..end].join("\n");
+        let mut content = wrap::pad_continuations(&lines[start..end], &self.continuations);
         ui.add(
             TextEdit::multiline(&mut content)
                 .desired_width(0.0)
                 .frame(false)
                 .interactive(false)
+                .margin(self.metrics.margin)
                 .layouter(&mut layouter),
         )
     }
@@ -45,43 +69,49 @@ impl Widget for OriginsWidget {
 
 type OriginsHighlightCache = FrameCache<LayoutJob, OriginsLayoutHandler>;
 
-fn origins_highlight(ctx: &Context, text: &str) -> LayoutJob {
-    impl ComputerMut<&str, LayoutJob> for OriginsLayoutHandler {
-        fn compute(&mut self, text: &str) -> LayoutJob {
-            puffin::profile_function!();
-            OriginsLayoutHandler::layout_job(text)
-        }
-    }
+fn origins_highlight(ctx: &Context, text: &str, font_id: &FontId, theme: Theme) -> LayoutJob {
+    ctx.memory_mut(|mem| {
+        mem.caches
+            .cache::<OriginsHighlightCache>()
+            .get((text, font_id, theme))
+    })
+}
 
-    ctx.memory_mut(|mem| mem.caches.cache::<OriginsHighlightCache>().get(text))
+impl ComputerMut<(&str, &FontId, Theme), LayoutJob> for OriginsLayoutHandler {
+    fn compute(&mut self, (text, font_id, theme): (&str, &FontId, Theme)) -> LayoutJob {
+        puffin::profile_function!();
+        OriginsLayoutHandler::layout_job(text, font_id, theme)
+    }
 }
+
 #[derive(Debug, Default)]
 struct OriginsLayoutHandler {}
 
 impl OriginsLayoutHandler {
-    fn layout_job(text: &str) -> LayoutJob {
+    fn layout_job(text: &str, font_id: &FontId, theme: Theme) -> LayoutJob {
         puffin::profile_function!();
 
         let mut job = LayoutJob::default();
         job.wrap.max_width = f32::INFINITY;
 
-        let insertion_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::GREEN);
-        let deletion_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::RED);
-        let neutral_format =
-            TextFormat::simple(FontId::new(12.0, FontFamily::Monospace), Color32::WHITE);
+        let mut insertion_format = TextFormat::simple(font_id.clone(), theme.insertion);
+        let mut deletion_format = TextFormat::simple(font_id.clone(), theme.deletion);
+        if theme.emphasized_origins {
+            insertion_format.background = theme.insertion.linear_multiply(0.25);
+            deletion_format.background = theme.deletion.linear_multiply(0.25);
+        }
+        let neutral_format = TextFormat::simple(font_id.clone(), theme.context);
+        let marker_format = TextFormat::simple(font_id.clone(), Color32::GRAY);
 
         for line in text.split('\n') {
-            if line.contains('+') {
-                job.append(format!("{line}\n").as_str(), 0.0, insertion_format.clone());
-            }
-            if line.contains('-') {
-                job.append(format!("{line}\n").as_str(), 0.0, deletion_format.clone());
-            }
-            if !line.contains('+') && !line.contains('-') {
-                job.append(format!("{line}\n").as_str(), 0.0, neutral_format.clone());
-            }
+            // Header rows have a blank origin, which renders like context.
+            let format = match line.chars().next().map(LineKind::from_origin) {
+                Some(LineKind::Insertion) => &insertion_format,
+                Some(LineKind::Deletion) => &deletion_format,
+                Some(LineKind::EofMarker) => &marker_format,
+                Some(LineKind::Context | LineKind::Header) | None => &neutral_format,
+            };
+            job.append(format!("{line}\n").as_str(), 0.0, format.clone());
         }
 
         job
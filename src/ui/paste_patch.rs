@@ -0,0 +1,55 @@
+use std::sync::mpsc::Sender;
+
+use egui::{Context, Id, ScrollArea, TextEdit, Window};
+
+use crate::data::Message;
+
+pub struct PastePatchWindow {
+    sender: Sender<Message>,
+}
+
+impl PastePatchWindow {
+    pub fn new(sender: Sender<Message>) -> PastePatchWindow {
+        PastePatchWindow { sender }
+    }
+}
+
+impl PastePatchWindow {
+    pub fn show(&self, ctx: &Context) {
+        puffin::profile_function!("PastePatchWindow");
+        let mut open = true;
+        let text_id = Id::new("pasted patch");
+        let mut text = ctx.data(|d| d.get_temp::<String>(text_id).unwrap_or_default());
+
+        Window::new("Paste diff")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label("Paste a patch in unified diff format, e.g. from `git diff` or an email:");
+                ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    let entry = ui.add(
+                        TextEdit::multiline(&mut text)
+                            .code_editor()
+                            .desired_rows(16)
+                            .desired_width(f32::INFINITY),
+                    );
+                    super::note_text_field(ui, &entry);
+                });
+
+                if ui.button("View").clicked() {
+                    self.sender
+                        .send(Message::ViewPatch(text.clone()))
+                        .expect("Channel closed unexpectedly!");
+                }
+            });
+
+        ctx.data_mut(|d| d.insert_temp(text_id, text));
+
+        if !open {
+            self.sender
+                .send(Message::TogglePastePatch)
+                .expect("Channel closed unexpectedly!");
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::mpsc::Sender;
+
+use egui::{CollapsingHeader, Color32, Context, RichText, ScrollArea, SidePanel};
+
+use crate::{
+    data::Message,
+    search::{FileMatches, SearchQuery},
+};
+
+pub struct SearchPanel {
+    sender: Sender<Message>,
+    query: SearchQuery,
+    results: Vec<FileMatches>,
+    error: Option<String>,
+}
+
+impl SearchPanel {
+    pub fn new(
+        sender: Sender<Message>,
+        query: SearchQuery,
+        results: Vec<FileMatches>,
+        error: Option<String>,
+    ) -> SearchPanel {
+        SearchPanel {
+            sender,
+            query,
+            results,
+            error,
+        }
+    }
+}
+
+impl SearchPanel {
+    pub fn show(&mut self, ctx: &Context) {
+        puffin::profile_function!("SearchPanel");
+        SidePanel::right("search panel")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.heading(RichText::new("Search all files").strong());
+
+                let changed = ui
+                    .horizontal(|ui| {
+                        let text = ui.text_edit_singleline(&mut self.query.text);
+                        super::note_text_field(ui, &text);
+                        let case = ui
+                            .checkbox(&mut self.query.case_sensitive, "Aa")
+                            .on_hover_text("Match case");
+                        let regex = ui
+                            .checkbox(&mut self.query.regex, ".*")
+                            .on_hover_text("Regular expression");
+                        text.changed() || case.changed() || regex.changed()
+                    })
+                    .inner;
+                if changed {
+                    self.sender
+                        .send(Message::SearchAllFiles(self.query.clone()))
+                        .expect("Channel closed unexpectedly!");
+                }
+
+                if let Some(error) = &self.error {
+                    ui.label(RichText::new(error).color(Color32::RED).monospace());
+                }
+
+                let match_count: usize = self.results.iter().map(|r| r.matches.len()).sum();
+                ui.label(
+                    RichText::new(format!(
+                        "{} matches in {} files",
+                        match_count,
+                        self.results.len()
+                    ))
+                    .color(Color32::GRAY),
+                );
+                ui.separator();
+
+                ScrollArea::vertical()
+                    .id_source("search results")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for file_matches in &self.results {
+                            CollapsingHeader::new(format!(
+                                "{} ({})",
+                                file_matches.file_name,
+                                file_matches.matches.len()
+                            ))
+                            .id_source(file_matches.diff_index)
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                for line_match in &file_matches.matches {
+                                    let text = RichText::new(format!(
+                                        "{:>5} {} {}",
+                                        line_match.line_no,
+                                        line_match.origin,
+                                        line_match.content.trim()
+                                    ))
+                                    .monospace();
+
+                                    if ui.selectable_label(false, text).clicked() {
+                                        self.sender
+                                            .send(Message::JumpToRow(
+                                                file_matches.diff_index,
+                                                line_match.row,
+                                            ))
+                                            .expect("Channel closed unexpectedly!");
+                                    }
+                                }
+                            });
+                        }
+                    });
+            });
+    }
+}
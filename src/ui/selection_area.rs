@@ -1,17 +1,75 @@
-use std::{path::PathBuf, sync::mpsc::Sender};
+use std::sync::mpsc::Sender;
 
-use egui::{Color32, Response, RichText, Ui, Widget};
+use egui::{Color32, DragValue, Id, Key, Response, RichText, TextEdit, Ui, Widget};
 
-use crate::{data::Message, AppData};
+use crate::{
+    data::{Message, ViewMode},
+    git::Whitespace,
+    session::RecentProjects,
+    settings::Settings,
+    AppData,
+};
 
 pub struct SelectionAreaWidget {
     app_data: Option<AppData>,
+    /// Whether a repository is being opened.
+    opening: bool,
+    recent_projects: RecentProjects,
+    settings: Settings,
+    view_mode: ViewMode,
     sender: Sender<Message>,
 }
 
 impl SelectionAreaWidget {
-    pub fn new(app_data: Option<AppData>, sender: Sender<Message>) -> SelectionAreaWidget {
-        SelectionAreaWidget { app_data, sender }
+    pub fn new(
+        app_data: Option<AppData>,
+        opening: bool,
+        recent_projects: RecentProjects,
+        settings: Settings,
+        view_mode: ViewMode,
+        sender: Sender<Message>,
+    ) -> SelectionAreaWidget {
+        SelectionAreaWidget {
+            app_data,
+            opening,
+            recent_projects,
+            settings,
+            view_mode,
+            sender,
+        }
+    }
+}
+
+impl SelectionAreaWidget {
+    fn recent_projects_menu(&self, ui: &mut Ui) {
+        for path in &self.recent_projects.paths {
+            if !self.recent_projects.failed.contains(path) {
+                if ui.button(path).clicked() {
+                    self.sender
+                        .send(Message::LoadDiff(path.into()))
+                        .expect("Channel closed unexpectedly!");
+                    ui.close_menu();
+                }
+                continue;
+            }
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(path).color(Color32::GRAY))
+                    .on_hover_text("Couldn't be opened");
+                if ui.small_button("Remove").clicked() {
+                    self.sender
+                        .send(Message::RemoveRecentProject(path.clone()))
+                        .expect("Channel closed unexpectedly!");
+                }
+            });
+        }
+        ui.separator();
+        if ui.button("Clear recent").clicked() {
+            self.sender
+                .send(Message::ClearRecentProjects)
+                .expect("Channel closed unexpectedly!");
+            ui.close_menu();
+        }
     }
 }
 
@@ -19,32 +77,271 @@ impl Widget for SelectionAreaWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         puffin::profile_function!("SelectionAreaWidget");
         ui.horizontal(|ui| {
-            ui.heading(RichText::new("Diff Viewer").color(Color32::WHITE));
+            ui.heading(RichText::new("Diff Viewer").strong());
             ui.separator();
 
+            let open = match self.opening {
+                true => "Opening…",
+                false => "Open",
+            };
+            if ui.button(RichText::new(open).strong()).clicked() {
+                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                    self.sender
+                        .send(Message::LoadDiff(path))
+                        .expect("Channel closed unexpectedly!");
+                }
+            }
+            ui.add_enabled_ui(!self.recent_projects.paths.is_empty(), |ui| {
+                ui.menu_button(RichText::new("⏷").strong(), |ui| {
+                    self.recent_projects_menu(ui)
+                })
+                .response
+                .on_hover_text("Recent repositories");
+            });
+
             if ui
-                .button(RichText::new("Open").color(Color32::WHITE))
+                .button(RichText::new("Compare folders…").strong())
+                .on_hover_text("Compare two directories, which don't have to be git repositories")
                 .clicked()
             {
-                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                let old_dir = rfd::FileDialog::new().set_title("Old folder").pick_folder();
+                let new_dir = old_dir
+                    .as_ref()
+                    .and_then(|_| rfd::FileDialog::new().set_title("New folder").pick_folder());
+                if let (Some(old_dir), Some(new_dir)) = (old_dir, new_dir) {
                     self.sender
-                        .send(Message::LoadDiff(path))
+                        .send(Message::CompareDirectories(old_dir, new_dir))
                         .expect("Channel closed unexpectedly!");
                 }
             }
 
             if ui
-                .button(RichText::new("Refresh").color(Color32::WHITE))
+                .button(RichText::new("Paste diff").strong())
+                .on_hover_text("View a patch without a repository")
                 .clicked()
             {
-                if let Some(app_data) = self.app_data {
+                self.sender
+                    .send(Message::TogglePastePatch)
+                    .expect("Channel closed unexpectedly!");
+            }
+
+            // Commits are looked up in the open repository.
+            let in_repository = self
+                .app_data
+                .as_ref()
+                .is_some_and(|app_data| app_data.compared_directory.is_none() && !app_data.pasted);
+            let revspec_id = Id::new("commit revspec");
+            let mut revspec = ui.data(|d| d.get_temp::<String>(revspec_id).unwrap_or_default());
+            let entry = ui.add_enabled(
+                in_repository,
+                TextEdit::singleline(&mut revspec)
+                    .hint_text("Commit SHA")
+                    .desired_width(90.0),
+            );
+            super::note_text_field(ui, &entry);
+            let submitted = entry.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+            if ui
+                .add_enabled(
+                    in_repository && !revspec.trim().is_empty(),
+                    egui::Button::new(RichText::new("Show commit").strong()),
+                )
+                .on_hover_text("Show what a commit changed compared to its first parent")
+                .clicked()
+                || (submitted && !revspec.trim().is_empty())
+            {
+                self.sender
+                    .send(Message::ViewCommit(revspec.clone()))
+                    .expect("Channel closed unexpectedly!");
+            }
+            ui.data_mut(|d| d.insert_temp(revspec_id, revspec));
+
+            ui.add_enabled_ui(in_repository, |ui| {
+                ui.menu_button(RichText::new("Compare…").strong(), |ui| {
+                    let range_id = Id::new("compared revisions");
+                    let (mut from, mut to, mut merge_base) = ui.data(|d| {
+                        d.get_temp::<(String, String, bool)>(range_id)
+                            .unwrap_or_else(|| ("HEAD~1".to_string(), "HEAD".to_string(), false))
+                    });
+                    ui.label(RichText::new("Changes between two revisions").color(Color32::GRAY));
+                    ui.horizontal(|ui| {
+                        let from = ui.add(
+                            TextEdit::singleline(&mut from)
+                                .hint_text("From")
+                                .desired_width(90.0),
+                        );
+                        super::note_text_field(ui, &from);
+                        ui.label("→");
+                        let to = ui.add(
+                            TextEdit::singleline(&mut to)
+                                .hint_text("To")
+                                .desired_width(90.0),
+                        );
+                        super::note_text_field(ui, &to);
+                    });
+                    ui.checkbox(&mut merge_base, "From the merge-base")
+                        .on_hover_text(
+                            "Only what \"To\" changed since it branched off \"From\", like a \
+                             pull request",
+                        );
+                    if ui
+                        .add_enabled(
+                            !from.trim().is_empty() && !to.trim().is_empty(),
+                            egui::Button::new("Compare"),
+                        )
+                        .on_hover_text("Commit SHAs, branches, tags or e.g. HEAD~3")
+                        .clicked()
+                    {
+                        self.sender
+                            .send(Message::CompareRevisions(
+                                from.clone(),
+                                to.clone(),
+                                merge_base,
+                                Vec::new(),
+                            ))
+                            .expect("Channel closed unexpectedly!");
+                        ui.close_menu();
+                    }
+                    ui.data_mut(|d| d.insert_temp(range_id, (from, to, merge_base)));
+                });
+            });
+
+            let stashes = self
+                .app_data
+                .as_ref()
+                .filter(|_| in_repository)
+                .map(|app_data| app_data.stashes.clone())
+                .unwrap_or_default();
+            ui.add_enabled_ui(!stashes.is_empty(), |ui| {
+                ui.menu_button(RichText::new("Stashes").strong(), |ui| {
+                    ui.label(
+                        RichText::new("What popping a stash would change").color(Color32::GRAY),
+                    );
+                    for stash in stashes {
+                        if ui
+                            .button(format!("stash@{{{}}}: {}", stash.index, stash.message))
+                            .clicked()
+                        {
+                            self.sender
+                                .send(Message::CompareStash(stash.index))
+                                .expect("Channel closed unexpectedly!");
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+
+            // A pasted patch has nothing to reload.
+            let can_refresh = !self
+                .app_data
+                .as_ref()
+                .is_some_and(|app_data| app_data.pasted);
+            if ui
+                .add_enabled(
+                    can_refresh,
+                    egui::Button::new(RichText::new("Refresh").strong()),
+                )
+                .clicked()
+            {
+                self.sender
+                    .send(Message::Refresh)
+                    .expect("Channel closed unexpectedly!");
+            }
+
+            // Kept while dragging, so the diffs are only listed again once it stops.
+            let dragged_id = Id::new("dragged context lines");
+            let mut context_lines = ui
+                .data(|d| d.get_temp::<u32>(dragged_id))
+                .unwrap_or(self.settings.context_lines);
+            ui.label(RichText::new("Context").strong());
+            let context = ui
+                .add(DragValue::new(&mut context_lines).clamp_range(0..=1000))
+                .on_hover_text("Unchanged lines around changes");
+            if context.dragged() {
+                ui.data_mut(|d| d.insert_temp(dragged_id, context_lines));
+            } else {
+                ui.data_mut(|d| d.remove::<u32>(dragged_id));
+                if context_lines != self.settings.context_lines {
+                    let settings = Settings {
+                        context_lines,
+                        ..self.settings.clone()
+                    };
                     self.sender
-                        .send(Message::LoadDiff(PathBuf::from(
-                            app_data.project_path.clone(),
-                        )))
+                        .send(Message::UpdateSettings(Box::new(settings)))
                         .expect("Channel closed unexpectedly!");
                 }
             }
+
+            let whitespace_text = match self.settings.whitespace {
+                Whitespace::Compare => "Whitespace",
+                _ => "Whitespace (ignored)",
+            };
+            ui.menu_button(RichText::new(whitespace_text).strong(), |ui| {
+                for whitespace in [
+                    Whitespace::Compare,
+                    Whitespace::IgnoreAmount,
+                    Whitespace::IgnoreAll,
+                ] {
+                    if ui
+                        .radio(
+                            self.settings.whitespace == whitespace,
+                            whitespace.label_text(),
+                        )
+                        .on_hover_text(whitespace.hover_text())
+                        .clicked()
+                    {
+                        let settings = Settings {
+                            whitespace,
+                            ..self.settings.clone()
+                        };
+                        self.sender
+                            .send(Message::UpdateSettings(Box::new(settings)))
+                            .expect("Channel closed unexpectedly!");
+                        ui.close_menu();
+                    }
+                }
+            });
+
+            let other_mode = match self.view_mode {
+                ViewMode::Unified => ViewMode::Split,
+                ViewMode::Split => ViewMode::Unified,
+            };
+            if ui
+                .button(RichText::new(other_mode.label_text()).strong())
+                .on_hover_text("Switch how the lines of files are laid out")
+                .clicked()
+            {
+                self.sender
+                    .send(Message::ChangeViewMode(other_mode))
+                    .expect("Channel closed unexpectedly!");
+            }
+
+            if ui
+                .button(RichText::new("Log").strong())
+                .on_hover_text("Recent commits, which can be shown by clicking them")
+                .clicked()
+            {
+                self.sender
+                    .send(Message::ToggleLog)
+                    .expect("Channel closed unexpectedly!");
+            }
+
+            if ui.button(RichText::new("Search").strong()).clicked() {
+                self.sender
+                    .send(Message::ToggleSearch)
+                    .expect("Channel closed unexpectedly!");
+            }
+
+            if ui.button(RichText::new("Settings").strong()).clicked() {
+                self.sender
+                    .send(Message::ToggleSettings)
+                    .expect("Channel closed unexpectedly!");
+            }
+
+            if ui.button(RichText::new("About").strong()).clicked() {
+                self.sender
+                    .send(Message::ToggleAbout)
+                    .expect("Channel closed unexpectedly!");
+            }
         })
         .response
     }
@@ -0,0 +1,325 @@
+use std::sync::mpsc::Sender;
+
+use egui::{ComboBox, Context, DragValue, Id, Slider, Ui, Window};
+
+use crate::{
+    data::Message,
+    git::{DiffAlgorithm, Whitespace},
+    settings::{Appearance, Density, IndentCheck, Palette, Settings, FONT_SIZES},
+};
+
+pub struct SettingsWindow {
+    sender: Sender<Message>,
+    settings: Settings,
+    /// Path of the open repository, whose overrides can be edited.
+    repo: Option<String>,
+}
+
+impl SettingsWindow {
+    pub fn new(
+        sender: Sender<Message>,
+        settings: Settings,
+        repo: Option<String>,
+    ) -> SettingsWindow {
+        SettingsWindow {
+            sender,
+            settings,
+            repo,
+        }
+    }
+}
+
+impl SettingsWindow {
+    pub fn show(&mut self, ctx: &Context) {
+        puffin::profile_function!("SettingsWindow");
+        let original = self.settings.clone();
+        let mut open = true;
+
+        let scope_id = Id::new("settings edit repo");
+        let mut edit_repo = ctx.data(|d| d.get_temp::<bool>(scope_id).unwrap_or(false));
+        let repo = self.repo.clone().filter(|_| edit_repo);
+        // Settings that can be overridden per repository are edited on a copy with the
+        // overrides applied, and written back to wherever they're being edited.
+        let shown = match &repo {
+            Some(path) => self.settings.for_repo(path),
+            None => self.settings.clone(),
+        };
+        let mut edited = shown.clone();
+        let overrides = repo
+            .as_ref()
+            .and_then(|path| self.settings.repos.get(path))
+            .cloned()
+            .unwrap_or_default();
+        let (mut reset_keywords, mut reset_indent_check, mut reset_ruler) = (false, false, false);
+        let mut reset_ignored = false;
+
+        Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(true)
+            .show(ctx, |ui| {
+                if self.repo.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.label("Editing:");
+                        ui.selectable_value(&mut edit_repo, false, "Defaults");
+                        ui.selectable_value(&mut edit_repo, true, "This repository")
+                            .on_hover_text("Keywords, ignored paths, indentation and the column guide can differ per repository");
+                    });
+                    ui.separator();
+                }
+
+                ui.label("External diff tool ({old} and {new} are replaced by file paths):");
+                let entry = ui.text_edit_singleline(&mut self.settings.external_diff_command);
+                super::note_text_field(ui, &entry);
+
+                ui.label("Editor ({path} and {line} are replaced; $EDITOR is used when empty):");
+                let entry = ui.text_edit_singleline(&mut self.settings.editor_command);
+                super::note_text_field(ui, &entry);
+
+                ui.horizontal(|ui| {
+                    ui.label("Keywords highlighted in added lines (separated by spaces):");
+                    if reset_button(ui, repo.is_some(), overrides.highlight_keywords.is_some()) {
+                        reset_keywords = true;
+                    }
+                });
+                let entry = ui.text_edit_singleline(&mut edited.highlight_keywords);
+                super::note_text_field(ui, &entry);
+
+                ui.horizontal(|ui| {
+                    ui.label("Ignored paths, e.g. lock files (globs separated by spaces):");
+                    if reset_button(ui, repo.is_some(), overrides.ignored_paths.is_some()) {
+                        reset_ignored = true;
+                    }
+                });
+                let entry = ui.text_edit_singleline(&mut edited.ignored_paths);
+                super::note_text_field(ui, &entry);
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Appearance:");
+                    for appearance in [Appearance::Dark, Appearance::Light] {
+                        ui.selectable_value(
+                            &mut self.settings.appearance,
+                            appearance,
+                            appearance.label_text(),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let theme = self.settings.theme_mut();
+                    ui.label("Added:");
+                    ui.color_edit_button_srgba(&mut theme.insertion);
+                    ui.label("Removed:");
+                    ui.color_edit_button_srgba(&mut theme.deletion);
+                    ui.label("Unchanged:");
+                    ui.color_edit_button_srgba(&mut theme.context);
+                    ui.label("Hunk headers:");
+                    ui.color_edit_button_srgba(&mut theme.header);
+                    ui.label("Highlights:");
+                    ui.color_edit_button_srgba(&mut theme.highlight);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Palette:");
+                    let appearance = self.settings.appearance;
+                    let theme = self.settings.theme_mut();
+                    let selected = Palette::of(*theme, appearance);
+                    ComboBox::from_id_source("palette")
+                        .selected_text(selected.map_or("Custom".to_string(), |p| p.label_text()))
+                        .show_ui(ui, |ui| {
+                            for palette in [Palette::Standard, Palette::Deuteranopia] {
+                                let label = palette.label_text();
+                                if ui.selectable_label(selected == Some(palette), label).clicked() {
+                                    *theme = palette.theme(appearance);
+                                }
+                            }
+                        });
+                    ui.checkbox(&mut theme.emphasized_origins, "Emphasize + and -");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Density:");
+                    for density in [Density::Compact, Density::Comfortable] {
+                        ui.selectable_value(
+                            &mut self.settings.density,
+                            density,
+                            density.label_text(),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Font size:");
+                    ui.add(
+                        Slider::new(&mut self.settings.font_size, FONT_SIZES)
+                            .step_by(1.0)
+                            .suffix(" pt"),
+                    )
+                    .on_hover_text("Ctrl+= and Ctrl+- zoom the diff, Ctrl+0 resets it");
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Diff algorithm:");
+                    for algorithm in [
+                        DiffAlgorithm::Myers,
+                        DiffAlgorithm::Patience,
+                        DiffAlgorithm::Minimal,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.settings.diff_algorithm,
+                            algorithm,
+                            algorithm.label_text(),
+                        );
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Whitespace:");
+                    for whitespace in [
+                        Whitespace::Compare,
+                        Whitespace::IgnoreAmount,
+                        Whitespace::IgnoreAll,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.settings.whitespace,
+                            whitespace,
+                            whitespace.label_text(),
+                        )
+                        .on_hover_text(whitespace.hover_text());
+                    }
+                });
+                ui.checkbox(
+                    &mut self.settings.include_untracked,
+                    "List untracked files with the unstaged changes",
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Unchanged lines around changes:");
+                    ui.add(DragValue::new(&mut self.settings.context_lines).clamp_range(0..=1000));
+                });
+                ui.horizontal(|ui| {
+                    let mut fold = self.settings.fold_unchanged.is_some();
+                    ui.checkbox(&mut fold, "Fold runs of unchanged lines longer than");
+                    let mut lines = self.settings.fold_unchanged.unwrap_or(10);
+                    ui.add_enabled(fold, DragValue::new(&mut lines).clamp_range(6..=1000));
+                    self.settings.fold_unchanged = fold.then_some(lines);
+                });
+
+                ui.horizontal(|ui| {
+                    ui.label("Expected indentation in added lines:");
+                    for indent_check in [IndentCheck::Off, IndentCheck::Tabs, IndentCheck::Spaces] {
+                        ui.selectable_value(
+                            &mut edited.indent_check,
+                            indent_check,
+                            indent_check.label_text(),
+                        );
+                    }
+                    if reset_button(ui, repo.is_some(), overrides.indent_check.is_some()) {
+                        reset_indent_check = true;
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.settings.show_line_numbers, "Line numbers (L)");
+                    ui.checkbox(&mut self.settings.show_origins, "Origins (O)");
+                    ui.checkbox(&mut self.settings.wrap_lines, "Wrap long lines (W)");
+                });
+                ui.checkbox(&mut self.settings.show_whitespace, "Show spaces and tabs");
+                ui.checkbox(
+                    &mut self.settings.hunk_separators,
+                    "Separate hunks with a gap",
+                );
+                ui.checkbox(
+                    &mut self.settings.context_breadcrumb,
+                    "Show the function of the topmost hunk above the code",
+                );
+                ui.checkbox(
+                    &mut self.settings.detect_moves,
+                    "Point out code moved between files",
+                )
+                .on_hover_text("Loads every file's lines, which can be slow for large changes");
+                ui.horizontal(|ui| {
+                    let mut show_ruler = edited.ruler_column.is_some();
+                    ui.checkbox(&mut show_ruler, "Show a column guide at");
+                    let mut column = edited.ruler_column.unwrap_or(100);
+                    ui.add_enabled(show_ruler, DragValue::new(&mut column).clamp_range(1..=500));
+                    edited.ruler_column = show_ruler.then_some(column);
+                    if reset_button(ui, repo.is_some(), overrides.ruler_column.is_some()) {
+                        reset_ruler = true;
+                    }
+                });
+                ui.checkbox(
+                    &mut self.settings.trim_common_prefix,
+                    "Trim common path prefix in file list",
+                );
+                ui.checkbox(
+                    &mut self.settings.file_tree,
+                    "Group file list by directory",
+                );
+                ui.checkbox(
+                    &mut self.settings.repository_in_title,
+                    "Show the repository and branch in the window title",
+                );
+                ui.checkbox(
+                    &mut self.settings.multi_pane,
+                    "Show a second file pane on wide windows (right-click a file to open it)",
+                );
+            });
+
+        match &repo {
+            Some(path) => {
+                let overrides = self.settings.repos.entry(path.clone()).or_default();
+                if edited.highlight_keywords != shown.highlight_keywords {
+                    overrides.highlight_keywords = Some(edited.highlight_keywords);
+                }
+                if edited.indent_check != shown.indent_check {
+                    overrides.indent_check = Some(edited.indent_check);
+                }
+                if edited.ruler_column != shown.ruler_column {
+                    overrides.ruler_column = Some(edited.ruler_column);
+                }
+                if edited.ignored_paths != shown.ignored_paths {
+                    overrides.ignored_paths = Some(edited.ignored_paths);
+                }
+
+                if reset_keywords {
+                    overrides.highlight_keywords = None;
+                }
+                if reset_indent_check {
+                    overrides.indent_check = None;
+                }
+                if reset_ruler {
+                    overrides.ruler_column = None;
+                }
+                if reset_ignored {
+                    overrides.ignored_paths = None;
+                }
+                if overrides.is_empty() {
+                    self.settings.repos.remove(path);
+                }
+            }
+            None => {
+                self.settings.highlight_keywords = edited.highlight_keywords;
+                self.settings.indent_check = edited.indent_check;
+                self.settings.ruler_column = edited.ruler_column;
+                self.settings.ignored_paths = edited.ignored_paths;
+            }
+        }
+        ctx.data_mut(|d| d.insert_temp(scope_id, edit_repo));
+
+        if self.settings != original {
+            self.sender
+                .send(Message::UpdateSettings(Box::new(self.settings.clone())))
+                .expect("Channel closed unexpectedly!");
+        }
+
+        if !open {
+            self.sender
+                .send(Message::ToggleSettings)
+                .expect("Channel closed unexpectedly!");
+        }
+    }
+}
+
+/// Button dropping a repository's override of a setting, shown while editing the repository.
+fn reset_button(ui: &mut Ui, editing_repo: bool, overridden: bool) -> bool {
+    editing_repo
+        && ui
+            .add_enabled(overridden, egui::Button::new("Reset to default").small())
+            .clicked()
+}
@@ -0,0 +1,278 @@
+use std::ops::Range;
+
+use egui::{
+    pos2,
+    text::{LayoutJob, TAB_SIZE},
+    Align2, Color32, Id, Rect, Response, RichText, ScrollArea, Sense, Stroke, TextFormat, Ui,
+    Widget,
+};
+use regex::Regex;
+
+use crate::{
+    git::{self, Diff, Line, LineKind, SplitRow},
+    search::SearchQuery,
+    settings::Settings,
+    ui::{code::append_line, metrics::DiffMetrics},
+};
+
+/// Shows a diff with the old version of the file on the left and the new one on the right.
+pub struct SplitDiffWidget {
+    diff: Diff,
+    pane: usize,
+    settings: Settings,
+    metrics: DiffMetrics,
+    /// Row of the unified diff to scroll to.
+    scroll_to_row: Option<usize>,
+    search: SearchQuery,
+    /// Digits the line numbers are padded to.
+    line_number_width: usize,
+}
+
+/// One side of the view, as laid out this frame.
+struct Side {
+    old: bool,
+    /// How far the lines are scrolled to the left, each side on its own. The line numbers
+    /// stay in place.
+    scroll: f32,
+}
+
+impl SplitDiffWidget {
+    pub fn new(
+        diff: Diff,
+        pane: usize,
+        settings: Settings,
+        scroll_to_row: Option<usize>,
+        search: SearchQuery,
+    ) -> SplitDiffWidget {
+        SplitDiffWidget {
+            line_number_width: git::line_number_width(&diff.lines),
+            diff,
+            pane,
+            metrics: DiffMetrics::new(settings.density, settings.font_size),
+            settings,
+            scroll_to_row,
+            search,
+        }
+    }
+}
+
+impl SplitDiffWidget {
+    /// Paints one side of a row: its line number and content, in the colors of its kind, with
+    /// the words that changed from the other side set off as in the unified view.
+    /// A `highlighted` side is the counterpart of the hovered one. The matches of `matcher`
+    /// get the background they have in the unified view.
+    fn paint_side(
+        &self,
+        ui: &Ui,
+        rect: Rect,
+        line: Option<usize>,
+        side: &Side,
+        highlighted: bool,
+        matcher: Option<&Regex>,
+    ) {
+        let painter = ui.painter_at(rect);
+        let highlight = ui.visuals().widgets.inactive.weak_bg_fill;
+        let theme = self.settings.theme();
+        let Some(index) = line else {
+            let background = match highlighted {
+                true => highlight,
+                false => theme.placeholder_background(),
+            };
+            painter.rect_filled(rect, 0.0, background);
+            return;
+        };
+        if highlighted {
+            painter.rect_filled(rect, 0.0, highlight);
+        }
+        let line = &self.diff.lines[index];
+
+        let font_id = self.metrics.font_id();
+        let glyph_width = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+        let color = match line.kind() {
+            LineKind::Insertion => theme.insertion,
+            LineKind::Deletion => theme.deletion,
+            _ => theme.context,
+        };
+        let mut left = rect.left() + self.metrics.margin.x;
+        if self.settings.show_line_numbers {
+            let line_no = match side.old {
+                true => line.old_lineno,
+                false => line.new_lineno,
+            };
+            let width = self.line_number_width;
+            let text = line_no.map_or(String::new(), |line_no| format!("{line_no:>width$}"));
+            painter.text(
+                pos2(left, rect.top()),
+                Align2::LEFT_TOP,
+                text,
+                font_id.clone(),
+                Color32::GRAY,
+            );
+            left += (width + 1) as f32 * glyph_width + self.metrics.column_spacing;
+        }
+        let painter =
+            painter.with_clip_rect(Rect::from_x_y_ranges(left..=rect.right(), rect.y_range()));
+
+        let content = line.content.trim_end_matches(['\n', '\r']);
+        let spans = &self.diff.changed_spans;
+        let changed = match spans.binary_search_by_key(&self.diff.line_rows[index], |(row, _)| *row)
+        {
+            Ok(k) => spans[k].1.as_slice(),
+            Err(_) => &[],
+        };
+        let changed_background = match line.kind() {
+            LineKind::Deletion => theme.deleted_words_background(),
+            _ => theme.inserted_words_background(),
+        };
+        let changed = changed
+            .iter()
+            .map(|range| (range.clone(), changed_background));
+        let backgrounds = matcher
+            .iter()
+            .flat_map(|matcher| matcher.find_iter(content))
+            .map(|found| (found.range(), theme.search_match_background()));
+        let backgrounds = changed
+            .chain(backgrounds)
+            .collect::<Vec<(Range<usize>, Color32)>>();
+        let segments = [(0..content.len(), TextFormat::simple(font_id, color))];
+        let mut job = LayoutJob::default();
+        append_line(&mut job, content, &segments, &backgrounds, None);
+        let galley = ui.fonts(|f| f.layout_job(job));
+        painter.galley(pos2(left - side.scroll, rect.top()), galley);
+    }
+
+    /// How far the lines of a side `width` wide are scrolled, after the horizontal scrolling
+    /// done over it if `hovered`. Lines longer than the side would otherwise be cut.
+    fn scroll_side(&self, ui: &Ui, old: bool, width: f32, hovered: bool) -> f32 {
+        let id = scroll_id(&self.diff, old);
+        let scroll = ui.data(|d| d.get_temp::<f32>(id)).unwrap_or(0.0);
+        let delta = match hovered {
+            true => ui.input(|i| i.scroll_delta.x),
+            false => 0.0,
+        };
+        if delta == 0.0 {
+            return scroll;
+        }
+
+        let font_id = self.metrics.font_id();
+        let glyph_width = ui.fonts(|f| f.glyph_width(&font_id, ' '));
+        let glyphs = |line: &Line| {
+            let tabs = line.content.matches('\t').count();
+            line.content.trim_end().chars().count() + tabs * (TAB_SIZE - 1)
+        };
+        let widest = self.diff.lines.iter().map(glyphs).max().unwrap_or(0);
+        let mut text_width = width - 2.0 * self.metrics.margin.x;
+        if self.settings.show_line_numbers {
+            let gutter = self.line_number_width + 1;
+            text_width -= gutter as f32 * glyph_width + self.metrics.column_spacing;
+        }
+        let max_scroll = (widest as f32 * glyph_width - text_width).max(0.0);
+        let scroll = (scroll - delta).clamp(0.0, max_scroll);
+        ui.data_mut(|d| d.insert_temp(id, scroll));
+        scroll
+    }
+}
+
+fn scroll_id(diff: &Diff, old: bool) -> Id {
+    Id::new(("split scroll", diff.file_name(), old))
+}
+
+impl Widget for SplitDiffWidget {
+    fn ui(self, ui: &mut Ui) -> Response {
+        puffin::profile_function!("SplitDiffWidget");
+        if !self.diff.is_loaded() {
+            return ui
+                .horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(RichText::new("Loading diff…").color(Color32::GRAY));
+                })
+                .response;
+        }
+        if self.diff.lines.is_empty() {
+            return ui.label(RichText::new(self.diff.empty_reason()).color(Color32::GRAY));
+        }
+
+        let rows = self.diff.split_rows();
+        let matcher = self.search.matcher().ok().flatten();
+        let row_height = self.metrics.row_height(ui);
+        let mut scroll_area = ScrollArea::vertical()
+            .id_source(("split diff", self.pane))
+            .auto_shrink([false, false]);
+        if let Some(row) = self.scroll_to_row {
+            let target = |split_row: &SplitRow| {
+                let header_row = split_row
+                    .header
+                    .and_then(|header| self.diff.header_indices.get(header));
+                let line_row = (split_row.old)
+                    .or(split_row.new)
+                    .and_then(|line| self.diff.line_rows.get(line));
+                header_row
+                    .or(line_row)
+                    .is_some_and(|&unified| unified >= row)
+            };
+            let split_row = rows.iter().position(target).unwrap_or(0);
+            scroll_area = scroll_area.vertical_scroll_offset(split_row as f32 * row_height);
+        }
+
+        ui.vertical(|ui| {
+            if let Some((old, new)) = self.diff.mode_change() {
+                ui.label(
+                    RichText::new(format!("Mode changed {:o} → {:o}", old, new))
+                        .color(Color32::GRAY),
+                );
+            }
+            ui.spacing_mut().item_spacing.y = 0.0;
+            scroll_area.show_rows(ui, row_height, rows.len(), |ui, row_range| {
+                let (rect, _) = ui.allocate_exact_size(
+                    egui::vec2(
+                        ui.available_width(),
+                        row_range.len() as f32 * row_height + 2.0 * self.metrics.margin.y,
+                    ),
+                    Sense::hover(),
+                );
+                let middle = rect.center().x;
+                // The side hovered, by its row, highlights the other side of the same row:
+                // the line it was changed into, or the gap where there is none.
+                let hovered =
+                    (ui.ctx().pointer_hover_pos()).filter(|_| ui.rect_contains_pointer(rect));
+                let hovered_row = hovered.map(|pos| {
+                    let offset = pos.y - rect.top() - self.metrics.margin.y;
+                    ((offset / row_height).max(0.0) as usize, pos.x < middle)
+                });
+                let [old_side, new_side] = [true, false].map(|old| {
+                    let hovered = hovered.is_some_and(|pos| (pos.x < middle) == old);
+                    let scroll = self.scroll_side(ui, old, rect.width() / 2.0, hovered);
+                    Side { old, scroll }
+                });
+                for (i, row) in rows[row_range.clone()].iter().enumerate() {
+                    let top = rect.top() + self.metrics.margin.y + i as f32 * row_height;
+                    if let Some(header) = row.header.and_then(|i| self.diff.headers.get(i)) {
+                        ui.painter().text(
+                            pos2(rect.left() + self.metrics.margin.x, top),
+                            Align2::LEFT_TOP,
+                            header.content.trim_end(),
+                            self.metrics.font_id(),
+                            self.settings.theme().header,
+                        );
+                        continue;
+                    }
+                    let old = Rect::from_x_y_ranges(rect.left()..=middle, top..=top + row_height);
+                    let new = Rect::from_x_y_ranges(middle..=rect.right(), top..=top + row_height);
+                    let (old_highlighted, new_highlighted) = match hovered_row {
+                        Some((hovered, on_old_side)) if hovered == i => (!on_old_side, on_old_side),
+                        _ => (false, false),
+                    };
+                    let matcher = matcher.as_ref();
+                    self.paint_side(ui, old, row.old, &old_side, old_highlighted, matcher);
+                    self.paint_side(ui, new, row.new, &new_side, new_highlighted, matcher);
+                }
+                ui.painter().vline(
+                    middle,
+                    rect.y_range(),
+                    Stroke::new(1.0, ui.visuals().widgets.noninteractive.bg_stroke.color),
+                );
+            });
+        })
+        .response
+    }
+}
@@ -1,14 +1,18 @@
 use egui::{Color32, Response, RichText, Ui, Widget};
 
-use crate::git::Stats;
+use crate::{git::Stats, settings::Theme};
+
+/// Color untracked files are pointed out in.
+pub const UNTRACKED_COLOR: Color32 = Color32::from_rgb(120, 170, 230);
 
 pub struct StatsWidget {
     stats: Stats,
+    theme: Theme,
 }
 
 impl StatsWidget {
-    pub fn new(stats: Stats) -> StatsWidget {
-        StatsWidget { stats }
+    pub fn new(stats: Stats, theme: Theme) -> StatsWidget {
+        StatsWidget { stats, theme }
     }
 }
 
@@ -20,27 +24,39 @@ impl Widget for StatsWidget {
         let deletion_count = self.stats.deletions;
 
         let files_richtext = match file_changed_count {
-            1 => {
-                RichText::new(format!("{} file changed,", file_changed_count)).color(Color32::WHITE)
-            }
-            _ => RichText::new(format!("{} files changed,", file_changed_count))
-                .color(Color32::WHITE),
+            1 => RichText::new(format!("{} file changed,", file_changed_count)).strong(),
+            _ => RichText::new(format!("{} files changed,", file_changed_count)).strong(),
         };
 
         let insertions_richtext = match insertion_count {
-            1 => RichText::new(format!("{} insertion(+),", insertion_count)).color(Color32::GREEN),
-            _ => RichText::new(format!("{} insertions(+),", insertion_count)).color(Color32::GREEN),
+            1 => RichText::new(format!("{} insertion(+),", insertion_count)),
+            _ => RichText::new(format!("{} insertions(+),", insertion_count)),
         };
 
         let deletions_richtext = match deletion_count {
-            1 => RichText::new(format!("{} deletion(-)", deletion_count)).color(Color32::RED),
-            _ => RichText::new(format!("{} deletions(-)", deletion_count)).color(Color32::RED),
+            1 => RichText::new(format!("{} deletion(-)", deletion_count)),
+            _ => RichText::new(format!("{} deletions(-)", deletion_count)),
+        };
+
+        let untracked_text = match self.stats.untracked_files {
+            0 => None,
+            1 => Some(format!(
+                "and 1 untracked file (+{})",
+                self.stats.untracked_insertions
+            )),
+            count => Some(format!(
+                "and {} untracked files (+{})",
+                count, self.stats.untracked_insertions
+            )),
         };
 
         ui.horizontal(|ui| {
             ui.label(files_richtext);
-            ui.label(insertions_richtext);
-            ui.label(deletions_richtext);
+            ui.label(insertions_richtext.color(self.theme.insertion));
+            ui.label(deletions_richtext.color(self.theme.deletion));
+            if let Some(text) = untracked_text {
+                ui.label(RichText::new(text).color(UNTRACKED_COLOR));
+            }
         })
         .response
     }
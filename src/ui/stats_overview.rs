@@ -0,0 +1,99 @@
+use std::sync::mpsc::Sender;
+
+use egui::{vec2, Grid, Rect, Response, RichText, ScrollArea, Sense, Ui, Widget};
+
+use crate::{
+    data::{DiffData, Message},
+    settings::Theme,
+};
+
+/// Widest a file's change bar can get, for the file with the most changed lines.
+const MAX_BAR_WIDTH: f32 = 200.0;
+
+/// `git diff --stat` style summary of every file, without rendering any diffs.
+pub struct StatsOverviewWidget {
+    diff_data: DiffData,
+    theme: Theme,
+    sender: Sender<Message>,
+}
+
+impl StatsOverviewWidget {
+    pub fn new(diff_data: DiffData, theme: Theme, sender: Sender<Message>) -> StatsOverviewWidget {
+        StatsOverviewWidget {
+            diff_data,
+            theme,
+            sender,
+        }
+    }
+}
+
+impl Widget for StatsOverviewWidget {
+    fn ui(self, ui: &mut Ui) -> Response {
+        puffin::profile_function!("StatsOverviewWidget");
+        let most_changes = self
+            .diff_data
+            .diffs
+            .iter()
+            .map(|diff| diff.insertions() + diff.deletions())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        ScrollArea::vertical()
+            .id_source("stats overview")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                Grid::new("stats overview grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (i, diff) in self.diff_data.diffs.iter().enumerate() {
+                            if ui
+                                .link(RichText::new(diff.file_name()).strong())
+                                .on_hover_text("Show this file's diff")
+                                .clicked()
+                            {
+                                self.sender
+                                    .send(Message::ChangeSelectedDiffIndex(i))
+                                    .expect("Channel closed unexpectedly!");
+                                self.sender
+                                    .send(Message::ToggleStatsOnly)
+                                    .expect("Channel closed unexpectedly!");
+                            }
+
+                            let (insertions, deletions) = (diff.insertions(), diff.deletions());
+                            ui.label(
+                                RichText::new(format!("+{}", insertions))
+                                    .color(self.theme.insertion),
+                            );
+                            ui.label(
+                                RichText::new(format!("-{}", deletions)).color(self.theme.deletion),
+                            );
+
+                            let scale = MAX_BAR_WIDTH / most_changes as f32;
+                            let (rect, _) = ui.allocate_exact_size(
+                                vec2(MAX_BAR_WIDTH, ui.spacing().interact_size.y * 0.5),
+                                Sense::hover(),
+                            );
+                            let split = rect.left() + insertions as f32 * scale;
+                            let painter = ui.painter();
+                            painter.rect_filled(
+                                Rect::from_x_y_ranges(rect.left()..=split, rect.y_range()),
+                                0.0,
+                                self.theme.insertion,
+                            );
+                            painter.rect_filled(
+                                Rect::from_x_y_ranges(
+                                    split..=split + deletions as f32 * scale,
+                                    rect.y_range(),
+                                ),
+                                0.0,
+                                self.theme.deletion,
+                            );
+                            ui.end_row();
+                        }
+                    })
+            })
+            .inner
+            .response
+    }
+}
@@ -0,0 +1,113 @@
+use std::ops::Range;
+
+/// Lines with more tokens than this on both sides multiplied are compared as a whole, as
+/// comparing them token by token gets slow.
+const MAX_COMPARISONS: usize = 40_000;
+
+/// Byte ranges of the words that differ between a removed line and the line added in its place.
+#[derive(Debug, PartialEq)]
+pub struct ChangedSpans {
+    pub old: Vec<Range<usize>>,
+    pub new: Vec<Range<usize>>,
+}
+
+/// Byte ranges of the parts of a removed line and the line added in its place that differ,
+/// by word. Returns `None` when the lines have nothing but whitespace in common, as pointing
+/// out every word of them would not tell anything the line colors don't.
+pub fn changed_spans(old: &str, new: &str) -> Option<ChangedSpans> {
+    let old_tokens = tokens(old);
+    let new_tokens = tokens(new);
+    if old_tokens.len() * new_tokens.len() > MAX_COMPARISONS {
+        return None;
+    }
+
+    // Lengths of the longest common subsequences of the token suffixes.
+    let text = |line: &str, range: &Range<usize>| line[range.clone()].to_string();
+    let old_texts = old_tokens.iter().map(|t| text(old, t)).collect::<Vec<_>>();
+    let new_texts = new_tokens.iter().map(|t| text(new, t)).collect::<Vec<_>>();
+    let mut lengths = vec![vec![0usize; new_texts.len() + 1]; old_texts.len() + 1];
+    for i in (0..old_texts.len()).rev() {
+        for j in (0..new_texts.len()).rev() {
+            lengths[i][j] = match old_texts[i] == new_texts[j] {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut old_changed = vec![true; old_texts.len()];
+    let mut new_changed = vec![true; new_texts.len()];
+    let mut common = false;
+    while i < old_texts.len() && j < new_texts.len() {
+        if old_texts[i] == new_texts[j] {
+            old_changed[i] = false;
+            new_changed[j] = false;
+            common |= !old_texts[i].trim().is_empty();
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    if !common {
+        return None;
+    }
+
+    Some(ChangedSpans {
+        old: merge(&old_tokens, &old_changed),
+        new: merge(&new_tokens, &new_changed),
+    })
+}
+
+/// Splits a line into runs of word characters, runs of whitespace and single other characters.
+fn tokens(line: &str) -> Vec<Range<usize>> {
+    let class = |c: char| match c {
+        c if c.is_alphanumeric() || c == '_' => 0,
+        c if c.is_whitespace() => 1,
+        _ => 2,
+    };
+
+    let mut tokens: Vec<Range<usize>> = Vec::new();
+    let mut previous = None;
+    for (start, c) in line.char_indices() {
+        let end = start + c.len_utf8();
+        match tokens.last_mut() {
+            Some(last) if previous == Some(class(c)) && class(c) != 2 => last.end = end,
+            _ => tokens.push(start..end),
+        }
+        previous = Some(class(c));
+    }
+    tokens
+}
+
+/// Joins the changed tokens next to each other into one range.
+fn merge(tokens: &[Range<usize>], changed: &[bool]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (token, _) in tokens.iter().zip(changed).filter(|(_, &changed)| changed) {
+        match ranges.last_mut() {
+            Some(last) if last.end == token.start => last.end = token.end,
+            _ => ranges.push(token.clone()),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_words_are_found() {
+        let spans = changed_spans("let x = foo(1);", "let y = bar(1, 2);").unwrap();
+        assert_eq!(spans.old, [4..5, 8..11]);
+        assert_eq!(spans.new, [4..5, 8..11, 13..16]);
+
+        // Nothing but a space in common.
+        assert_eq!(changed_spans("one two", "three four"), None);
+        let spans = changed_spans("same", "same").unwrap();
+        assert!(spans.old.is_empty() && spans.new.is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+use std::ops::Range;
+
+use egui::text::TAB_SIZE;
+
+/// Where each row of a text starts once its long lines are wrapped, counted in rows of the
+/// screen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WrappedRows {
+    /// The screen row each row starts at, followed by the number of screen rows.
+    starts: Vec<usize>,
+    /// Characters that fit in a screen row, if lines are wrapped at all.
+    columns: Option<usize>,
+    tab_marks: bool,
+    /// Rows with a blank screen row left above them; see [`WrappedRows::with_gaps`].
+    gaps: Vec<usize>,
+}
+
+impl WrappedRows {
+    /// Wraps the lines of `text` after `columns` characters, the way egui lays out text that
+    /// may break anywhere: a character that doesn't fit anymore starts the next screen row.
+    /// Tabs take up [`TAB_SIZE`] columns, or with `tab_marks`, one for the arrow marking them
+    /// and a gap that can't be broken after it.
+    pub fn new(text: &str, columns: usize, tab_marks: bool) -> WrappedRows {
+        let columns = columns.max(TAB_SIZE);
+        let mut starts = vec![0];
+        let mut screen_rows = 0;
+        for line in text.lines() {
+            screen_rows += wrap_line(line, columns, tab_marks).0;
+            starts.push(screen_rows);
+        }
+        WrappedRows {
+            starts,
+            columns: Some(columns),
+            tab_marks,
+            gaps: Vec::new(),
+        }
+    }
+
+    /// `rows` rows that take up one screen row each.
+    pub fn unwrapped(rows: usize) -> WrappedRows {
+        WrappedRows {
+            starts: (0..=rows).collect(),
+            columns: None,
+            tab_marks: false,
+            gaps: Vec::new(),
+        }
+    }
+
+    /// Leaves a blank screen row above each of `rows` but the first. It counts as a screen row
+    /// the row above wraps onto, so the columns next to the code are padded for it too.
+    pub fn with_gaps(mut self, rows: &[usize]) -> WrappedRows {
+        let mut gaps = (rows.iter().copied())
+            .filter(|&row| row > 0 && row < self.row_count())
+            .collect::<Vec<usize>>();
+        gaps.sort_unstable();
+        gaps.dedup();
+        for (row, start) in self.starts.iter_mut().enumerate() {
+            *start += gaps.partition_point(|&gap| gap <= row);
+        }
+        self.gaps = gaps;
+        self
+    }
+
+    /// Characters that fit in a screen row, if lines are wrapped at all.
+    pub fn columns(&self) -> Option<usize> {
+        self.columns
+    }
+
+    /// Rows with a blank screen row above them, in order.
+    pub fn gaps(&self) -> &[usize] {
+        &self.gaps
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    pub fn screen_rows(&self) -> usize {
+        self.starts[self.starts.len() - 1]
+    }
+
+    /// The screen row `row` starts at, or the end for rows past the last.
+    pub fn start(&self, row: usize) -> usize {
+        self.starts[row.min(self.starts.len() - 1)]
+    }
+
+    /// The row shown at `screen_row`, or the last one past the end.
+    pub fn row_at(&self, screen_row: usize) -> usize {
+        let row = self.starts.partition_point(|&start| start <= screen_row);
+        row.saturating_sub(1)
+            .min(self.starts.len().saturating_sub(2))
+    }
+
+    /// The last screen row the text of `row` is shown in, above the gap after it if any.
+    pub fn last(&self, row: usize) -> usize {
+        let gap = self.gaps.binary_search(&(row + 1)).is_ok();
+        self.start(row + 1) - 1 - usize::from(gap)
+    }
+
+    /// The screen rows each of `rows` wraps onto after its first.
+    pub fn continuations(&self, rows: Range<usize>) -> Vec<usize> {
+        rows.map(|row| (self.start(row + 1) - self.start(row)).saturating_sub(1))
+            .collect()
+    }
+
+    /// The column `line` ends at in the last screen row it wraps onto.
+    pub fn end_column(&self, line: &str) -> usize {
+        match self.columns {
+            Some(columns) => wrap_line(line, columns, self.tab_marks).1,
+            None => line.chars().count(),
+        }
+    }
+}
+
+/// The screen rows `line` takes up, and the columns taken up in the last of them.
+fn wrap_line(line: &str, columns: usize, tab_marks: bool) -> (usize, usize) {
+    let mut rows = 1;
+    let mut width = 0;
+    for c in line.chars() {
+        // What has to fit in the row, and how far it moves on the next character.
+        let (glyph_width, advance) = match c {
+            '\t' if tab_marks => (1, TAB_SIZE),
+            '\t' => (TAB_SIZE, TAB_SIZE),
+            _ => (1, 1),
+        };
+        if width > 0 && width + glyph_width > columns {
+            rows += 1;
+            width = 0;
+        }
+        width += advance;
+    }
+    (rows, width)
+}
+
+/// The `lines` of a column shown next to wrapped text, joined with a blank line for every
+/// screen row their row wraps onto, so that both line up.
+pub fn pad_continuations(lines: &[&str], continuations: &[usize]) -> String {
+    let mut padded = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        padded.push(*line);
+        let blank_rows = continuations.get(i).copied().unwrap_or(0);
+        padded.extend(std::iter::repeat_n("", blank_rows));
+    }
+    padded.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_line_up_with_wrapped_code() {
+        let code = "short\n0123456789abcdefghij\n\n\tindented by a tab\n0123456789";
+        let wrapped = WrappedRows::new(code, 10, false);
+        // The tab takes up 4 columns, which wraps its line onto a third screen row.
+        assert_eq!(wrapped.starts, [0, 1, 3, 4, 7, 8]);
+        assert_eq!(wrapped.screen_rows(), 8);
+        assert_eq!(wrapped.row_at(2), 1);
+        assert_eq!(wrapped.row_at(6), 3);
+        assert_eq!(wrapped.row_at(7), 4);
+        assert_eq!(wrapped.row_at(100), 4);
+        assert_eq!(wrapped.end_column("\tindented by a tab"), 1);
+        assert_eq!(WrappedRows::unwrapped(5).row_at(3), 3);
+        // A marked tab is broken after like the arrow it's shown as.
+        let tab_marks = WrappedRows::new("", 10, true);
+        assert_eq!(wrapped.end_column("0123456\tx"), 5);
+        assert_eq!(tab_marks.end_column("0123456\tx"), 1);
+
+        // The line numbers of the rows from the second on, each in the screen row its
+        // code starts at.
+        let continuations = wrapped.continuations(1..5);
+        assert_eq!(continuations, [1, 0, 2, 0]);
+        let numbers = pad_continuations(&["2", "3", "4", "5"], &continuations);
+        let number_rows = numbers.split('\n').collect::<Vec<_>>();
+        assert_eq!(number_rows, ["2", "", "3", "4", "", "", "5"]);
+        for (line, number) in ["2", "3", "4", "5"].iter().enumerate() {
+            let screen_row = wrapped.start(line + 1) - wrapped.start(1);
+            assert_eq!(number_rows[screen_row], *number);
+        }
+    }
+
+    #[test]
+    fn gaps_are_left_above_rows() {
+        let wrapped = WrappedRows::new("header\n0123456789abc\nheader\nline", 10, false);
+        let gapped = wrapped.with_gaps(&[0, 2]);
+        // Nothing is left above the first row.
+        assert_eq!(gapped.starts, [0, 1, 4, 5, 6]);
+        assert_eq!(gapped.gaps(), [2]);
+        assert_eq!(gapped.continuations(0..4), [0, 2, 0, 0]);
+        assert_eq!(gapped.last(1), 2);
+        assert_eq!(gapped.last(2), 4);
+        assert_eq!(gapped.row_at(3), 1);
+        assert_eq!(gapped.row_at(4), 2);
+    }
+}